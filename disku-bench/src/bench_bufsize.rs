@@ -156,11 +156,7 @@ fn main() {
         );
     }
 
-    // Peak RSS
-    if let Some(rss) = get_peak_rss() {
-        println!();
-        println!("peak RSS: {}", format_bytes(rss));
-    }
+    print_resource_usage();
 
     println!();
     println!("done.");
@@ -118,11 +118,7 @@ fn main() {
         println!("verdict: counting adds measurable overhead ({:.1}%)", counting_overhead_pct);
     }
 
-    // Peak RSS
-    if let Some(rss) = get_peak_rss() {
-        println!();
-        println!("peak RSS: {}", format_bytes(rss));
-    }
+    print_resource_usage();
 
     println!();
     println!("done.");
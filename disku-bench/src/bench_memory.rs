@@ -0,0 +1,140 @@
+//! Benchmark: memory footprint of a scanned tree.
+//!
+//! Scans one path and reports how many nodes the tree holds, the process's
+//! peak RSS, and two bytes-per-node figures: the RSS growth across the scan
+//! (which includes the scanner's transient buffers) and an estimate of what
+//! the finished tree itself occupies. Run it before and after a change to
+//! the tree representation to compare.
+//!
+//! Usage:
+//!   bench_memory [OPTIONS] [PATH]
+//!
+//! Options:
+//!   --intern-names   Share repeated directory names (ScanOptions::intern_names)
+//!
+//! Peak RSS only ever grows, so each configuration needs its own process run.
+
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::sync::atomic::Ordering;
+
+use disku_bench::bench_utils::*;
+use disku_core::scanner::{ScanOptions, ScanProgress};
+use disku_core::tree::FileNode;
+
+struct Args {
+    path: std::path::PathBuf,
+    intern_names: bool,
+}
+
+fn main() {
+    let args = parse_args();
+    let options = ScanOptions {
+        intern_names: args.intern_names,
+        ..ScanOptions::default()
+    };
+
+    println!("=== tree memory footprint ===");
+    println!("target:        {}", args.path.display());
+    println!("intern names:  {}", args.intern_names);
+    println!("node size:     {} B (size_of::<FileNode>())", size_of::<FileNode>());
+    println!();
+
+    let rss_before = resource_usage().map(|u| u.peak_rss);
+    let progress = ScanProgress::new();
+    let start = std::time::Instant::now();
+    let tree = scan(&args.path, &progress, &options);
+    let elapsed = start.elapsed().as_secs_f64();
+    let rss_after = resource_usage().map(|u| u.peak_rss);
+
+    let nodes = tree.total_nodes();
+    let files = progress.files_scanned.load(Ordering::Relaxed);
+    let dirs = progress.dirs_scanned.load(Ordering::Relaxed);
+    let estimate = estimate_tree_bytes(&tree);
+
+    println!("scan:          {:.3}s | {} files | {} dirs", elapsed, files, dirs);
+    println!("tree nodes:    {}", nodes);
+    println!();
+    println!(
+        "estimated tree:  {} ({:.1} B/node)",
+        format_bytes(estimate),
+        estimate as f64 / nodes as f64
+    );
+    match (rss_before, rss_after) {
+        (Some(before), Some(after)) => {
+            let grown = after.saturating_sub(before);
+            println!(
+                "peak RSS:        {} (+{} during scan, {:.1} B/node)",
+                format_bytes(after),
+                format_bytes(grown),
+                grown as f64 / nodes as f64
+            );
+        }
+        _ => println!("peak RSS:        unavailable"),
+    }
+
+    std::hint::black_box(&tree);
+}
+
+/// Scan with the scanner the TUI would use on this platform.
+fn scan(path: &std::path::Path, progress: &ScanProgress, options: &ScanOptions) -> FileNode {
+    #[cfg(target_os = "macos")]
+    {
+        disku_core::mac_scanner::scan_bulk_with_options(path, progress, options)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        disku_core::scanner::scan_with_options(path, progress, options)
+    }
+}
+
+/// Heap and inline bytes the finished tree holds: every node slot the child
+/// vectors have allocated (including unused capacity) plus each distinct name
+/// allocation with its `Arc` counts. Allocator overhead isn't included.
+fn estimate_tree_bytes(root: &FileNode) -> u64 {
+    const ARC_HEADER: usize = 2 * size_of::<usize>();
+
+    let mut bytes = size_of::<FileNode>();
+    let mut names: HashSet<*const u8> = HashSet::new();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if names.insert(node.name.as_ptr()) {
+            bytes += ARC_HEADER + node.name.len();
+        }
+        bytes += node.children.capacity() * size_of::<FileNode>();
+        stack.extend(node.children.iter());
+    }
+    bytes as u64
+}
+
+fn parse_args() -> Args {
+    let mut path: Option<std::path::PathBuf> = None;
+    let mut intern_names = false;
+
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--intern-names" => intern_names = true,
+            other if other.starts_with('-') => {
+                eprintln!("error: unknown option: {}", other);
+                eprintln!("usage: bench_memory [--intern-names] [PATH]");
+                std::process::exit(1);
+            }
+            _ => {
+                path = Some(std::path::PathBuf::from(arg));
+            }
+        }
+    }
+
+    let path = path.unwrap_or_else(|| {
+        std::env::var("HOME")
+            .map(std::path::PathBuf::from)
+            .unwrap_or_else(|_| std::path::PathBuf::from("/"))
+    });
+
+    if !path.is_dir() {
+        eprintln!("error: not a directory: {}", path.display());
+        std::process::exit(1);
+    }
+
+    Args { path, intern_names }
+}
@@ -1,3 +1,5 @@
+pub use disku_core::utils::resource_usage;
+
 pub fn format_bytes(bytes: u64) -> String {
     const KB: u64 = 1024;
     const MB: u64 = 1024 * KB;
@@ -25,19 +27,16 @@ pub fn fmax(v: &[f64]) -> f64 {
     v.iter().cloned().fold(f64::NEG_INFINITY, f64::max)
 }
 
-pub fn get_peak_rss() -> Option<u64> {
-    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
-    let ret = unsafe { libc::getrusage(libc::RUSAGE_SELF, &mut usage) };
-    if ret == 0 {
-        #[cfg(target_os = "macos")]
-        {
-            Some(usage.ru_maxrss as u64)
-        }
-        #[cfg(not(target_os = "macos"))]
-        {
-            Some(usage.ru_maxrss as u64 * 1024)
-        }
-    } else {
-        None
+/// Print the process's peak RSS and the CPU time it has used, for the end
+/// of a benchmark.
+pub fn print_resource_usage() {
+    if let Some(usage) = resource_usage() {
+        println!();
+        println!("peak RSS: {}", format_bytes(usage.peak_rss));
+        println!(
+            "cpu time: {:.3}s user, {:.3}s system",
+            usage.user.as_secs_f64(),
+            usage.system.as_secs_f64()
+        );
     }
 }
@@ -7,6 +7,10 @@
 //!   -n, --iterations N   Number of benchmark runs (default: 5)
 //!   --single             Single run mode (for use with `leaks --atExit`)
 //!   --compare            Also run jwalk scanner and compare results
+//!   --intern             Intern repeated directory names (compare peak RSS)
+//!   --spindle, --no-spindle
+//!                        Force the spindle-friendly read mode on or off
+//!                        (default: on for rotational disks)
 
 use disku_bench::bench_utils::*;
 
@@ -18,6 +22,8 @@ fn main() {
     println!("target:     {}", args.path.display());
     println!("iterations: {}", args.iterations);
     println!("compare:    {}", args.compare);
+    println!("intern:     {}", args.options.intern_names);
+    println!("spindle:    {:?}", args.options.spindle_friendly);
     println!();
 
     // Collect per-run results
@@ -28,10 +34,12 @@ fn main() {
             println!("--- run {}/{} ---", i + 1, args.iterations);
         }
 
-        let result = run_mac_scan(&args.path);
+        let result = run_mac_scan(&args.path, &args.options);
         println!(
-            "  time: {:.3}s | files: {} | dirs: {} | errors: {} | size: {} | {:.0} files/sec",
+            "  time: {:.3}s | cpu: {:.3}s | files: {} | dirs: {} | errors: {} | size: {} | \
+             {:.0} files/sec",
             result.wall_secs,
+            result.cpu_secs,
             result.file_count,
             result.dir_count,
             result.errors,
@@ -50,16 +58,15 @@ fn main() {
         print_consistency(&results);
     }
 
-    // Peak RSS
-    if let Some(rss) = get_peak_rss() {
-        println!("peak RSS:   {}", format_bytes(rss));
+    if let Some(usage) = resource_usage() {
+        println!("peak RSS:   {}", format_bytes(usage.peak_rss));
     }
 
     // Cross-scanner comparison
     if args.compare {
         println!();
         println!("=== jwalk comparison ===");
-        let jwalk_result = run_jwalk_scan(&args.path);
+        let jwalk_result = run_jwalk_scan(&args.path, &args.options);
         let mac_best = results
             .iter()
             .map(|r| r.wall_secs)
@@ -144,6 +151,7 @@ struct Args {
     path: std::path::PathBuf,
     iterations: usize,
     compare: bool,
+    options: disku_core::scanner::ScanOptions,
 }
 
 fn parse_args() -> Args {
@@ -152,6 +160,7 @@ fn parse_args() -> Args {
     let mut iterations: usize = 5;
     let mut single = false;
     let mut compare = false;
+    let mut options = disku_core::scanner::ScanOptions::default();
 
     while let Some(arg) = args_iter.next() {
         match arg.as_str() {
@@ -168,9 +177,12 @@ fn parse_args() -> Args {
             }
             "--single" => single = true,
             "--compare" => compare = true,
+            "--intern" => options.intern_names = true,
+            "--spindle" => options.spindle_friendly = Some(true),
+            "--no-spindle" => options.spindle_friendly = Some(false),
             other if other.starts_with('-') => {
                 eprintln!("error: unknown option: {}", other);
-                eprintln!("usage: bench_scan [--iterations N] [--single] [--compare] [PATH]");
+                eprintln!("usage: bench_scan [--iterations N] [--single] [--compare] [--intern] [--spindle | --no-spindle] [PATH]");
                 std::process::exit(1);
             }
             _ => {
@@ -198,6 +210,7 @@ fn parse_args() -> Args {
         path,
         iterations,
         compare,
+        options,
     }
 }
 
@@ -205,6 +218,9 @@ fn parse_args() -> Args {
 
 struct RunResult {
     wall_secs: f64,
+    /// User and system CPU time over the run; well under `wall_secs` when
+    /// the scan waited on the disk.
+    cpu_secs: f64,
     file_count: u64,
     dir_count: u64,
     total_size: u64,
@@ -215,13 +231,15 @@ struct RunResult {
 // -- Scanner runners --
 
 #[cfg(target_os = "macos")]
-fn run_mac_scan(path: &std::path::Path) -> RunResult {
+fn run_mac_scan(path: &std::path::Path, options: &disku_core::scanner::ScanOptions) -> RunResult {
     use std::sync::atomic::Ordering;
 
     let progress = disku_core::scanner::ScanProgress::new();
+    let usage = resource_usage();
     let start = std::time::Instant::now();
-    let tree = disku_core::mac_scanner::scan_bulk(path, &progress);
+    let tree = disku_core::mac_scanner::scan_bulk_with_options(path, &progress, options);
     let wall_secs = start.elapsed().as_secs_f64();
+    let cpu_secs = cpu_secs_since(usage);
 
     let files_scanned = progress.files_scanned.load(Ordering::Relaxed);
     let errors = progress.errors.load(Ordering::Relaxed);
@@ -230,6 +248,7 @@ fn run_mac_scan(path: &std::path::Path) -> RunResult {
 
     RunResult {
         wall_secs,
+        cpu_secs,
         file_count,
         dir_count,
         total_size,
@@ -239,13 +258,15 @@ fn run_mac_scan(path: &std::path::Path) -> RunResult {
 }
 
 #[cfg(target_os = "macos")]
-fn run_jwalk_scan(path: &std::path::Path) -> RunResult {
+fn run_jwalk_scan(path: &std::path::Path, options: &disku_core::scanner::ScanOptions) -> RunResult {
     use std::sync::atomic::Ordering;
 
     let progress = disku_core::scanner::ScanProgress::new();
+    let usage = resource_usage();
     let start = std::time::Instant::now();
-    let tree = disku_core::scanner::scan(path, &progress);
+    let tree = disku_core::scanner::scan_with_options(path, &progress, options);
     let wall_secs = start.elapsed().as_secs_f64();
+    let cpu_secs = cpu_secs_since(usage);
 
     let files_scanned = progress.files_scanned.load(Ordering::Relaxed);
     let errors = progress.errors.load(Ordering::Relaxed);
@@ -254,6 +275,7 @@ fn run_jwalk_scan(path: &std::path::Path) -> RunResult {
 
     RunResult {
         wall_secs,
+        cpu_secs,
         file_count,
         dir_count,
         total_size,
@@ -262,6 +284,15 @@ fn run_jwalk_scan(path: &std::path::Path) -> RunResult {
     }
 }
 
+/// User and system CPU time since `before` was read, in seconds.
+#[cfg(target_os = "macos")]
+fn cpu_secs_since(before: Option<disku_core::utils::ResourceUsage>) -> f64 {
+    match (before, resource_usage()) {
+        (Some(before), Some(after)) => after.since(&before).cpu().as_secs_f64(),
+        _ => 0.0,
+    }
+}
+
 // -- Tree stats --
 
 fn tree_stats(node: &disku_core::tree::FileNode) -> (u64, u64, u64) {
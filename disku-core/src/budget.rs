@@ -0,0 +1,296 @@
+//! Time-limited scanning, for a quick overview of a huge tree.
+//!
+//! [`scan_with_budget`] reads directories best-first rather than walking the
+//! whole tree: each directory read hands an estimated size to every
+//! subdirectory it finds, and the subdirectories with the largest estimates
+//! are read next. Once the time limit passes, no more directories are read;
+//! the ones still waiting stay empty and are flagged
+//! [`FileNode::unexpanded`], so sizes above them are lower bounds.
+//!
+//! With no real sizes to go on until a directory is read, the estimate is a
+//! uniform split: a directory's estimate divided evenly among its
+//! subdirectories. Shallow directories and those with few siblings therefore
+//! come first, and a lone big subtree (`/home/<user>`) is followed down
+//! before a wide one (`/usr/share/locale`) is fanned out.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use rayon::prelude::*;
+
+use crate::scanner::{
+    allocated_size, collapse_bundles, collapse_trash, file_id_of, in_scan_pool, is_compressed,
+    mtime_of, out_of_descriptors, scan_file, DeviceBounds, OpenLimit, ProgressSink, ScanOptions,
+};
+use crate::tree::{FileNode, NameInterner};
+use crate::utils::root_name;
+
+/// Estimated size of the scan root. Only the ratios between estimates
+/// matter, so any large number works.
+const ROOT_ESTIMATE: u64 = 1 << 50;
+
+/// Directories read per round, per worker thread. Larger rounds keep every
+/// thread busy but let the scan overshoot its deadline by more.
+const BATCH_PER_THREAD: usize = 4;
+
+/// A directory waiting to be read, ordered by estimate and then by discovery
+/// so ties are read breadth-first.
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+struct Pending {
+    estimate: u64,
+    order: Reverse<u64>,
+    slot: usize,
+    path: PathBuf,
+}
+
+/// One node of the tree as it's being built, linked to its parent by index.
+struct Slot {
+    node: FileNode,
+    children: Vec<usize>,
+}
+
+/// One entry of a directory listing.
+struct Listed {
+    name: String,
+    is_dir: bool,
+    size: u64,
+    allocated: u64,
+    compressed: bool,
+    mtime: u32,
+    file_id: Option<u64>,
+}
+
+/// Scan `root` for at most roughly `limit`, reading the likeliest-big
+/// directories first (see the module docs). The scan can run past `limit` by
+/// one round of directory reads. Of the [`ScanOptions`], `round_to_blocks` is
+/// not applied.
+pub fn scan_with_budget<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+    limit: Duration,
+) -> FileNode {
+    if let Some(node) = scan_file(root, progress) {
+        return node;
+    }
+    in_scan_pool(root, options, || scan_in_pool(root, progress, options, limit))
+}
+
+/// [`scan_with_budget`] on the pool the scan's directory reads run on.
+fn scan_in_pool<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+    limit: Duration,
+) -> FileNode {
+    let deadline = Instant::now() + limit;
+    let bounds = DeviceBounds::new(root, options.cross_filesystems);
+    let open_limit = OpenLimit::new(options);
+    let batch = rayon::current_num_threads() * BATCH_PER_THREAD;
+
+    let mut slots = vec![Slot {
+        node: FileNode::new_dir(root_name(root)),
+        children: Vec::new(),
+    }];
+    let mut queue = BinaryHeap::new();
+    let mut order = 0;
+    queue.push(Pending {
+        estimate: ROOT_ESTIMATE,
+        order: Reverse(order),
+        slot: 0,
+        path: root.to_path_buf(),
+    });
+
+    while !queue.is_empty() && Instant::now() < deadline && !progress.cancelled() {
+        let round: Vec<Pending> = std::iter::from_fn(|| queue.pop()).take(batch).collect();
+        progress.pending_dirs((queue.len() + round.len()) as u64);
+        let listings: Vec<(Pending, Vec<Listed>)> = round
+            .into_par_iter()
+            .map(|dir| {
+                progress.dir();
+                progress.set_current(&dir.path);
+                let _slot = open_limit.acquire();
+                let listing = read_dir(&dir.path, root, progress, options, bounds.as_ref());
+                (dir, listing)
+            })
+            .collect();
+
+        for (dir, listing) in listings {
+            slots[dir.slot].node.unexpanded = false;
+            let subdirs = listing.iter().filter(|e| e.is_dir).count() as u64;
+            let estimate = dir.estimate / subdirs.max(1);
+            for entry in listing {
+                let index = slots.len();
+                let node = if entry.is_dir {
+                    order += 1;
+                    queue.push(Pending {
+                        estimate,
+                        order: Reverse(order),
+                        slot: index,
+                        path: dir.path.join(&entry.name),
+                    });
+                    let mut node = FileNode::new_dir(entry.name).with_file_id(entry.file_id);
+                    node.unexpanded = true;
+                    node
+                } else {
+                    FileNode::new_file(entry.name, entry.size)
+                        .with_allocation(entry.allocated, entry.compressed)
+                        .with_mtime(entry.mtime)
+                        .with_file_id(entry.file_id)
+                };
+                slots.push(Slot {
+                    node,
+                    children: Vec::new(),
+                });
+                slots[dir.slot].children.push(index);
+            }
+        }
+    }
+
+    if let Some(bounds) = &bounds {
+        bounds.report(progress);
+    }
+    let mut tree = assemble(&mut slots, 0);
+    if options.treat_bundles_as_files {
+        collapse_bundles(&mut tree);
+    }
+    if !options.expand_trash {
+        collapse_trash(&mut tree, root);
+    }
+    if options.intern_names {
+        NameInterner::new().intern_dir_names(&mut tree);
+    }
+    tree.sort_by_size();
+    tree
+}
+
+/// List one directory, reporting progress and errors. Entries that are
+/// excluded, or directories outside the scan's device bounds, are left out.
+fn read_dir<P: ProgressSink + ?Sized>(
+    dir: &Path,
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+    bounds: Option<&DeviceBounds>,
+) -> Vec<Listed> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            if out_of_descriptors(&e) {
+                progress.fd_exhausted(dir);
+            }
+            progress.error(dir);
+            return Vec::new();
+        }
+    };
+    let mut listing = Vec::new();
+    let mut excluded = 0;
+    let mut file_bytes: u64 = 0;
+    for entry in entries {
+        let Ok(entry) = entry else {
+            progress.error(dir);
+            continue;
+        };
+        let path = entry.path();
+        let Ok(meta) = std::fs::symlink_metadata(&path) else {
+            progress.error(&path);
+            continue;
+        };
+        let is_dir = meta.is_dir();
+        if let Some(exclude) = &options.exclude {
+            let rel = path.strip_prefix(root).unwrap_or(&path);
+            if exclude.is_excluded(rel, is_dir) {
+                excluded += 1;
+                continue;
+            }
+        }
+        if is_dir && !bounds.is_none_or(|b| b.should_enter(&path, &meta)) {
+            continue;
+        }
+        let file_id = options.capture_ids.then(|| file_id_of(&meta)).flatten();
+        let name = entry.file_name().to_string_lossy().to_string();
+        if is_dir {
+            listing.push(Listed {
+                name,
+                is_dir,
+                size: 0,
+                allocated: 0,
+                compressed: false,
+                mtime: 0,
+                file_id,
+            });
+        } else {
+            progress.file(meta.len());
+            file_bytes = file_bytes.saturating_add(meta.len());
+            listing.push(Listed {
+                name,
+                is_dir,
+                size: meta.len(),
+                allocated: allocated_size(&meta),
+                compressed: is_compressed(&meta),
+                mtime: mtime_of(&meta),
+                file_id,
+            });
+        }
+    }
+    if excluded > 0 {
+        progress.excluded(excluded);
+    }
+    progress.dir_files(dir, file_bytes);
+    listing
+}
+
+/// Move the node at `index` and its descendants out of `slots` into a tree,
+/// with totals filled in.
+fn assemble(slots: &mut [Slot], index: usize) -> FileNode {
+    /// A node taken out of its slot, with the slots of its children still
+    /// to assemble and the children already assembled.
+    struct Open {
+        node: FileNode,
+        pending: std::vec::IntoIter<usize>,
+        done: Vec<FileNode>,
+    }
+    fn open(slots: &mut [Slot], index: usize) -> Open {
+        let children = std::mem::take(&mut slots[index].children);
+        Open {
+            node: std::mem::replace(&mut slots[index].node, FileNode::new_dir("")),
+            done: Vec::with_capacity(children.len()),
+            pending: children.into_iter(),
+        }
+    }
+
+    // Through a list rather than recursing, so a deep tree can't overflow
+    // the stack
+    let mut stack = vec![open(slots, index)];
+    while let Some(top) = stack.last_mut() {
+        if let Some(child) = top.pending.next() {
+            stack.push(open(slots, child));
+            continue;
+        }
+        let Some(Open { mut node, done, .. }) = stack.pop() else {
+            break;
+        };
+        if node.is_dir {
+            node.children = done;
+            node.update_totals();
+        }
+        match stack.last_mut() {
+            Some(parent) => parent.done.push(node),
+            None => return node,
+        }
+    }
+    unreachable!("the node at `index` is returned once it's assembled")
+}
+
+/// Number of directories a time-limited scan left unread below `node`.
+pub fn count_unexpanded(node: &FileNode) -> u64 {
+    let mut count = 0;
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        count += u64::from(node.unexpanded);
+        stack.extend(&node.children);
+    }
+    count
+}
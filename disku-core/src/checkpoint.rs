@@ -0,0 +1,224 @@
+//! Resumable scans for very slow storage (cold network shares and the like).
+//!
+//! [`scan_resumable`] scans the root one top-level directory at a time. After
+//! each directory finishes, every completed subtree so far is written to the
+//! checkpoint file, which is an ordinary snapshot (see [`crate::snapshot`])
+//! whose tree holds only the finished directories.
+//!
+//! A directory is recorded only once its whole scan has returned, so one that
+//! was in progress when the process stopped is simply absent from the
+//! checkpoint and is scanned again from scratch on the next run. The same goes
+//! for one whose scan was cancelled partway. Files directly in the root are
+//! cheap and always rescanned.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::scanner::{
+    allocated_size, is_compressed, mtime_of, scan_root, scan_walking, tallied, ProgressSink,
+    ScanOptions,
+};
+use crate::snapshot::{load_snapshot, save_snapshot};
+use crate::tree::{merge, FileNode, ScanResult};
+use crate::utils::root_name;
+
+/// [`scan_resumable`] with each top-level directory walked by the fastest
+/// scanner that can scan a single directory (the MFT scanner only reads
+/// whole volumes), returning the tree with the scan's counts like
+/// [`scan_root`]. A single file is scanned as usual.
+pub fn scan_root_resumable<P: ProgressSink + ?Sized>(
+    root: &Path,
+    checkpoint: &Path,
+    progress: &P,
+    options: &ScanOptions,
+) -> io::Result<ScanResult> {
+    if !root.is_dir() {
+        return Ok(scan_root(root, progress, options, None));
+    }
+    tallied(root, progress, |p| {
+        let tree = scan_resumable(root, checkpoint, p, |dir| scan_walking(dir, p, options))?;
+        Ok((tree, None))
+    })
+}
+
+/// Scan `root`, skipping top-level directories already recorded in
+/// `checkpoint` and recording each newly finished one there.
+///
+/// `scan_dir` scans a single top-level directory; its result is renamed to the
+/// directory's name. The checkpoint is removed once the whole scan completes,
+/// and kept for the next run if `progress` cancels it.
+/// Fails if the checkpoint belongs to a different root or can't be written;
+/// a missing checkpoint just starts from nothing.
+pub fn scan_resumable<P, F>(
+    root: &Path,
+    checkpoint: &Path,
+    progress: &P,
+    scan_dir: F,
+) -> io::Result<FileNode>
+where
+    P: ProgressSink + ?Sized,
+    F: Fn(&Path) -> FileNode,
+{
+    let root_name = root_name(root);
+    let mut done = match load_snapshot(checkpoint) {
+        Ok(scan) if scan.root_path == root => scan.tree,
+        Ok(scan) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("checkpoint is for {}, not {}", scan.root_path.display(), root.display()),
+            ))
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => FileNode::new_dir(root_name.clone()),
+        Err(e) => return Err(e),
+    };
+
+    let mut fresh = FileNode::new_dir(root_name);
+    let mut pending = Vec::new();
+    let mut listed = Vec::new();
+    let mut file_bytes: u64 = 0;
+    for entry in fs::read_dir(root)? {
+        let Ok(entry) = entry else {
+            progress.error(root);
+            continue;
+        };
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Ok(meta) = entry.metadata() else {
+            progress.error(&entry.path());
+            continue;
+        };
+        if meta.is_dir() {
+            listed.push(name.clone());
+            if !done.children.iter().any(|c| *c.name == *name) {
+                pending.push((name, entry.path()));
+            }
+        } else {
+            progress.file(meta.len());
+            file_bytes = file_bytes.saturating_add(meta.len());
+            fresh.children.push(
+                FileNode::new_file(name, meta.len())
+                    .with_allocation(allocated_size(&meta), is_compressed(&meta))
+                    .with_mtime(mtime_of(&meta)),
+            );
+        }
+    }
+
+    // Directories deleted since the checkpoint was written are stale
+    done.children.retain(|c| listed.iter().any(|n| **n == *c.name));
+    done.update_totals();
+    for child in &done.children {
+        progress.dir();
+        progress.set_current(&root.join(child.name()));
+        progress.dir_files(&root.join(child.name()), child.size);
+    }
+    progress.dir_files(root, file_bytes);
+
+    // Fail now, not after the first long directory, if the file is unwritable
+    write_checkpoint(root, &done, checkpoint)?;
+
+    for (name, path) in pending {
+        if progress.cancelled() {
+            break;
+        }
+        progress.dir();
+        let mut subtree = scan_dir(&path);
+        subtree.name = name.into();
+        done.children.push(subtree);
+        done.update_totals();
+        // Cancelled partway, the directory may be missing parts: it goes in
+        // the tree returned but not the checkpoint, so the next run redoes it
+        if progress.cancelled() {
+            break;
+        }
+        write_checkpoint(root, &done, checkpoint)?;
+    }
+
+    let mut tree = merge(done, fresh);
+    tree.sort_by_size();
+    if !progress.cancelled() {
+        // Best effort: a leftover checkpoint only means the next resume is
+        // instant
+        let _ = fs::remove_file(checkpoint);
+    }
+    Ok(tree)
+}
+
+/// Replace the checkpoint atomically, so a crash mid-write leaves the previous
+/// one intact rather than a truncated file.
+fn write_checkpoint(root: &Path, done: &FileNode, checkpoint: &Path) -> io::Result<()> {
+    let mut tmp = checkpoint.as_os_str().to_owned();
+    tmp.push(".tmp");
+    // Keep any `.gz` suffix last so the temporary file is compressed too
+    let tmp = match checkpoint.extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("gz") => checkpoint.with_extension("tmp.gz"),
+        _ => tmp.into(),
+    };
+    let scan = ScanResult::new(root.to_path_buf(), done.clone());
+    save_snapshot(&scan, &tmp)?;
+    fs::rename(&tmp, checkpoint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+
+    /// Counts nothing; says the scan is cancelled once `cancel` is set.
+    #[derive(Default)]
+    struct Cancellable {
+        cancel: AtomicBool,
+    }
+
+    impl ProgressSink for Cancellable {
+        fn file(&self, _size: u64) {}
+        fn dir(&self) {}
+        fn error(&self, _path: &Path) {}
+        fn set_current(&self, _path: &Path) {}
+        fn cancelled(&self) -> bool {
+            self.cancel.load(Ordering::Relaxed)
+        }
+    }
+
+    #[test]
+    fn cancelled_directory_stays_out_of_the_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        for name in ["a", "b", "c"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("f"), vec![0u8; 100]).unwrap();
+        }
+        let checkpoint = dir.path().join("checkpoint.json");
+
+        // The second directory's scan is cut short
+        let progress = Cancellable::default();
+        let scanned = Mutex::new(Vec::new());
+        scan_resumable(&root, &checkpoint, &progress, |path| {
+            let mut scanned = scanned.lock().unwrap();
+            scanned.push(path.file_name().unwrap().to_owned());
+            if scanned.len() == 2 {
+                progress.cancel.store(true, Ordering::Relaxed);
+                return FileNode::new_dir("partial");
+            }
+            scan_walking(path, &progress, &ScanOptions::default())
+        })
+        .unwrap();
+        let scanned = scanned.into_inner().unwrap();
+        assert_eq!(scanned.len(), 2);
+
+        let recorded = load_snapshot(&checkpoint).unwrap().tree;
+        assert_eq!(recorded.children.len(), 1);
+        assert_eq!(recorded.children[0].os_name(), scanned[0]);
+        assert_eq!(recorded.size, 100);
+
+        // The next run picks up the other two and finishes
+        let progress = Cancellable::default();
+        let tree = scan_resumable(&root, &checkpoint, &progress, |path| {
+            assert_ne!(path.file_name().unwrap(), scanned[0]);
+            scan_walking(path, &progress, &ScanOptions::default())
+        })
+        .unwrap();
+        assert_eq!(tree.size, 300);
+        assert!(!checkpoint.exists());
+    }
+}
@@ -0,0 +1,356 @@
+//! Persistent user defaults loaded from `~/.config/disku/config.toml`.
+//!
+//! Precedence is CLI flags > config file > built-in defaults: front-ends load
+//! the [`Config`], seed their options from it, then apply command-line flags
+//! on top. A few choices made in the UI are written back with [`persist`].
+//!
+//! ```toml
+//! [scan]
+//! collapse_bundles = true
+//! round_to_blocks = false
+//! capture_ids = false  # record inode numbers; shown in the TUI footer
+//! cross_filesystems = false  # descend into other mounted filesystems
+//! system_files = "include"    # NTFS metafiles: "include", "group" or "exclude"
+//! low_priority = false  # scan at the lowest CPU and disk priority
+//! expand_trash = false  # list trash folders' contents instead of one (Trash) entry
+//! spindle_friendly = true  # read few directories at once; omit to detect spinning disks
+//! include_xattrs = false  # count resource forks and extended attributes (macOS)
+//! max_open_dirs = 256  # directories held open at once; omit for half of `ulimit -n`
+//!
+//! [ui]
+//! sort = "name"       # or "size" or "recent"
+//! hyperlinks = false  # omit to auto-detect
+//! drive_sort = "used"  # drive picker order: "detected", "free", "total", "used" or "path"
+//! both_sizes = false  # list on-disk size and slack next to logical size
+//! item_counts = false  # list each directory's number of entries
+//! truncate = "tail"  # which end of a long name to cut: "tail", "middle" or "head"
+//! palette = "default"  # or "deuteranopia" ("colorblind") or "protanopia"
+//! cost_per_gb_month = 0.023  # show storage cost at this rate; omit to hide it
+//! group_below = 1.0  # fold entries under this percent of their directory; omit to list all
+//! percent_decimals = 2  # decimal places of the list's percentages; omit for 1, or 0 when narrow
+//! bookmarks = ["/home/me/src"]  # directories marked with b in the TUI
+//!
+//! [cleanup]
+//! cache_names = ["node_modules", "target", "__pycache__"]  # offered by the TUI's cache view
+//! protected_paths = ["/data/archive"]  # never deleted, on top of the system paths
+//!
+//! [tree]
+//! depth = 4
+//! per_dir = 20
+//! ```
+
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::scanner::{ScanOptions, SystemFiles};
+use crate::tree::DEFAULT_CACHE_NAMES;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub scan: ScanConfig,
+    pub ui: UiConfig,
+    pub tree: TreeConfig,
+    pub cleanup: CleanupConfig,
+}
+
+/// Defaults for [`ScanOptions`].
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanConfig {
+    pub collapse_bundles: bool,
+    pub intern_names: bool,
+    pub round_to_blocks: bool,
+    pub capture_ids: bool,
+    pub cross_filesystems: bool,
+    pub system_files: SystemFiles,
+    /// Not a [`ScanOptions`] field: front ends apply it to the whole process
+    /// with [`crate::utils::lower_priority`] before scanning.
+    pub low_priority: bool,
+    pub expand_trash: bool,
+    /// Force [`ScanOptions::spindle_friendly`] on or off; unset means detect.
+    pub spindle_friendly: Option<bool>,
+    pub include_xattrs: bool,
+    /// [`ScanOptions::max_open_dirs`]; unset means half of `ulimit -n`.
+    pub max_open_dirs: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    pub sort: SortKey,
+    /// Force OSC 8 hyperlinks on or off; unset means detect from the terminal.
+    pub hyperlinks: Option<bool>,
+    pub drive_sort: DriveSort,
+    /// Start the TUI with the on-disk size and slack columns shown.
+    pub both_sizes: bool,
+    /// Start the TUI with each directory's entry count shown.
+    pub item_counts: bool,
+    pub truncate: Truncation,
+    pub palette: Palette,
+    /// Price of storing a GB for a month (say 0.023 for S3 Standard), to
+    /// show what the selected entry, and each line of `--top`, would cost.
+    pub cost_per_gb_month: Option<f64>,
+    /// List a directory's entries under this percent of its size as one
+    /// "… N more" row, which opens into them on demand. See
+    /// [`crate::tree::small_tail`].
+    pub group_below: Option<f64>,
+    /// Decimal places of the TUI list's percentages, at most
+    /// [`MAX_PERCENT_DECIMALS`]. Unset means one, or none on narrow
+    /// terminals where the columns would crowd out names.
+    pub percent_decimals: Option<u8>,
+    /// Directories bookmarked in the TUI, kept here with [`persist`] so
+    /// they're offered again whenever a later scan includes them.
+    pub bookmarks: Vec<PathBuf>,
+}
+
+/// Most decimal places [`UiConfig::percent_decimals`] may ask for; beyond
+/// this the digits are noise.
+pub const MAX_PERCENT_DECIMALS: u8 = 3;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortKey {
+    #[default]
+    Size,
+    Name,
+    /// Newest modification time first.
+    Recent,
+}
+
+impl SortKey {
+    /// The order after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            SortKey::Size => SortKey::Name,
+            SortKey::Name => SortKey::Recent,
+            SortKey::Recent => SortKey::Size,
+        }
+    }
+}
+
+/// Which part of a name too long for its column is cut, marked with `~`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    /// Keep the start: `verylongpre~`.
+    #[default]
+    Tail,
+    /// Keep both ends, including the whole extension where it fits:
+    /// `verylong~fix.ext`. Suits generated names sharing a long prefix.
+    Middle,
+    /// Keep the end: `~prefix_suffix.ext`.
+    Head,
+}
+
+/// Colors the TUI tells directories and file categories apart with.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Palette {
+    #[default]
+    Default,
+    /// Friendly to red-green color blindness, the most common kind.
+    #[serde(alias = "colorblind")]
+    Deuteranopia,
+    /// Friendly to red blindness, where reds also look dim.
+    Protanopia,
+}
+
+impl std::str::FromStr for Palette {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "default" => Ok(Palette::Default),
+            "deuteranopia" | "colorblind" => Ok(Palette::Deuteranopia),
+            "protanopia" => Ok(Palette::Protanopia),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Order of the drive picker. Every order but `Detected` and `Path` puts the
+/// drive you're most likely after (most free, biggest, fullest) first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DriveSort {
+    /// The order the platform lists them in.
+    #[default]
+    Detected,
+    Free,
+    Total,
+    Used,
+    Path,
+}
+
+impl DriveSort {
+    /// The order after this one, wrapping around.
+    pub fn next(self) -> Self {
+        match self {
+            DriveSort::Detected => DriveSort::Free,
+            DriveSort::Free => DriveSort::Total,
+            DriveSort::Total => DriveSort::Used,
+            DriveSort::Used => DriveSort::Path,
+            DriveSort::Path => DriveSort::Detected,
+        }
+    }
+
+    /// The value as written in the config file.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DriveSort::Detected => "detected",
+            DriveSort::Free => "free",
+            DriveSort::Total => "total",
+            DriveSort::Used => "used",
+            DriveSort::Path => "path",
+        }
+    }
+}
+
+/// Settings for the `--output-tree` printer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TreeConfig {
+    pub depth: usize,
+    pub per_dir: usize,
+}
+
+impl Default for TreeConfig {
+    fn default() -> Self {
+        Self {
+            depth: 3,
+            per_dir: 10,
+        }
+    }
+}
+
+/// Settings for the TUI's bulk cache cleanup.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CleanupConfig {
+    /// Directory names treated as caches; see [`tree::find_caches`](crate::tree::find_caches).
+    pub cache_names: Vec<String>,
+    /// Paths never to delete, with everything in them, on top of the system
+    /// locations [`is_protected_path`](crate::utils::is_protected_path)
+    /// always refuses.
+    pub protected_paths: Vec<PathBuf>,
+}
+
+impl Default for CleanupConfig {
+    fn default() -> Self {
+        Self {
+            cache_names: DEFAULT_CACHE_NAMES.iter().map(|n| n.to_string()).collect(),
+            protected_paths: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(PathBuf, std::io::Error),
+    Parse(PathBuf, Box<toml::de::Error>),
+    /// The file couldn't be parsed for editing by [`persist`].
+    Edit(PathBuf, Box<toml_edit::TomlError>),
+    /// There's no home directory to keep a config file in.
+    NoPath,
+}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "reading {}: {}", path.display(), e),
+            ConfigError::Parse(path, e) => write!(f, "parsing {}: {}", path.display(), e),
+            ConfigError::Edit(path, e) => write!(f, "parsing {}: {}", path.display(), e),
+            ConfigError::NoPath => write!(f, "no config directory"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Where the config file lives: `$XDG_CONFIG_HOME/disku/config.toml`,
+    /// falling back to `~/.config/disku/config.toml`.
+    pub fn path() -> Option<PathBuf> {
+        let base = std::env::var_os("XDG_CONFIG_HOME")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|h| h.join(".config")))?;
+        Some(base.join("disku").join("config.toml"))
+    }
+
+    /// Load the config file. A missing file yields the built-in defaults; an
+    /// unreadable or malformed one is an error the caller can report before
+    /// carrying on with defaults.
+    pub fn load() -> Result<Config, ConfigError> {
+        let Some(path) = Self::path() else {
+            return Ok(Config::default());
+        };
+        match std::fs::read_to_string(&path) {
+            Ok(text) => toml::from_str(&text).map_err(|e| ConfigError::Parse(path, Box::new(e))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Config::default()),
+            Err(e) => Err(ConfigError::Io(path, e)),
+        }
+    }
+
+    /// Scan options seeded from the `[scan]` table.
+    pub fn scan_options(&self) -> ScanOptions {
+        ScanOptions {
+            treat_bundles_as_files: self.scan.collapse_bundles,
+            intern_names: self.scan.intern_names,
+            round_to_blocks: self.scan.round_to_blocks,
+            capture_ids: self.scan.capture_ids,
+            cross_filesystems: self.scan.cross_filesystems,
+            // Patterns come from the command line
+            exclude: None,
+            system_files: self.scan.system_files,
+            expand_trash: self.scan.expand_trash,
+            spindle_friendly: self.scan.spindle_friendly,
+            include_xattrs: self.scan.include_xattrs,
+            max_open_dirs: self.scan.max_open_dirs,
+        }
+    }
+}
+
+/// Set `key = "value"` in `[table]` of the config file, creating the file and
+/// table as needed. Everything else in the file, comments included, is kept
+/// as it was.
+pub fn persist(table: &str, key: &str, value: &str) -> Result<(), ConfigError> {
+    persist_value(table, key, value.into())
+}
+
+/// Like [`persist`], for a list of strings.
+pub fn persist_list<S: AsRef<str>>(
+    table: &str,
+    key: &str,
+    values: &[S],
+) -> Result<(), ConfigError> {
+    let array: toml_edit::Array = values.iter().map(|v| v.as_ref()).collect();
+    persist_value(table, key, array.into())
+}
+
+fn persist_value(table: &str, key: &str, value: toml_edit::Value) -> Result<(), ConfigError> {
+    let path = Config::path().ok_or(ConfigError::NoPath)?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(ConfigError::Io(path, e)),
+    };
+    let mut doc: toml_edit::Document = text
+        .parse()
+        .map_err(|e| ConfigError::Edit(path.clone(), Box::new(e)))?;
+    doc[table][key] = toml_edit::value(value);
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| ConfigError::Io(path.clone(), e))?;
+    }
+    std::fs::write(&path, doc.to_string()).map_err(|e| ConfigError::Io(path, e))
+}
+
+pub(crate) fn home_dir() -> Option<PathBuf> {
+    let var = if cfg!(windows) { "USERPROFILE" } else { "HOME" };
+    std::env::var_os(var)
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+}
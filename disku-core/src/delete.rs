@@ -0,0 +1,69 @@
+//! Removing scanned entries from disk.
+//!
+//! Deleting goes to the OS recycle bin / Trash by default, so a mistake can be
+//! undone; permanent removal is an explicit opt-in. Where there is no Trash to
+//! move to, [`TRASH_SUPPORTED`] is false and front ends should warn that the
+//! delete will be permanent rather than quietly switching modes.
+
+use std::io;
+use std::path::Path;
+
+/// Whether this platform has a recycle bin / Trash we can move items to.
+pub const TRASH_SUPPORTED: bool = cfg!(any(windows, target_os = "macos", target_os = "linux"));
+
+/// How [`remove_path`] gets rid of an entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeleteMode {
+    /// Move to the recycle bin / Trash.
+    Trash,
+    /// Remove for good (`remove_dir_all` / `remove_file`).
+    Permanent,
+}
+
+impl DeleteMode {
+    /// Trash unless `permanent` is set, or there's no Trash on this platform.
+    pub fn new(permanent: bool) -> Self {
+        if permanent || !TRASH_SUPPORTED {
+            DeleteMode::Permanent
+        } else {
+            DeleteMode::Trash
+        }
+    }
+
+    /// Verb for confirmation prompts ("Move to Trash" / "Permanently delete").
+    pub fn verb(self) -> &'static str {
+        match self {
+            DeleteMode::Trash => "Move to Trash",
+            DeleteMode::Permanent => "Permanently delete",
+        }
+    }
+}
+
+/// Remove the file or directory at `path`. Symlinks are removed themselves,
+/// never followed.
+pub fn remove_path(path: &Path, mode: DeleteMode) -> io::Result<()> {
+    match mode {
+        DeleteMode::Trash => move_to_trash(path),
+        DeleteMode::Permanent => {
+            let meta = std::fs::symlink_metadata(path)?;
+            if meta.is_dir() {
+                std::fs::remove_dir_all(path)
+            } else {
+                std::fs::remove_file(path)
+            }
+        }
+    }
+}
+
+#[cfg(any(windows, target_os = "macos", target_os = "linux"))]
+fn move_to_trash(path: &Path) -> io::Result<()> {
+    trash::delete(path).map_err(|e| io::Error::other(format!("couldn't move to Trash: {}", e)))
+}
+
+#[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+fn move_to_trash(_path: &Path) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "no Trash on this platform; delete permanently instead",
+    ))
+}
@@ -0,0 +1,170 @@
+//! Gitignore-style exclusion patterns, applied while scanning.
+//!
+//! Patterns are matched against paths relative to the scan root, with `/` as
+//! the separator on every platform:
+//!
+//! - `*` and `?` stay within one path component; `**` spans any number.
+//! - A pattern with no `/` (other than a trailing one) matches at any depth:
+//!   `node_modules` is `**/node_modules`.
+//! - A leading `/`, or a `/` anywhere but the end, anchors it to the root:
+//!   `/build` and `docs/_site` only match there.
+//! - A trailing `/` matches directories only.
+//!
+//! An excluded directory is not descended into, so everything below it is
+//! left out too. Negation (`!pattern`) isn't supported.
+
+use std::fmt;
+use std::io;
+use std::path::{Component, Path};
+
+use globset::{Glob, GlobBuilder, GlobSet, GlobSetBuilder};
+
+#[cfg(windows)]
+use crate::scanner::ProgressSink;
+#[cfg(windows)]
+use crate::tree::FileNode;
+
+/// A compiled set of exclusion patterns.
+#[derive(Debug, Clone)]
+pub struct ExcludeSet {
+    /// Patterns that apply to any entry.
+    any: GlobSet,
+    /// Patterns written with a trailing `/`, which apply to directories only.
+    dirs: GlobSet,
+    len: usize,
+}
+
+/// A pattern that isn't a valid glob.
+#[derive(Debug)]
+pub struct PatternError {
+    /// The offending pattern; empty when the set as a whole was rejected.
+    pub pattern: String,
+    source: globset::Error,
+}
+
+impl fmt::Display for PatternError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.pattern.is_empty() {
+            // Every glob compiled; combining them failed (e.g. too large)
+            write!(f, "invalid exclude patterns: {}", self.source.kind())
+        } else {
+            write!(f, "invalid exclude pattern {:?}: {}", self.pattern, self.source.kind())
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+impl ExcludeSet {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, PatternError> {
+        let mut any = GlobSetBuilder::new();
+        let mut dirs = GlobSetBuilder::new();
+        for pattern in patterns {
+            let pattern = pattern.as_ref();
+            let (glob, dir_only) = compile(pattern).map_err(|source| PatternError {
+                pattern: pattern.to_string(),
+                source,
+            })?;
+            if dir_only {
+                dirs.add(glob);
+            } else {
+                any.add(glob);
+            }
+        }
+        let build = |b: GlobSetBuilder| {
+            b.build().map_err(|source| PatternError {
+                pattern: String::new(),
+                source,
+            })
+        };
+        Ok(ExcludeSet {
+            any: build(any)?,
+            dirs: build(dirs)?,
+            len: patterns.len(),
+        })
+    }
+
+    /// Number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether the entry at `rel` (relative to the scan root) is excluded.
+    pub fn is_excluded(&self, rel: &Path, is_dir: bool) -> bool {
+        let rel = slash_path(rel);
+        if rel.is_empty() {
+            return false;
+        }
+        self.any.is_match(&rel) || (is_dir && self.dirs.is_match(&rel))
+    }
+}
+
+/// Remove every excluded entry from an already-built tree and recompute the
+/// totals, for scanners that can't skip entries as they go (the MFT reader
+/// sees the whole volume regardless).
+#[cfg(windows)]
+pub(crate) fn prune<P: ProgressSink + ?Sized>(tree: &mut FileNode, exclude: &ExcludeSet, progress: &P) {
+    // Each directory still to prune with its path, from a list rather than
+    // by recursing, so a deep tree can't overflow the stack
+    let mut walk = vec![(&mut *tree, std::path::PathBuf::new())];
+    while let Some((node, rel)) = walk.pop() {
+        let before = node.children.len();
+        node.children
+            .retain(|child| !exclude.is_excluded(&rel.join(&*child.name), child.is_dir));
+        let removed = (before - node.children.len()) as u64;
+        if removed > 0 {
+            progress.excluded(removed);
+        }
+        for child in node.children.iter_mut().filter(|c| c.is_dir) {
+            let rel = rel.join(&*child.name);
+            walk.push((child, rel));
+        }
+    }
+    tree.for_each_dir_bottom_up(FileNode::update_totals);
+}
+
+/// Read patterns from a gitignore-style file: one per line, with blank lines
+/// and `#` comments skipped and trailing whitespace trimmed.
+pub fn read_patterns(path: &Path) -> io::Result<Vec<String>> {
+    let text = std::fs::read_to_string(path)?;
+    Ok(text
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Turn one gitignore-style pattern into a glob over root-relative paths, and
+/// whether it only applies to directories.
+pub(crate) fn compile(pattern: &str) -> Result<(Glob, bool), globset::Error> {
+    let (pattern, dir_only) = match pattern.strip_suffix('/') {
+        Some(rest) => (rest, true),
+        None => (pattern, false),
+    };
+    let glob = match pattern.strip_prefix('/') {
+        Some(anchored) => anchored.to_string(),
+        None if pattern.contains('/') => pattern.to_string(),
+        None => format!("**/{}", pattern),
+    };
+    let glob = GlobBuilder::new(&glob).literal_separator(true).build()?;
+    Ok((glob, dir_only))
+}
+
+/// `rel` joined with `/`, whatever the platform separator.
+fn slash_path(rel: &Path) -> String {
+    let mut out = String::new();
+    for component in rel.components() {
+        if let Component::Normal(part) = component {
+            if !out.is_empty() {
+                out.push('/');
+            }
+            out.push_str(&part.to_string_lossy());
+        }
+    }
+    out
+}
@@ -0,0 +1,221 @@
+//! The "why is my disk full" summary: where a scan's space went, under
+//! plain-language headings, for people who'd rather not browse the tree.
+//!
+//! [`analyze`] picks out caches, the trash and Downloads folders first, then
+//! totals everything else by file [`Category`]. Each heading comes with a
+//! sentence of advice and its biggest entries, and caches are flagged as
+//! safe to delete, since whatever made them rebuilds them when needed.
+
+use std::collections::HashMap;
+
+use crate::scanner::is_trash_name;
+use crate::tree::FileNode;
+use crate::utils::{categorize, Category};
+
+/// Directory names the OS and apps keep rebuildable caches in, treated as
+/// caches along with the configured
+/// [`cache_names`](crate::config::CleanupConfig::cache_names).
+pub const SYSTEM_CACHE_NAMES: &[&str] = &[".cache", "Caches", "INetCache", "Temp"];
+
+/// Entries listed under each heading.
+pub const TOP_ENTRIES: usize = 5;
+
+/// A heading of the summary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FindingKind {
+    Caches,
+    Trash,
+    Downloads,
+    /// Files of a category, outside the other headings' directories.
+    Files(Category),
+}
+
+impl FindingKind {
+    pub fn label(self) -> &'static str {
+        match self {
+            FindingKind::Caches => "Caches and build files",
+            FindingKind::Trash => "Trash",
+            FindingKind::Downloads => "Downloads",
+            FindingKind::Files(category) => category.label(),
+        }
+    }
+
+    /// What's under this heading and what can be done about it, in a sentence.
+    pub fn advice(self) -> &'static str {
+        match self {
+            FindingKind::Caches => "Rebuilt by apps and tools when needed, so safe to delete.",
+            FindingKind::Trash => "Already deleted, but only freed once the trash is emptied.",
+            FindingKind::Downloads => "Installers and archives pile up; check what's still needed.",
+            FindingKind::Files(category) => match category {
+                Category::Video => "Movies and recordings. Old ones can go to an external drive.",
+                Category::Image => "Photos and pictures. Look for duplicates or old albums.",
+                Category::Audio => "Music and recordings. Streaming can replace local copies.",
+                Category::Document => "Documents and PDFs, usually worth keeping.",
+                Category::Code => "Source code and scripts.",
+                Category::Archive => "Zip files and the like, often already unpacked.",
+                Category::DiskImage => "Installer and virtual machine disks, often unused later.",
+                Category::Executable => "Programs. Uninstall unused ones rather than delete files.",
+                Category::Other => "Everything else, mostly app data and system files.",
+            },
+        }
+    }
+
+    /// Whether everything under this heading can be deleted without losing
+    /// anything: only caches.
+    pub fn safe(self) -> bool {
+        self == FindingKind::Caches
+    }
+}
+
+/// One heading of the summary with its total.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub kind: FindingKind,
+    pub size: u64,
+    /// Nav paths and sizes of the biggest entries counted here, largest first;
+    /// at most [`TOP_ENTRIES`].
+    pub top: Vec<(Vec<usize>, u64)>,
+}
+
+impl Finding {
+    fn add(&mut self, parent: &[usize], index: usize, size: u64) {
+        self.size = self.size.saturating_add(size);
+        let full = self.top.len() == TOP_ENTRIES;
+        if full && self.top.last().is_some_and(|&(_, smallest)| smallest >= size) {
+            return;
+        }
+        let mut nav_path = parent.to_vec();
+        nav_path.push(index);
+        let at = self.top.partition_point(|&(_, s)| s >= size);
+        self.top.insert(at, (nav_path, size));
+        self.top.truncate(TOP_ENTRIES);
+    }
+}
+
+/// Sort the space under `node` into [`Finding`]s, largest first, leaving out
+/// empty ones. A directory named in `cache_names` or [`SYSTEM_CACHE_NAMES`],
+/// a trash folder or a Downloads folder is counted whole under its heading;
+/// any other file counts under its category.
+pub fn analyze<S: AsRef<str>>(node: &FileNode, cache_names: &[S]) -> Vec<Finding> {
+    let is_cache = |name: &str| {
+        cache_names.iter().any(|n| n.as_ref() == name) || SYSTEM_CACHE_NAMES.contains(&name)
+    };
+    let mut found: HashMap<FindingKind, Finding> = HashMap::new();
+    let mut stack: Vec<(&FileNode, Vec<usize>)> = vec![(node, Vec::new())];
+
+    while let Some((dir, nav_path)) = stack.pop() {
+        for (i, child) in dir.children.iter().enumerate() {
+            let kind = if child.trash || (child.is_dir && is_trash_name(&child.name)) {
+                FindingKind::Trash
+            } else if !child.is_dir {
+                FindingKind::Files(categorize(&child.name))
+            } else if is_cache(&child.name) {
+                FindingKind::Caches
+            } else if child.name.eq_ignore_ascii_case("Downloads") {
+                FindingKind::Downloads
+            } else {
+                let mut child_nav = nav_path.clone();
+                child_nav.push(i);
+                stack.push((child, child_nav));
+                continue;
+            };
+            found
+                .entry(kind)
+                .or_insert_with(|| Finding {
+                    kind,
+                    size: 0,
+                    top: Vec::new(),
+                })
+                .add(&nav_path, i, child.size);
+        }
+    }
+
+    let mut findings: Vec<Finding> = found.into_values().filter(|f| f.size > 0).collect();
+    findings.sort_by(|a, b| b.size.cmp(&a.size).then(a.kind.label().cmp(b.kind.label())));
+    findings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str, children: Vec<FileNode>) -> FileNode {
+        let mut dir = FileNode::new_dir(name);
+        dir.children = children;
+        dir.update_totals();
+        dir
+    }
+
+    fn finding(findings: &[Finding], kind: FindingKind) -> &Finding {
+        findings.iter().find(|f| f.kind == kind).unwrap()
+    }
+
+    #[test]
+    fn space_is_sorted_under_headings() {
+        let videos = (1..=7).map(|i| FileNode::new_file(format!("{}.mp4", i), i * 10)).collect();
+        let home = dir(
+            "home",
+            vec![
+                dir(".cache", vec![FileNode::new_file("blob", 100)]),
+                dir("node_modules", vec![FileNode::new_file("index.js", 50)]),
+                dir(
+                    "Downloads",
+                    vec![FileNode::new_file("setup.exe", 70), FileNode::new_file("clip.mp4", 30)],
+                ),
+                dir("Videos", videos),
+            ],
+        );
+        let root = dir(
+            "/",
+            vec![
+                home,
+                dir(".Trash-1000", vec![FileNode::new_file("old.mp4", 40)]),
+                FileNode::new_file("notes", 5),
+            ],
+        );
+        let findings = analyze(&root, &["node_modules"]);
+
+        let kinds: Vec<FindingKind> = findings.iter().map(|f| f.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                FindingKind::Files(Category::Video),
+                FindingKind::Caches,
+                FindingKind::Downloads,
+                FindingKind::Trash,
+                FindingKind::Files(Category::Other),
+            ]
+        );
+
+        let caches = finding(&findings, FindingKind::Caches);
+        assert_eq!(caches.size, 150);
+        assert_eq!(caches.top, [(vec![0, 0], 100), (vec![0, 1], 50)]);
+        assert!(caches.kind.safe());
+
+        // Downloads is counted whole, not by what's in it
+        let downloads = finding(&findings, FindingKind::Downloads);
+        assert_eq!((downloads.size, &downloads.top[..]), (100, &[(vec![0, 2], 100)][..]));
+
+        let trash = finding(&findings, FindingKind::Trash);
+        assert_eq!((trash.size, &trash.top[..]), (40, &[(vec![1], 40)][..]));
+
+        // Seven videos, the biggest five listed
+        let video = finding(&findings, FindingKind::Files(Category::Video));
+        assert_eq!(video.size, 280);
+        let sizes: Vec<u64> = video.top.iter().map(|&(_, size)| size).collect();
+        assert_eq!(sizes, [70, 60, 50, 40, 30]);
+        assert_eq!(video.top[0].0, [0, 3, 6]);
+
+        assert_eq!(finding(&findings, FindingKind::Files(Category::Other)).size, 5);
+    }
+
+    #[test]
+    fn nothing_to_report_in_an_empty_tree() {
+        assert!(analyze::<&str>(&FileNode::new_dir("/"), &[]).is_empty());
+        let empty = dir(
+            "/",
+            vec![dir("a", vec![dir("b", Vec::new())]), dir("Downloads", Vec::new())],
+        );
+        assert!(analyze::<&str>(&empty, &[]).is_empty());
+    }
+}
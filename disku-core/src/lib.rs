@@ -1,7 +1,21 @@
+pub mod budget;
+pub mod checkpoint;
+pub mod config;
+pub mod delete;
+pub mod exclude;
+pub mod guide;
 #[cfg(target_os = "macos")]
 pub mod mac_scanner;
 #[cfg(windows)]
 pub mod mft_scanner;
+pub mod progress_channel;
+pub mod progress_log;
 pub mod scanner;
+#[cfg(feature = "sftp")]
+pub mod sftp_scanner;
+pub mod snapshot;
 pub mod tree;
+pub mod treemap;
+#[cfg(windows)]
+pub mod usn_journal;
 pub mod utils;
@@ -1,23 +1,44 @@
+use std::cell::RefCell;
 use std::ffi::{CStr, CString};
 use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
 use std::path::Path;
-use std::sync::atomic::Ordering;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 
 use rayon::prelude::*;
 
-use crate::scanner::ScanProgress;
-use crate::tree::FileNode;
+use crate::scanner::{
+    allocated_size, collapse_trash, epoch_secs, file_id_of, in_scan_pool, is_bundle_name,
+    is_compressed, mtime_of, out_of_descriptors, DeviceBounds, OpenLimit, ProgressSink,
+    ScanOptions, UF_COMPRESSED,
+};
+use crate::tree::{saturating_sum, FileNode, NameInterner};
+use crate::utils::{root_name, round_up_to_block, BlockSizeCache};
 
 // macOS attribute constants
 const ATTR_BIT_MAP_COUNT: u16 = 5;
 const ATTR_CMN_RETURNED_ATTRS: u32 = 0x80000000;
 const ATTR_CMN_NAME: u32 = 0x00000001;
 const ATTR_CMN_OBJTYPE: u32 = 0x00000008;
+const ATTR_CMN_MODTIME: u32 = 0x00000400;
+const ATTR_CMN_FLAGS: u32 = 0x00040000;
+const ATTR_CMN_FILEID: u32 = 0x02000000;
 const ATTR_CMN_ERROR: u32 = 0x20000000;
+const ATTR_FILE_ALLOCSIZE: u32 = 0x00000004;
 const ATTR_FILE_DATALENGTH: u32 = 0x00000200;
+const ATTR_FILE_RSRCLENGTH: u32 = 0x00001000;
 const VDIR: u32 = 2; // directory
 
 const BULK_BUF_SIZE: usize = 256 * 1024; // 256 KB buffer
+/// Largest the buffer grows to for an entry that won't fit in it.
+const MAX_BULK_BUF_SIZE: usize = 4 * 1024 * 1024;
+
+thread_local! {
+    /// Each scanning thread's getattrlistbulk buffer, kept from one directory
+    /// to the next rather than allocated afresh for each.
+    static BULK_BUF: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
 const MAX_DEPTH: usize = 512;
 
 #[repr(C, packed(4))]
@@ -54,150 +75,445 @@ struct BulkEntry {
     name: String,
     is_dir: bool,
     size: u64,
+    allocated: u64,
+    compressed: bool,
+    mtime: u32,
+    file_id: Option<u64>,
 }
 
-/// Get the device ID for a path (used to avoid crossing filesystem boundaries).
-fn get_dev(path: &Path) -> Option<u64> {
-    use std::os::unix::fs::MetadataExt;
-    std::fs::symlink_metadata(path).map(|m| m.dev()).ok()
+/// Scan a directory tree using macOS getattrlistbulk for fast enumeration.
+pub fn scan_bulk<P: ProgressSink + ?Sized>(root: &Path, progress: &P) -> FileNode {
+    scan_bulk_with_options(root, progress, &ScanOptions::default())
 }
 
-/// Scan a directory tree using macOS getattrlistbulk for fast enumeration.
-pub fn scan_bulk(root: &Path, progress: &ScanProgress) -> FileNode {
-    let root_name = root
-        .file_name()
-        .map(|n| n.to_string_lossy().to_string())
-        .unwrap_or_else(|| root.to_string_lossy().to_string());
-
-    let root_dev = get_dev(root);
-    let children = scan_dir_recursive(root, progress, root_dev, 0);
-    let mut node = FileNode::new_dir(root_name);
+/// Like [`scan_bulk`], but honouring the given [`ScanOptions`].
+pub fn scan_bulk_with_options<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+) -> FileNode {
+    if let Some(node) = crate::scanner::scan_file(root, progress) {
+        return node;
+    }
+
+    let root_name = root_name(root);
+
+    let ctx = ScanCtx {
+        progress,
+        options,
+        root,
+        bounds: DeviceBounds::new(root, options.cross_filesystems),
+        names: options.intern_names.then(NameInterner::new),
+        block_sizes: options.round_to_blocks.then(BlockSizeCache::new),
+        root_dev: std::fs::symlink_metadata(root).ok().map(|m| m.dev()),
+        pending: AtomicU64::new(1),
+        open_limit: OpenLimit::new(options),
+    };
+    let children = in_scan_pool(root, options, || scan_dir_recursive(&ctx, root, 0));
+    ctx.finish_dir();
+    if let Some(bounds) = &ctx.bounds {
+        bounds.report(progress);
+    }
+    let root_id = options
+        .capture_ids
+        .then(|| std::fs::symlink_metadata(root).ok())
+        .flatten()
+        .and_then(|m| file_id_of(&m));
+    let mut node = FileNode::new_dir(root_name).with_file_id(root_id);
     node.children = children;
-    node.size = node.children.iter().map(|c| c.size).sum();
+    node.update_totals();
+    if !options.expand_trash {
+        collapse_trash(&mut node, root);
+    }
     node.sort_by_size();
     node
 }
 
-fn scan_dir_recursive(dir_path: &Path, progress: &ScanProgress, root_dev: Option<u64>, depth: usize) -> Vec<FileNode> {
-    if depth >= MAX_DEPTH {
-        return Vec::new();
+/// Per-scan state shared by every level of the recursion.
+struct ScanCtx<'a, P: ?Sized> {
+    progress: &'a P,
+    options: &'a ScanOptions,
+    /// Exclusion patterns are matched relative to this.
+    root: &'a Path,
+    /// Which directories may be entered; other devices are skipped unless
+    /// crossing filesystems.
+    bounds: Option<DeviceBounds>,
+    names: Option<NameInterner>,
+    /// Block sizes by device, when rounding file sizes up to whole blocks.
+    block_sizes: Option<BlockSizeCache>,
+    /// Device the root is on, and so everything below it unless crossing
+    /// filesystems.
+    root_dev: Option<u64>,
+    /// Directories queued for recursion whose subtree isn't finished yet.
+    pending: AtomicU64,
+    /// Directories held open at once, across every worker.
+    open_limit: OpenLimit,
+}
+
+impl<P: ProgressSink + ?Sized> ScanCtx<'_, P> {
+    /// Block size to round up the files directly in `dir_path` to, when
+    /// rounding: that of the filesystem it's on. A directory's files are all
+    /// on its own device (only directories can be mount points), so one
+    /// lookup covers them, and only when crossing filesystems does it take a
+    /// stat to tell which device that is.
+    fn block_size(&self, dir_path: &Path) -> Option<u64> {
+        let sizes = self.block_sizes.as_ref()?;
+        let dev = if self.options.cross_filesystems {
+            std::fs::symlink_metadata(dir_path).ok().map(|m| m.dev()).or(self.root_dev)
+        } else {
+            self.root_dev
+        };
+        sizes.get(dev?, dir_path)
     }
 
-    if let Ok(mut cp) = progress.current_path.try_lock() {
-        *cp = dir_path.to_string_lossy().to_string();
+    /// Whether the entry `name` in `dir_path` matches the exclusion patterns,
+    /// reporting it if so.
+    fn excludes(&self, dir_path: &Path, name: &str, is_dir: bool) -> bool {
+        let Some(exclude) = &self.options.exclude else {
+            return false;
+        };
+        let path = dir_path.join(name);
+        let rel = path.strip_prefix(self.root).unwrap_or(&path);
+        let excluded = exclude.is_excluded(rel, is_dir);
+        if excluded {
+            self.progress.excluded(1);
+        }
+        excluded
     }
 
-    let entries = match read_dir_bulk(dir_path) {
-        Some(e) => e,
-        None => {
-            return read_dir_fallback(dir_path, progress, root_dev, depth);
+    /// Add `count` directories to those pending and report the total.
+    fn queue_dirs(&self, count: usize) {
+        let pending = self.pending.fetch_add(count as u64, Ordering::Relaxed) + count as u64;
+        self.progress.pending_dirs(pending);
+    }
+
+    /// Count one pending directory as finished and report the total.
+    fn finish_dir(&self) {
+        let pending = self.pending.fetch_sub(1, Ordering::Relaxed) - 1;
+        self.progress.pending_dirs(pending);
+    }
+
+    /// Report the directory at `dir_path` as skipped for want of file
+    /// descriptors.
+    fn out_of_descriptors(&self, dir_path: &Path) {
+        self.progress.fd_exhausted(dir_path);
+        self.progress.error(dir_path);
+    }
+}
+
+fn scan_dir_recursive<P: ProgressSink + ?Sized>(
+    ctx: &ScanCtx<'_, P>,
+    dir_path: &Path,
+    depth: usize,
+) -> Vec<FileNode> {
+    if depth >= MAX_DEPTH || ctx.progress.cancelled() {
+        return Vec::new();
+    }
+
+    let progress = ctx.progress;
+    let options = ctx.options;
+    progress.set_current(dir_path);
+
+    // The directory is closed again before recursing into its subdirectories
+    let listing = {
+        let _slot = ctx.open_limit.acquire();
+        read_dir_bulk(dir_path, options.capture_ids, options.include_xattrs)
+    };
+    let entries = match listing {
+        Ok(e) => e,
+        Err(BulkReadError::Unsupported) => {
+            return read_dir_fallback(ctx, dir_path, depth);
+        }
+        Err(BulkReadError::Malformed) => {
+            progress.bulk_fallback(dir_path);
+            return read_dir_fallback(ctx, dir_path, depth);
+        }
+        // Deleted or locked since its parent was listed: record and move on
+        Err(BulkReadError::Vanished) => {
+            progress.error(dir_path);
+            return Vec::new();
+        }
+        Err(BulkReadError::NoDescriptors) => {
+            ctx.out_of_descriptors(dir_path);
+            return Vec::new();
+        }
+        Err(BulkReadError::Interrupted(partial)) => {
+            progress.error(dir_path);
+            partial
         }
     };
 
     let mut file_nodes: Vec<FileNode> = Vec::with_capacity(entries.len());
-    let mut dir_entries: Vec<(String, std::path::PathBuf)> = Vec::with_capacity(entries.len() / 8);
-
-    for entry in entries {
+    let mut dir_entries: Vec<(String, std::path::PathBuf, Option<u64>)> =
+        Vec::with_capacity(entries.len() / 8);
+    let mut file_bytes: u64 = 0;
+    let block_size = ctx.block_size(dir_path);
+
+    for mut entry in entries {
+        if ctx.excludes(dir_path, &entry.name, entry.is_dir) {
+            continue;
+        }
         if entry.is_dir {
-            progress.dirs_scanned.fetch_add(1, Ordering::Relaxed);
+            progress.dir();
         } else {
-            progress.files_scanned.fetch_add(1, Ordering::Relaxed);
+            if options.include_xattrs {
+                // The resource fork came with the listing
+                entry.size = entry.size.saturating_add(xattr_size(&dir_path.join(&entry.name)));
+            }
+            entry.size = file_size(entry.size, block_size);
+            progress.file(entry.size);
+            file_bytes = file_bytes.saturating_add(entry.size);
         }
 
         if entry.is_dir {
             let child_path = dir_path.join(&entry.name);
-            // Skip directories on different filesystems (network mounts, iCloud, etc.)
-            if let Some(rd) = root_dev {
-                if get_dev(&child_path) != Some(rd) {
-                    continue;
-                }
+            if !should_enter(ctx, &child_path) {
+                continue;
             }
-            dir_entries.push((entry.name, child_path));
+            dir_entries.push((entry.name, child_path, entry.file_id));
         } else {
-            file_nodes.push(FileNode::new_file(entry.name, entry.size));
+            file_nodes.push(
+                FileNode::new_file(entry.name, entry.size)
+                    .with_allocation(entry.allocated, entry.compressed)
+                    .with_mtime(entry.mtime)
+                    .with_file_id(entry.file_id),
+            );
         }
     }
 
+    progress.dir_files(dir_path, file_bytes);
+    ctx.queue_dirs(dir_entries.len());
     let dir_nodes: Vec<FileNode> = dir_entries
         .into_par_iter()
-        .map(|(name, child_path)| {
-            let children = scan_dir_recursive(&child_path, progress, root_dev, depth + 1);
-            let mut child_node = FileNode::new_dir(name);
-            child_node.children = children;
-            child_node.size = child_node.children.iter().map(|c| c.size).sum();
-            child_node
-        })
+        .map(|(name, child_path, id)| build_dir_node(ctx, name, &child_path, id, depth))
         .collect();
 
     file_nodes.extend(dir_nodes);
     file_nodes
 }
 
-/// Use getattrlistbulk to read all entries in a directory in bulk.
-/// Returns None if the syscall is unavailable or fails.
-fn read_dir_bulk(dir_path: &Path) -> Option<Vec<BulkEntry>> {
-    let c_path = CString::new(dir_path.as_os_str().as_bytes()).ok()?;
+/// Whether to descend into `child_path`. Directories on other filesystems
+/// (network mounts, iCloud, etc.) are skipped unless crossing filesystems, and
+/// then any directory already entered is; one that vanished since it was
+/// listed is skipped and counted as an error.
+fn should_enter<P: ProgressSink + ?Sized>(ctx: &ScanCtx<'_, P>, child_path: &Path) -> bool {
+    let Some(bounds) = &ctx.bounds else {
+        return true;
+    };
+    match std::fs::symlink_metadata(child_path) {
+        Ok(meta) => bounds.should_enter(child_path, &meta),
+        Err(_) => {
+            ctx.progress.error(child_path);
+            false
+        }
+    }
+}
+
+/// `len` rounded up to a whole number of `block_size` blocks, when rounding.
+fn file_size(len: u64, block_size: Option<u64>) -> u64 {
+    match block_size {
+        Some(block) => round_up_to_block(len, block),
+        None => len,
+    }
+}
+
+/// Recurse into a child directory and wrap its contents in a node, collapsing
+/// bundles into a leaf when requested.
+fn build_dir_node<P: ProgressSink + ?Sized>(
+    ctx: &ScanCtx<'_, P>,
+    name: String,
+    child_path: &Path,
+    file_id: Option<u64>,
+    depth: usize,
+) -> FileNode {
+    let children = scan_dir_recursive(ctx, child_path, depth + 1);
+    ctx.finish_dir();
+    if ctx.options.treat_bundles_as_files && is_bundle_name(&name) {
+        let size = saturating_sum(children.iter().map(|c| c.size));
+        let allocated = saturating_sum(children.iter().map(|c| c.allocated));
+        let mtime = children.iter().map(|c| c.mtime).max().unwrap_or(0);
+        return FileNode::new_file(name, size)
+            .with_allocation(allocated, false)
+            .with_mtime(mtime)
+            .with_file_id(file_id);
+    }
+    let name: Arc<str> = match &ctx.names {
+        Some(names) => names.intern(&name),
+        None => name.into(),
+    };
+    let mut child_node = FileNode::new_dir(name).with_file_id(file_id);
+    child_node.children = children;
+    child_node.update_totals();
+    child_node
+}
+
+/// Why [`read_dir_bulk`] couldn't return a complete listing.
+enum BulkReadError {
+    /// The syscall isn't usable here; retry with plain `readdir`.
+    Unsupported,
+    /// The directory disappeared or became unreadable after its parent was
+    /// listed (`ENOENT`, `EACCES`, `ENOTDIR`).
+    Vanished,
+    /// The process or system is out of file descriptors (`EMFILE`,
+    /// `ENFILE`); plain `readdir` would fail the same way.
+    NoDescriptors,
+    /// Reading failed part-way through (usually the directory was deleted
+    /// mid-scan); carries the entries read before the failure.
+    Interrupted(Vec<BulkEntry>),
+    /// An entry wouldn't fit even the largest buffer, or its length ran past
+    /// the end of the batch. The rest of that batch is lost with it, so the
+    /// whole directory has to be re-read with plain `readdir`.
+    Malformed,
+}
+
+fn is_vanished(errno: i32) -> bool {
+    matches!(errno, libc::ENOENT | libc::EACCES | libc::EPERM | libc::ENOTDIR)
+}
+
+/// Use getattrlistbulk to read all entries in a directory in bulk, including
+/// each entry's file ID when `with_ids` is set, and its resource fork in its
+/// size when `with_rsrc` is.
+fn read_dir_bulk(
+    dir_path: &Path,
+    with_ids: bool,
+    with_rsrc: bool,
+) -> Result<Vec<BulkEntry>, BulkReadError> {
+    let c_path =
+        CString::new(dir_path.as_os_str().as_bytes()).map_err(|_| BulkReadError::Unsupported)?;
     let raw_fd = unsafe { libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_DIRECTORY) };
     if raw_fd < 0 {
-        return None;
+        let err = std::io::Error::last_os_error();
+        let errno = err.raw_os_error().unwrap_or(0);
+        return Err(if is_vanished(errno) {
+            BulkReadError::Vanished
+        } else if out_of_descriptors(&err) {
+            BulkReadError::NoDescriptors
+        } else {
+            BulkReadError::Unsupported
+        });
     }
     let fd = OwnedFd(raw_fd);
 
     let alist = AttrList {
         bitmapcount: ATTR_BIT_MAP_COUNT,
         reserved: 0,
-        commonattr: ATTR_CMN_RETURNED_ATTRS | ATTR_CMN_NAME | ATTR_CMN_OBJTYPE | ATTR_CMN_ERROR,
+        commonattr: ATTR_CMN_RETURNED_ATTRS
+            | ATTR_CMN_NAME
+            | ATTR_CMN_OBJTYPE
+            | ATTR_CMN_MODTIME
+            | ATTR_CMN_FLAGS
+            | if with_ids { ATTR_CMN_FILEID } else { 0 }
+            | ATTR_CMN_ERROR,
         volattr: 0,
         dirattr: 0,
-        fileattr: ATTR_FILE_DATALENGTH,
+        fileattr: ATTR_FILE_ALLOCSIZE
+            | ATTR_FILE_DATALENGTH
+            | if with_rsrc { ATTR_FILE_RSRCLENGTH } else { 0 },
         forkattr: 0,
     };
 
-    let mut buf = vec![0u8; BULK_BUF_SIZE];
+    // Nothing in the loop hands work to rayon, so no other directory can be
+    // read on this thread while the buffer is borrowed
+    BULK_BUF.with_borrow_mut(|buf| {
+        if buf.len() < BULK_BUF_SIZE {
+            buf.resize(BULK_BUF_SIZE, 0);
+        }
+        let results = read_bulk_entries(&fd, &alist, buf);
+        // Don't keep a buffer grown for one huge entry on every worker
+        if buf.len() > BULK_BUF_SIZE {
+            buf.truncate(BULK_BUF_SIZE);
+            buf.shrink_to_fit();
+        }
+        results
+    })
+}
+
+/// Read every entry of the open directory `fd` through `buf`, growing it for
+/// an entry that won't fit.
+fn read_bulk_entries(
+    fd: &OwnedFd,
+    alist: &AttrList,
+    buf: &mut Vec<u8>,
+) -> Result<Vec<BulkEntry>, BulkReadError> {
     let mut results = Vec::with_capacity(256);
 
     loop {
         let count = unsafe {
             getattrlistbulk(
                 fd.0,
-                &alist,
+                alist,
                 buf.as_mut_ptr() as *mut libc::c_void,
-                BULK_BUF_SIZE,
+                buf.len(),
                 0,
             )
         };
 
         if count < 0 {
-            return None;
+            let errno = std::io::Error::last_os_error().raw_os_error().unwrap_or(0);
+            // The next entry doesn't fit; the directory position hasn't moved,
+            // so retry it with more room
+            if errno == libc::ERANGE {
+                if buf.len() >= MAX_BULK_BUF_SIZE {
+                    return Err(BulkReadError::Malformed);
+                }
+                buf.resize(buf.len() * 2, 0);
+                continue;
+            }
+            return Err(if !results.is_empty() || is_vanished(errno) {
+                BulkReadError::Interrupted(results)
+            } else {
+                BulkReadError::Unsupported
+            });
         }
         if count == 0 {
             break;
         }
+        parse_bulk_batch(buf, count as usize, &mut results)?;
+    }
 
-        let mut offset = 0usize;
-        for _ in 0..count {
-            if offset + 4 > BULK_BUF_SIZE {
-                break;
-            }
-
-            let Ok(len_bytes) = buf[offset..offset + 4].try_into() else {
-                break;
-            };
-            let entry_length = u32::from_ne_bytes(len_bytes) as usize;
-
-            if entry_length == 0 || offset + entry_length > BULK_BUF_SIZE {
-                break;
-            }
+    Ok(results)
+}
 
-            if let Some(entry) = parse_bulk_entry(&buf[offset..offset + entry_length]) {
-                results.push(entry);
-            }
+/// Parse the `count` entries getattrlistbulk packed into `buf`, appending
+/// the usable ones to `results`.
+fn parse_bulk_batch(
+    buf: &[u8],
+    count: usize,
+    results: &mut Vec<BulkEntry>,
+) -> Result<(), BulkReadError> {
+    let mut offset = 0usize;
+    for _ in 0..count {
+        // Stopping here would silently drop the rest of the batch
+        let Some(len_bytes) = buf.get(offset..offset.saturating_add(4)) else {
+            return Err(BulkReadError::Malformed);
+        };
+        let entry_length = u32::from_ne_bytes(len_bytes.try_into().unwrap()) as usize;
+        if entry_length == 0 {
+            return Err(BulkReadError::Malformed);
+        }
+        let Some(data) = buf.get(offset..offset.saturating_add(entry_length)) else {
+            return Err(BulkReadError::Malformed);
+        };
 
-            offset += entry_length;
+        if let Some(entry) = parse_bulk_entry(data) {
+            results.push(entry);
         }
+
+        offset += entry_length;
     }
+    Ok(())
+}
 
-    Some(results)
+/// Run a getattrlistbulk batch through the parser: `data` is the entry count
+/// as two little-endian bytes followed by the buffer. For the fuzz target in
+/// `fuzz/`, which looks for panics; the result is thrown away.
+#[cfg(fuzzing)]
+pub fn fuzz_bulk_batch(data: &[u8]) {
+    let Some((count, buf)) = data.split_first_chunk::<2>() else {
+        return;
+    };
+    let mut results = Vec::new();
+    let _ = parse_bulk_batch(buf, usize::from(u16::from_le_bytes(*count)), &mut results);
 }
 
 /// Parse a single entry from the getattrlistbulk buffer.
@@ -208,7 +524,15 @@ fn parse_bulk_entry(data: &[u8]) -> Option<BulkEntry> {
     //   error: u32 (4 bytes) — only if ATTR_CMN_ERROR bit set in returned commonattr
     //   name: attrreference_t { offset: i32, length: u32 } (8 bytes)
     //   objtype: u32 (4 bytes)
-    //   [file_datalength: u64 (8 bytes)] — only for files if fileattr was returned
+    //   modtime: timespec { tv_sec: i64, tv_nsec: i64 } (16 bytes)
+    //   flags: u32 (4 bytes) — st_flags, only if ATTR_CMN_FLAGS returned
+    //   [fileid: u64 (8 bytes)] — only if ATTR_CMN_FILEID was requested
+    //   [file_allocsize: off_t (8 bytes)] — only for files if fileattr was returned
+    //   [file_datalength: off_t (8 bytes)] — likewise
+    //   [file_rsrclength: off_t (8 bytes)] — likewise, only if requested
+    //
+    // Entries can be truncated if the directory changes under us, so every read
+    // is bounds-checked and a short entry is skipped rather than trusted.
 
     const ATTR_SET_SIZE: usize = 20; // attribute_set_t = 5 x u32
     if data.len() < 4 + ATTR_SET_SIZE {
@@ -218,13 +542,13 @@ fn parse_bulk_entry(data: &[u8]) -> Option<BulkEntry> {
     let mut pos = 4; // skip entry length
 
     // Read returned attribute_set_t (NOT AttrList — no bitmapcount/reserved header)
-    let ret_commonattr = u32::from_ne_bytes(data[pos..pos + 4].try_into().ok()?);
-    let ret_fileattr = u32::from_ne_bytes(data[pos + 12..pos + 16].try_into().ok()?);
+    let ret_commonattr = u32::from_ne_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
+    let ret_fileattr = u32::from_ne_bytes(data.get(pos + 12..pos + 16)?.try_into().ok()?);
     pos += ATTR_SET_SIZE; // skip attribute_set_t (20 bytes)
 
     // Error attribute (if present)
     if ret_commonattr & ATTR_CMN_ERROR != 0 {
-        let err = u32::from_ne_bytes(data[pos..pos + 4].try_into().ok()?);
+        let err = u32::from_ne_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
         pos += 4;
         if err != 0 {
             return None; // skip entries with errors
@@ -235,110 +559,244 @@ fn parse_bulk_entry(data: &[u8]) -> Option<BulkEntry> {
     if ret_commonattr & ATTR_CMN_NAME == 0 {
         return None;
     }
-    let name_ref_offset = i32::from_ne_bytes(data[pos..pos + 4].try_into().ok()?);
-    let _name_ref_length = u32::from_ne_bytes(data[pos + 4..pos + 8].try_into().ok()?);
+    let name_ref_offset = i32::from_ne_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
+    let name_ref_length = u32::from_ne_bytes(data.get(pos + 4..pos + 8)?.try_into().ok()?);
     let name_data_start = usize::try_from(
         (pos as i64).checked_add(name_ref_offset as i64)?
     ).ok()?;
     pos += 8;
 
-    let name = if name_data_start < data.len() {
-        let name_slice = &data[name_data_start..];
-        match CStr::from_bytes_until_nul(name_slice) {
-            Ok(cs) => cs.to_string_lossy().to_string(),
-            Err(_) => {
-                // manually find null terminator
-                let end = name_slice.iter().position(|&b| b == 0).unwrap_or(name_slice.len());
-                String::from_utf8_lossy(&name_slice[..end]).to_string()
-            }
-        }
-    } else {
-        return None;
+    // The name's length includes its NUL; keep to it rather than reading on
+    // into whatever follows when the NUL is missing
+    let name_end = name_data_start.checked_add(name_ref_length as usize)?;
+    let name_slice = data.get(name_data_start..name_end.min(data.len()))?;
+    let name = match CStr::from_bytes_until_nul(name_slice) {
+        Ok(cs) => cs.to_string_lossy().to_string(),
+        Err(_) => String::from_utf8_lossy(name_slice).to_string(),
     };
 
-    // Skip . and ..
-    if name == "." || name == ".." {
+    // Skip . and .., and anything nameless
+    if name.is_empty() || name == "." || name == ".." {
         return None;
     }
 
     // Object type
     let obj_type = if ret_commonattr & ATTR_CMN_OBJTYPE != 0 {
-        let t = u32::from_ne_bytes(data[pos..pos + 4].try_into().ok()?);
+        let t = u32::from_ne_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
         pos += 4;
         t
     } else {
         return None;
     };
 
+    let mtime = if ret_commonattr & ATTR_CMN_MODTIME != 0 {
+        let secs = i64::from_ne_bytes(data.get(pos..pos + 8)?.try_into().ok()?);
+        pos += 16;
+        u64::try_from(secs).map_or(0, epoch_secs)
+    } else {
+        0
+    };
+
+    let flags = if ret_commonattr & ATTR_CMN_FLAGS != 0 {
+        let f = u32::from_ne_bytes(data.get(pos..pos + 4)?.try_into().ok()?);
+        pos += 4;
+        f
+    } else {
+        0
+    };
+
+    let file_id = if ret_commonattr & ATTR_CMN_FILEID != 0 {
+        let id = u64::from_ne_bytes(data.get(pos..pos + 8)?.try_into().ok()?);
+        pos += 8;
+        Some(id)
+    } else {
+        None
+    };
+
     let is_dir = obj_type == VDIR;
 
-    // File data length (only present for regular files when fileattr returned)
+    // File sizes (only present for regular files when fileattr returned)
+    let allocsize = if !is_dir && (ret_fileattr & ATTR_FILE_ALLOCSIZE != 0) {
+        let a = u64::from_ne_bytes(data.get(pos..pos + 8)?.try_into().ok()?);
+        pos += 8;
+        Some(a)
+    } else {
+        None
+    };
     let size = if !is_dir && (ret_fileattr & ATTR_FILE_DATALENGTH != 0) {
-        u64::from_ne_bytes(data[pos..pos + 8].try_into().ok()?)
+        let s = u64::from_ne_bytes(data.get(pos..pos + 8)?.try_into().ok()?);
+        pos += 8;
+        s
+    } else {
+        0
+    };
+    let rsrc = if !is_dir && (ret_fileattr & ATTR_FILE_RSRCLENGTH != 0) {
+        u64::from_ne_bytes(data.get(pos..pos + 8)?.try_into().ok()?)
     } else {
         0
     };
 
-    Some(BulkEntry { name, is_dir, size })
+    Some(BulkEntry {
+        name,
+        is_dir,
+        size: size.saturating_add(rsrc),
+        allocated: allocsize.unwrap_or(size.saturating_add(rsrc)),
+        compressed: flags & UF_COMPRESSED != 0,
+        mtime,
+        file_id,
+    })
 }
 
 /// Simple readdir + stat fallback for a single directory when getattrlistbulk fails.
-fn read_dir_fallback(dir_path: &Path, progress: &ScanProgress, root_dev: Option<u64>, depth: usize) -> Vec<FileNode> {
+fn read_dir_fallback<P: ProgressSink + ?Sized>(
+    ctx: &ScanCtx<'_, P>,
+    dir_path: &Path,
+    depth: usize,
+) -> Vec<FileNode> {
+    let progress = ctx.progress;
+    let slot = ctx.open_limit.acquire();
     let entries = match std::fs::read_dir(dir_path) {
         Ok(e) => e,
+        Err(e) if out_of_descriptors(&e) => {
+            ctx.out_of_descriptors(dir_path);
+            return Vec::new();
+        }
         Err(_) => {
-            progress.errors.fetch_add(1, Ordering::Relaxed);
+            progress.error(dir_path);
             return Vec::new();
         }
     };
 
     let mut file_nodes: Vec<FileNode> = Vec::new();
-    let mut dir_entries: Vec<(String, std::path::PathBuf)> = Vec::new();
+    let mut dir_entries: Vec<(String, std::path::PathBuf, Option<u64>)> = Vec::new();
+    let mut file_bytes: u64 = 0;
+    let block_size = ctx.block_size(dir_path);
 
     for entry in entries {
         let entry = match entry {
             Ok(e) => e,
             Err(_) => {
-                progress.errors.fetch_add(1, Ordering::Relaxed);
+                progress.error(dir_path);
                 continue;
             }
         };
         let meta = match entry.metadata() {
             Ok(m) => m,
             Err(_) => {
-                progress.errors.fetch_add(1, Ordering::Relaxed);
+                progress.error(&entry.path());
                 continue;
             }
         };
 
         let name = entry.file_name().to_string_lossy().to_string();
+        if ctx.excludes(dir_path, &name, meta.is_dir()) {
+            continue;
+        }
+        let file_id = ctx.options.capture_ids.then(|| file_id_of(&meta)).flatten();
 
         if meta.is_dir() {
-            progress.dirs_scanned.fetch_add(1, Ordering::Relaxed);
-            // Skip directories on different filesystems (network mounts, iCloud, etc.)
-            if let Some(rd) = root_dev {
-                if get_dev(&entry.path()) != Some(rd) {
-                    continue;
-                }
+            progress.dir();
+            if !should_enter(ctx, &entry.path()) {
+                continue;
             }
-            dir_entries.push((name, entry.path()));
+            dir_entries.push((name, entry.path(), file_id));
         } else {
-            progress.files_scanned.fetch_add(1, Ordering::Relaxed);
-            file_nodes.push(FileNode::new_file(name, meta.len()));
+            let mut size = meta.len();
+            if ctx.options.include_xattrs {
+                let path = entry.path();
+                size = saturating_sum([size, resource_fork_len(&path), xattr_size(&path)]);
+            }
+            let size = file_size(size, block_size);
+            progress.file(size);
+            file_bytes = file_bytes.saturating_add(size);
+            file_nodes.push(
+                FileNode::new_file(name, size)
+                    .with_allocation(allocated_size(&meta), is_compressed(&meta))
+                    .with_mtime(mtime_of(&meta))
+                    .with_file_id(file_id),
+            );
         }
     }
 
+    // The iterator closed the directory once exhausted
+    drop(slot);
+    progress.dir_files(dir_path, file_bytes);
+    ctx.queue_dirs(dir_entries.len());
     let dir_nodes: Vec<FileNode> = dir_entries
         .into_par_iter()
-        .map(|(name, child_path)| {
-            let children = scan_dir_recursive(&child_path, progress, root_dev, depth + 1);
-            let mut child_node = FileNode::new_dir(name);
-            child_node.children = children;
-            child_node.size = child_node.children.iter().map(|c| c.size).sum();
-            child_node
-        })
+        .map(|(name, child_path, id)| build_dir_node(ctx, name, &child_path, id, depth))
         .collect();
 
     file_nodes.extend(dir_nodes);
     file_nodes
 }
+
+/// Length of `path`'s resource fork, or 0 when it has none.
+fn resource_fork_len(path: &Path) -> u64 {
+    std::fs::symlink_metadata(path.join("..namedfork/rsrc")).map_or(0, |m| m.len())
+}
+
+/// Total size of `path`'s extended attributes, not following symlinks. The
+/// resource fork, which shows up among them, is left out: callers count it
+/// separately.
+fn xattr_size(path: &Path) -> u64 {
+    let Ok(c_path) = CString::new(path.as_os_str().as_bytes()) else {
+        return 0;
+    };
+    // A null buffer asks for the length of the name list
+    let list = |buf: *mut libc::c_char, size| unsafe {
+        libc::listxattr(c_path.as_ptr(), buf, size, libc::XATTR_NOFOLLOW)
+    };
+    let len = match usize::try_from(list(std::ptr::null_mut(), 0)) {
+        Ok(len) if len > 0 => len,
+        _ => return 0,
+    };
+    let mut names = vec![0u8; len];
+    // Attributes added since the first call make this fail with ERANGE
+    let Ok(len) = usize::try_from(list(names.as_mut_ptr().cast(), names.len())) else {
+        return 0;
+    };
+    names.truncate(len);
+    let sizes = names
+        .split(|&b| b == 0)
+        .filter(|name| !name.is_empty() && *name != b"com.apple.ResourceFork")
+        .filter_map(|name| {
+            let name = CString::new(name).ok()?;
+            let size = unsafe {
+                libc::getxattr(
+                    c_path.as_ptr(),
+                    name.as_ptr(),
+                    std::ptr::null_mut(),
+                    0,
+                    0,
+                    libc::XATTR_NOFOLLOW,
+                )
+            };
+            u64::try_from(size).ok()
+        });
+    saturating_sum(sizes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner::tests::{tree_to_delete_from, Deleting};
+
+    #[test]
+    fn directory_deleted_mid_scan_is_an_error() {
+        let dir = tree_to_delete_from();
+        let root = dir.path().canonicalize().unwrap();
+        let progress = Deleting {
+            doomed: Some(root.join("doomed")),
+            ..Deleting::default()
+        };
+        let tree = scan_bulk(&root, &progress);
+
+        assert!(!root.join("doomed").exists());
+        assert_eq!(progress.errors.load(Ordering::Relaxed), 1);
+        assert_eq!(tree.size, 200);
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), 200);
+        let keep = tree.children.iter().find(|c| c.name() == "keep").unwrap();
+        assert_eq!(keep.children.len(), 20);
+    }
+}
@@ -1,61 +1,171 @@
-use std::sync::atomic::Ordering;
+use std::path::Path;
+use std::sync::Arc;
 
-use ntfs_reader::api::NtfsAttributeType;
+use ntfs_reader::api::{NtfsAttributeType, EPOCH_DIFFERENCE};
+use ntfs_reader::errors::NtfsReaderError;
 use ntfs_reader::mft::Mft;
 use ntfs_reader::volume::Volume;
 use rayon::prelude::*;
 use rustc_hash::FxHashMap;
 
-use crate::scanner::ScanProgress;
+use crate::scanner::{collapse_trash, epoch_secs, ProgressSink, ScanOptions, SystemFiles};
 use crate::tree::FileNode;
+use crate::utils::decode_utf16_name;
 
 const ROOT_RECORD: u64 = 5;
+/// Records below this are reserved for NTFS metafiles (`$MFT` through
+/// `$Extend` and a few unused slots).
+const FIRST_USER_RECORD: usize = 16;
+/// Name of the directory [`SystemFiles::Group`] gathers metafiles under.
+const SYSTEM_DIR_NAME: &str = "(system)";
 const MAX_DEPTH: usize = 512;
 const PAR_THRESHOLD: usize = 16;
 
 struct MftEntry {
-    name: String,
+    // Shared with the built FileNode so the name is allocated once
+    name: Arc<str>,
+    /// See [`FileNode::utf16_name`].
+    utf16_name: Option<Box<[u16]>>,
     parent_ref: u64,
     size: u64,
+    allocated: u64,
+    compressed: bool,
+    mtime: u32,
     is_dir: bool,
 }
 
+/// Why the MFT couldn't be read directly. Callers fall back to a directory walk
+/// either way; the distinction lets the UI explain why that walk is slower.
+#[derive(Debug)]
+pub enum MftError {
+    /// Raw volume access needs an elevated (administrator) process.
+    AccessDenied,
+    /// The volume isn't NTFS, or its MFT couldn't be parsed.
+    NotNtfs,
+    /// Anything else, with the reader's description.
+    Other(String),
+}
+
+impl MftError {
+    /// A short suggestion worth showing the user, if there is one.
+    pub fn hint(&self) -> Option<&'static str> {
+        match self {
+            MftError::AccessDenied => Some("run as administrator for faster scanning"),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for MftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MftError::AccessDenied => write!(f, "access denied opening the volume"),
+            MftError::NotNtfs => write!(f, "volume is not NTFS"),
+            MftError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for MftError {}
+
+impl From<NtfsReaderError> for MftError {
+    fn from(e: NtfsReaderError) -> Self {
+        match e {
+            NtfsReaderError::ElevationError => MftError::AccessDenied,
+            NtfsReaderError::IOError(io) if io.kind() == std::io::ErrorKind::PermissionDenied => {
+                MftError::AccessDenied
+            }
+            NtfsReaderError::BinReadError(_)
+            | NtfsReaderError::MissingMftAttribute(_)
+            | NtfsReaderError::CorruptMftRecord { .. }
+            | NtfsReaderError::InvalidMftRecord { .. }
+            | NtfsReaderError::CorruptMft { .. } => MftError::NotNtfs,
+            other => MftError::Other(other.to_string()),
+        }
+    }
+}
+
 /// Scan an NTFS volume by reading the MFT directly.
-/// Requires admin privileges. Returns None on any failure.
-pub fn scan_mft(drive_letter: char, progress: &ScanProgress) -> Option<FileNode> {
+/// Requires admin privileges; see [`MftError`] for the failure cases.
+pub fn scan_mft<P: ProgressSink + ?Sized>(
+    drive_letter: char,
+    progress: &P,
+) -> Result<FileNode, MftError> {
+    scan_mft_with_options(drive_letter, progress, &ScanOptions::default())
+}
+
+/// Like [`scan_mft`], but honouring the given [`ScanOptions`]: `capture_ids`
+/// (file IDs are MFT record numbers), `exclude` and `system_files`.
+pub fn scan_mft_with_options<P: ProgressSink + ?Sized>(
+    drive_letter: char,
+    progress: &P,
+    options: &ScanOptions,
+) -> Result<FileNode, MftError> {
+    let capture_ids = options.capture_ids;
     let volume_path = format!("\\\\.\\{}:", drive_letter);
-    let volume = Volume::new(&volume_path).ok()?;
-    let mft = Mft::new(volume).ok()?;
+    let volume = Volume::new(&volume_path)?;
+    let mft = Mft::new(volume)?;
 
     // Use Vec indexed by record number for O(1) lookups
-    let max_record = mft.max_record as usize;
-    let mut entries: Vec<Option<MftEntry>> = Vec::with_capacity(max_record);
-    entries.resize_with(max_record + 1, || None);
+    let record_count = record_count(
+        mft.max_record,
+        mft.volume.volume_size,
+        mft.volume.file_record_size,
+    )
+    .ok_or_else(|| MftError::Other(format!("implausible MFT size: {} records", mft.max_record)))?;
+    let mut entries: Vec<Option<MftEntry>> = Vec::new();
+    entries
+        .try_reserve_exact(record_count)
+        .map_err(|_| MftError::Other(format!("no memory to index {} MFT records", record_count)))?;
+    entries.resize_with(record_count, || None);
 
     mft.iterate_files(|file| {
-        progress.files_scanned.fetch_add(1, Ordering::Relaxed);
-
-        let record_num = file.number() as usize;
+        // The records still have to be read, but not indexed
+        if progress.cancelled() {
+            return;
+        }
+        // Out-of-range numbers are skipped below
+        let record_num = usize::try_from(file.number()).unwrap_or(usize::MAX);
         let is_dir = file.is_directory();
 
         let Some(fname) = file.get_best_file_name(&mft) else {
             return;
         };
 
-        let name = fname.to_string();
+        // Copied out first: the struct is packed
+        let units = fname.data;
+        let units = &units[..usize::from(fname.header.name_length)];
+        let (name, utf16_name) = decode_utf16_name(units);
+        let name: Arc<str> = name.into();
         let parent_ref = fname.parent();
 
-        let size = if is_dir {
-            0
+        let DataSize {
+            size,
+            allocated,
+            compressed,
+        } = if is_dir {
+            DataSize::default()
         } else {
             get_data_size(file)
         };
 
+        let mtime = if is_dir { 0 } else { modified_time(file) };
+
+        if is_dir {
+            progress.dir();
+        } else {
+            progress.file(size);
+        }
+
         if record_num < entries.len() {
             entries[record_num] = Some(MftEntry {
                 name,
+                utf16_name,
                 parent_ref,
                 size,
+                allocated,
+                compressed,
+                mtime,
                 is_dir,
             });
         }
@@ -78,37 +188,130 @@ pub fn scan_mft(drive_letter: char, progress: &ScanProgress) -> Option<FileNode>
 
     let mut root = FileNode::new_dir(root_name.clone());
     if let Some(child_refs) = children_map.get(&ROOT_RECORD) {
-        root.children = child_refs
-            .par_iter()
-            .filter_map(|&child_ref| build_subtree(child_ref, &entries, &children_map, 0))
-            .collect();
+        let build = |refs: &[usize]| -> Vec<FileNode> {
+            refs.par_iter()
+                .filter_map(|&child_ref| {
+                    build_subtree(child_ref, &entries, &children_map, capture_ids, 0)
+                })
+                .collect()
+        };
+        let (system, user): (Vec<usize>, Vec<usize>) =
+            child_refs.iter().partition(|&&r| r < FIRST_USER_RECORD);
+        match options.system_files {
+            SystemFiles::Include => root.children = build(child_refs),
+            SystemFiles::Group => {
+                root.children = build(&user);
+                let mut group = FileNode::new_dir(SYSTEM_DIR_NAME);
+                group.children = build(&system);
+                if !group.children.is_empty() {
+                    group.update_totals();
+                    root.children.push(group);
+                }
+            }
+            SystemFiles::Exclude => root.children = build(&user),
+        }
+    }
+    root.update_totals();
+    if !options.expand_trash {
+        collapse_trash(&mut root, Path::new(&root_name));
+    }
+    if let Some(exclude) = &options.exclude {
+        crate::exclude::prune(&mut root, exclude, progress);
     }
-    root.size = root.children.iter().map(|c| c.size).sum();
-    root.name = root_name;
+    if capture_ids {
+        root.file_id = Some(ROOT_RECORD);
+    }
+    root.name = root_name.into();
     root.sort_by_size();
-    Some(root)
+    Ok(root)
 }
 
-fn get_data_size(file: &ntfs_reader::file::NtfsFile) -> u64 {
-    file.get_attribute(NtfsAttributeType::Data)
-        .map(|attr| {
-            if attr.header.is_non_resident == 0 {
-                attr.resident_header()
-                    .map(|rh| rh.value_length as u64)
-                    .unwrap_or(0)
-            } else {
-                attr.nonresident_header()
-                    .map(|nrh| nrh.data_size)
-                    .unwrap_or(0)
-            }
-        })
-        .unwrap_or(0)
+/// NTFS can't hold more files than this (record numbers are 32-bit in
+/// practice, whatever the on-disk field width).
+const MAX_RECORDS: u64 = u32::MAX as u64;
+
+/// Number of slots needed to index records `0..=max_record`, or None when a
+/// corrupt MFT reports more records than the volume could hold or this
+/// platform could address.
+fn record_count(max_record: u64, volume_size: u64, record_size: u64) -> Option<usize> {
+    let count = max_record.checked_add(1)?;
+    if count > MAX_RECORDS || count > volume_size / record_size.max(1) {
+        return None;
+    }
+    usize::try_from(count).ok()
+}
+
+/// Logical and on-disk sizes of a file's unnamed `$DATA` stream.
+#[derive(Default)]
+struct DataSize {
+    size: u64,
+    allocated: u64,
+    compressed: bool,
+}
+
+/// Modification time from `$STANDARD_INFORMATION`, as Unix seconds. NTFS
+/// counts 100ns ticks from 1601.
+fn modified_time(file: &ntfs_reader::file::NtfsFile) -> u32 {
+    let Some(info) = file
+        .get_attribute(NtfsAttributeType::StandardInformation)
+        .and_then(|attr| attr.as_standard_info())
+    else {
+        return 0;
+    };
+    // Copied out first: the struct is packed
+    let ticks = info.modification_time;
+    ticks
+        .checked_sub(EPOCH_DIFFERENCE)
+        .map_or(0, |t| epoch_secs(t / 10_000_000))
+}
+
+/// `$DATA` attribute header flag for NTFS (LZNT1) compression.
+const ATTR_FLAG_COMPRESSED: u16 = 0x0001;
+/// Compressed/sparse non-resident attributes carry an extra `compressed_size`
+/// field right after the standard non-resident header.
+const COMPRESSED_SIZE_OFFSET: usize = 0x40;
+
+fn get_data_size(file: &ntfs_reader::file::NtfsFile) -> DataSize {
+    let Some(attr) = file.get_attribute(NtfsAttributeType::Data) else {
+        return DataSize::default();
+    };
+    if attr.header.is_non_resident == 0 {
+        // Resident data lives inside the MFT record and takes no clusters
+        let size = attr
+            .resident_header()
+            .map(|rh| rh.value_length as u64)
+            .unwrap_or(0);
+        return DataSize {
+            size,
+            allocated: size,
+            compressed: false,
+        };
+    }
+    let Some(nrh) = attr.nonresident_header() else {
+        return DataSize::default();
+    };
+    let compressed = attr.header.flags & ATTR_FLAG_COMPRESSED != 0;
+    let allocated = if compressed {
+        attr.data()
+            .get(COMPRESSED_SIZE_OFFSET..COMPRESSED_SIZE_OFFSET + 8)
+            .and_then(|b| b.try_into().ok())
+            .map(u64::from_le_bytes)
+            .unwrap_or(nrh.allocated_size)
+    } else {
+        nrh.allocated_size
+    };
+    DataSize {
+        size: nrh.data_size,
+        allocated,
+        compressed,
+    }
 }
 
 fn build_subtree(
     ref_num: usize,
     entries: &[Option<MftEntry>],
     children_map: &FxHashMap<u64, Vec<usize>>,
+    capture_ids: bool,
     depth: usize,
 ) -> Option<FileNode> {
     if depth >= MAX_DEPTH {
@@ -123,7 +326,9 @@ fn build_subtree(
                 child_refs
                     .par_iter()
                     .filter(|&&cr| cr != ref_num)
-                    .filter_map(|&cr| build_subtree(cr, entries, children_map, depth + 1))
+                    .filter_map(|&cr| {
+                        build_subtree(cr, entries, children_map, capture_ids, depth + 1)
+                    })
                     .collect()
             } else {
                 let mut v = Vec::with_capacity(child_refs.len());
@@ -132,7 +337,7 @@ fn build_subtree(
                         continue;
                     }
                     if let Some(child) =
-                        build_subtree(child_ref, entries, children_map, depth + 1)
+                        build_subtree(child_ref, entries, children_map, capture_ids, depth + 1)
                     {
                         v.push(child);
                     }
@@ -146,16 +351,42 @@ fn build_subtree(
         Vec::new()
     };
 
-    let size = if entry.is_dir {
-        children.iter().map(|c| c.size).sum()
-    } else {
-        entry.size
-    };
-
-    Some(FileNode {
+    let mut node = FileNode {
         name: entry.name.clone(),
-        size,
+        size: entry.size,
+        allocated: entry.allocated,
         is_dir: entry.is_dir,
+        compressed: entry.compressed,
+        mtime: entry.mtime,
+        unexpanded: false,
+        trash: false,
+        file_id: capture_ids.then_some(ref_num as u64),
+        utf16_name: entry.utf16_name.clone(),
         children,
-    })
+    };
+    if entry.is_dir {
+        node.update_totals();
+    }
+    Some(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_count_limits() {
+        const VOLUME: u64 = 1 << 40;
+        assert_eq!(record_count(0, VOLUME, 1024), Some(1));
+        assert_eq!(record_count(99_999, VOLUME, 1024), Some(100_000));
+        assert_eq!(record_count(MAX_RECORDS - 1, 1 << 60, 1024), Some(MAX_RECORDS as usize));
+        assert_eq!(record_count(MAX_RECORDS, 1 << 60, 1024), None);
+        assert_eq!(record_count(u64::MAX, VOLUME, 1024), None);
+        // More records than fit on the volume
+        assert_eq!(record_count(1023, 1024 * 1024, 1024), Some(1024));
+        assert_eq!(record_count(1024, 1024 * 1024, 1024), None);
+        // A zero record size is read as one byte rather than dividing by zero
+        assert_eq!(record_count(9, 10, 0), Some(10));
+        assert_eq!(record_count(10, 10, 0), None);
+    }
 }
@@ -0,0 +1,141 @@
+//! Progress pushed to a consumer as it happens, for front ends that stream it
+//! (the CLI's `--json` mode) rather than poll [`ScanProgress`] counters.
+//!
+//! [`ChannelProgress`] sends a [`ProgressUpdate`] at most once per interval
+//! into a bounded channel. When the channel is full the update is dropped
+//! rather than queued or waited for: every update carries running totals, so
+//! a slow reader just sees fewer of them and the scan never stalls on it.
+//! Dropping the receiver cancels the scan (see [`ProgressSink::cancelled`]).
+
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::scanner::{ProgressSink, ScanProgress};
+
+/// Running totals at one point during a scan.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ProgressUpdate {
+    pub files: u64,
+    pub dirs: u64,
+    /// Logical size of the files counted so far.
+    pub bytes: u64,
+    pub errors: u64,
+    /// The directory being read most recently.
+    pub current: String,
+}
+
+/// A [`ProgressSink`] that keeps the usual [`ScanProgress`] counters and also
+/// sends throttled [`ProgressUpdate`]s down a bounded channel.
+pub struct ChannelProgress {
+    /// Counters, exclusions and mounts, as a polled scan would record them.
+    pub counters: ScanProgress,
+    sender: SyncSender<ProgressUpdate>,
+    interval: Duration,
+    /// When the next update is due.
+    next_send: Mutex<Instant>,
+    cancelled: AtomicBool,
+}
+
+impl ChannelProgress {
+    /// An adapter sending at most one update per `interval`, holding up to
+    /// `capacity` unread ones, and the receiving end of its channel.
+    pub fn new(interval: Duration, capacity: usize) -> (Self, Receiver<ProgressUpdate>) {
+        let (sender, receiver) = sync_channel(capacity);
+        let progress = Self {
+            counters: ScanProgress::new(),
+            sender,
+            interval,
+            next_send: Mutex::new(Instant::now()),
+            cancelled: AtomicBool::new(false),
+        };
+        (progress, receiver)
+    }
+
+    /// The totals so far.
+    pub fn update(&self) -> ProgressUpdate {
+        let counters = &self.counters;
+        ProgressUpdate {
+            files: counters.files_scanned.load(Ordering::Relaxed),
+            dirs: counters.dirs_scanned.load(Ordering::Relaxed),
+            bytes: counters.bytes_scanned.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+            current: counters
+                .current_path
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        }
+    }
+
+    /// Send an update if one is due. Never blocks: another thread already
+    /// sending, or a full channel, means this one is skipped.
+    fn maybe_send(&self) {
+        let Ok(mut next_send) = self.next_send.try_lock() else {
+            return;
+        };
+        let now = Instant::now();
+        if now < *next_send {
+            return;
+        }
+        *next_send = now + self.interval;
+        drop(next_send);
+        if let Err(TrySendError::Disconnected(_)) = self.sender.try_send(self.update()) {
+            self.cancelled.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+impl ProgressSink for ChannelProgress {
+    fn file(&self, size: u64) {
+        self.counters.file(size);
+    }
+
+    fn dir(&self) {
+        self.counters.dir();
+        // Every scanner reports directories, and often enough to pace updates
+        self.maybe_send();
+    }
+
+    fn error(&self, path: &Path) {
+        self.counters.error(path);
+    }
+
+    fn set_current(&self, path: &Path) {
+        self.counters.set_current(path);
+    }
+
+    fn excluded(&self, count: u64) {
+        self.counters.excluded(count);
+    }
+
+    fn mount(&self, path: &Path, fs_type: Option<&str>) {
+        self.counters.mount(path, fs_type);
+    }
+
+    fn bulk_fallback(&self, path: &Path) {
+        self.counters.bulk_fallback(path);
+    }
+
+    fn cycle(&self, path: &Path) {
+        self.counters.cycle(path);
+    }
+
+    fn fd_exhausted(&self, path: &Path) {
+        self.counters.fd_exhausted(path);
+    }
+
+    fn pending_dirs(&self, count: u64) {
+        self.counters.pending_dirs(count);
+    }
+
+    fn dir_files(&self, dir: &Path, bytes: u64) {
+        self.counters.dir_files(dir, bytes);
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
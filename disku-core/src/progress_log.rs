@@ -0,0 +1,91 @@
+//! A progress log for long unattended scans: every few seconds, a line with
+//! the running [`ScanProgress`] totals, appended to a file that can be
+//! followed with `tail -f`.
+//!
+//! Lines are written from a thread of their own polling the counters, so the
+//! log keeps going however busy (or absent) the UI is, and a scan that hangs
+//! shows up as the same totals repeating with the timestamp moving on. Each
+//! line looks like
+//!
+//! ```text
+//! 2024-03-09T14:05:00Z scanning files=18204 dirs=1312 bytes=7514880215 errors=0 current=/srv
+//! ```
+//!
+//! with `done` in place of `scanning` on the last one.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::scanner::ScanProgress;
+use crate::utils::format_utc;
+
+/// Time between lines.
+pub const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A running progress log. Dropping it writes the final line and stops the
+/// thread.
+pub struct ProgressLog {
+    /// Dropped to stop the thread.
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProgressLog {
+    /// Start appending `progress` to the file at `path` every `interval`,
+    /// creating the file if needed.
+    pub fn start(path: &Path, progress: &ScanProgress, interval: Duration) -> io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let progress = progress.clone();
+        let (stop, stopped) = channel::<()>();
+        let thread = std::thread::spawn(move || loop {
+            let done = match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => false,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => true,
+            };
+            // A log that can't be written isn't worth stopping the scan for
+            if write_line(&mut file, &progress, done).is_err() || done {
+                return;
+            }
+        });
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for ProgressLog {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn write_line(file: &mut File, progress: &ScanProgress, done: bool) -> io::Result<()> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let current = progress
+        .current_path
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    writeln!(
+        file,
+        "{} {} files={} dirs={} bytes={} errors={} current={}",
+        format_utc(now),
+        if done { "done" } else { "scanning" },
+        progress.files_scanned.load(Ordering::Relaxed),
+        progress.dirs_scanned.load(Ordering::Relaxed),
+        progress.bytes_scanned.load(Ordering::Relaxed),
+        progress.errors.load(Ordering::Relaxed),
+        current
+    )
+}
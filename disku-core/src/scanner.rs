@@ -1,16 +1,99 @@
+use std::cmp::Reverse;
+use std::convert::Infallible;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
 
-use jwalk::WalkDir;
+use jwalk::{Parallelism, WalkDir};
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use crate::tree::{build_tree, FileNode};
+use crate::budget::{count_unexpanded, scan_with_budget};
+use crate::exclude::ExcludeSet;
+use crate::tree::{
+    build_tree, count_entries, saturating_sum, FileNode, FlatEntry, MountPoint, NameInterner,
+    ScanResult,
+};
+use crate::utils::{fs_type, is_rotational, open_file_limit, root_name, BlockSizeCache};
 
+/// Receives progress notifications from the scanners.
+///
+/// Scanners call these from many rayon workers at once, so implementations
+/// must be cheap and thread-safe. [`ScanProgress`] is the stock implementation
+/// used by the TUI and GUI.
+pub trait ProgressSink: Sync {
+    /// A non-directory entry of `size` bytes was recorded.
+    fn file(&self, size: u64);
+    /// A directory entry was recorded.
+    fn dir(&self);
+    /// An entry at `path` could not be read and was skipped.
+    fn error(&self, path: &Path);
+    /// The scanner started reading the directory at `path`.
+    fn set_current(&self, path: &Path);
+    /// `count` entries matched [`ScanOptions::exclude`] and were left out
+    /// (an excluded directory counts once, not per entry inside it).
+    fn excluded(&self, _count: u64) {}
+    /// The directory at `path` is where a different filesystem (of type
+    /// `fs_type`, when known) begins. Only reported when crossing filesystems.
+    fn mount(&self, _path: &Path, _fs_type: Option<&str>) {}
+    /// The macOS bulk scanner got a listing of the directory at `path` it
+    /// couldn't fully parse, and re-read it with plain `readdir` instead.
+    fn bulk_fallback(&self, _path: &Path) {}
+    /// The directory at `path` had been entered already, by device and
+    /// inode, through another path (a bind mount or mount loop), and was
+    /// skipped. Only detected when crossing filesystems.
+    fn cycle(&self, _path: &Path) {}
+    /// The directory at `path` couldn't be opened because the process (or
+    /// the system) ran out of file descriptors, and was skipped. Reported
+    /// along with [`error`](Self::error); see [`ScanOptions::max_open_dirs`].
+    fn fd_exhausted(&self, _path: &Path) {}
+    /// `count` directories have been queued and not yet finished with. Only
+    /// the scanners that queue directories report it; it's for seeing how
+    /// well a scan parallelizes (a queue shorter than the thread count leaves
+    /// threads idle).
+    fn pending_dirs(&self, _count: u64) {}
+    /// The files read directly in the directory at `dir` came to `bytes`. A
+    /// directory may be reported in several parts. Lets a sink keep running
+    /// totals by location while the tree is still being built; not reported
+    /// by the MFT scanner, which only has paths once the whole MFT is read.
+    fn dir_files(&self, _dir: &Path, _bytes: u64) {}
+    /// Whether whoever wanted this scan has gone away. Scanners check between
+    /// directories and, once it's true, stop and return the (incomplete) tree
+    /// found so far.
+    fn cancelled(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Clone)]
 pub struct ScanProgress {
     pub files_scanned: Arc<AtomicU64>,
     pub dirs_scanned: Arc<AtomicU64>,
+    /// Logical size of the files counted so far.
+    pub bytes_scanned: Arc<AtomicU64>,
     pub errors: Arc<AtomicU64>,
+    pub excluded: Arc<AtomicU64>,
+    /// Directories re-read after a malformed bulk listing (see
+    /// [`ProgressSink::bulk_fallback`]). Should stay 0; anything else is worth
+    /// a bug report.
+    pub bulk_fallbacks: Arc<AtomicU64>,
+    /// Directories skipped as already entered (see [`ProgressSink::cycle`]).
+    pub cycles_detected: Arc<AtomicU64>,
+    /// Directories skipped for want of file descriptors (see
+    /// [`ProgressSink::fd_exhausted`]).
+    pub fd_exhausted: Arc<AtomicU64>,
+    /// Directories queued and not yet finished, as last reported through
+    /// [`ProgressSink::pending_dirs`].
+    pub pending_dirs: Arc<AtomicU64>,
     pub current_path: Arc<Mutex<String>>,
+    /// Filesystems the scan crossed into; copy into [`ScanResult::mounts`]
+    /// with [`take_mounts`](Self::take_mounts).
+    ///
+    /// [`ScanResult::mounts`]: crate::tree::ScanResult::mounts
+    pub mounts: Arc<Mutex<Vec<MountPoint>>>,
+    /// Bytes found so far under each of the root's entries, when asked for
+    /// with [`with_live_totals`](Self::with_live_totals).
+    pub live: Option<Arc<Mutex<LiveTotals>>>,
 }
 
 impl ScanProgress {
@@ -18,44 +101,1095 @@ impl ScanProgress {
         Self {
             files_scanned: Arc::new(AtomicU64::new(0)),
             dirs_scanned: Arc::new(AtomicU64::new(0)),
+            bytes_scanned: Arc::new(AtomicU64::new(0)),
             errors: Arc::new(AtomicU64::new(0)),
+            excluded: Arc::new(AtomicU64::new(0)),
+            bulk_fallbacks: Arc::new(AtomicU64::new(0)),
+            cycles_detected: Arc::new(AtomicU64::new(0)),
+            fd_exhausted: Arc::new(AtomicU64::new(0)),
+            pending_dirs: Arc::new(AtomicU64::new(0)),
             current_path: Arc::new(Mutex::new(String::new())),
+            mounts: Arc::new(Mutex::new(Vec::new())),
+            live: None,
+        }
+    }
+
+    /// Also keep [`LiveTotals`] for a scan of `root`, for previewing the
+    /// scan before it finishes.
+    pub fn with_live_totals(mut self, root: &Path) -> Self {
+        self.live = Some(Arc::new(Mutex::new(LiveTotals::new(root))));
+        self
+    }
+
+    /// The mount points reported so far, leaving none behind.
+    pub fn take_mounts(&self) -> Vec<MountPoint> {
+        std::mem::take(&mut *self.mounts.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl ProgressSink for ScanProgress {
+    fn file(&self, size: u64) {
+        self.files_scanned.fetch_add(1, Ordering::Relaxed);
+        self.bytes_scanned.fetch_add(size, Ordering::Relaxed);
+    }
+
+    fn dir(&self) {
+        self.dirs_scanned.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn error(&self, _path: &Path) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn excluded(&self, count: u64) {
+        self.excluded.fetch_add(count, Ordering::Relaxed);
+    }
+
+    fn bulk_fallback(&self, _path: &Path) {
+        self.bulk_fallbacks.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn cycle(&self, _path: &Path) {
+        self.cycles_detected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn fd_exhausted(&self, _path: &Path) {
+        self.fd_exhausted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn pending_dirs(&self, count: u64) {
+        self.pending_dirs.store(count, Ordering::Relaxed);
+    }
+
+    fn mount(&self, path: &Path, fs_type: Option<&str>) {
+        self.mounts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(MountPoint {
+                path: path.to_path_buf(),
+                fs_type: fs_type.map(str::to_string),
+            });
+    }
+
+    fn set_current(&self, path: &Path) {
+        // Best effort: skip the update rather than block a scanner thread
+        if let Ok(mut cp) = self.current_path.try_lock() {
+            *cp = path.to_string_lossy().to_string();
+        }
+    }
+
+    fn dir_files(&self, dir: &Path, bytes: u64) {
+        if let Some(live) = &self.live {
+            live.lock().unwrap_or_else(|e| e.into_inner()).add(dir, bytes);
+        }
+    }
+}
+
+/// Logical bytes a scan in progress has found so far, totalled by the scan
+/// root's top-level directories, as reported through
+/// [`ProgressSink::dir_files`]. Enough for a rough, growing picture of where
+/// the space is while the real tree is still being built.
+#[derive(Debug)]
+pub struct LiveTotals {
+    root: PathBuf,
+    /// Bytes in files directly in the root.
+    own: u64,
+    /// Bytes under each top-level directory, by name.
+    dirs: FxHashMap<String, u64>,
+}
+
+impl LiveTotals {
+    pub fn new(root: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            own: 0,
+            dirs: FxHashMap::default(),
+        }
+    }
+
+    /// Count `bytes` of files in `dir`, under whichever top-level directory
+    /// it's in. Directories outside the root are ignored.
+    fn add(&mut self, dir: &Path, bytes: u64) {
+        let Ok(rel) = dir.strip_prefix(&self.root) else {
+            return;
+        };
+        if bytes == 0 {
+            return;
+        }
+        let total = match rel.components().next() {
+            Some(top) => {
+                let name = top.as_os_str().to_string_lossy().into_owned();
+                self.dirs.entry(name).or_default()
+            }
+            None => &mut self.own,
+        };
+        *total = total.saturating_add(bytes);
+    }
+
+    /// The totals so far as a tree one level deep: the root, with a childless
+    /// directory per top-level directory that has any bytes yet, largest
+    /// first. The root's size includes its own files, which aren't listed.
+    /// `None` until anything has been counted.
+    pub fn snapshot(&self) -> Option<FileNode> {
+        if self.own == 0 && self.dirs.is_empty() {
+            return None;
+        }
+        let mut root = FileNode::new_dir(root_name(&self.root));
+        root.children = self
+            .dirs
+            .iter()
+            .map(|(name, &bytes)| {
+                let mut dir = FileNode::new_dir(name.as_str());
+                dir.size = bytes;
+                dir.allocated = bytes;
+                dir
+            })
+            .collect();
+        root.children.sort_unstable_by_key(|c| Reverse(c.size));
+        root.size = self.own.saturating_add(saturating_sum(root.children.iter().map(|c| c.size)));
+        root.allocated = root.size;
+        Some(root)
+    }
+}
+
+/// Tunables that change how a scan builds its tree. Defaults match the
+/// historical behaviour of every scanner.
+#[derive(Debug, Clone, Default)]
+pub struct ScanOptions {
+    /// Record macOS bundle directories (`.app`, `.framework`, `.bundle`, ...) as a
+    /// single leaf carrying the bundle's total size instead of exposing its
+    /// internals.
+    pub treat_bundles_as_files: bool,
+    /// Share one allocation between identical directory names. Cuts memory on
+    /// trees full of repeated `node_modules`/`.git`-style names at the cost of
+    /// a hash lookup per directory. Not applied by the MFT scanner.
+    pub intern_names: bool,
+    /// Round every file up to its filesystem's block size (`statvfs`
+    /// `f_bsize`) before summing, matching `du`'s default output. Unix only.
+    pub round_to_blocks: bool,
+    /// Record each entry's inode / file ID / MFT record number in
+    /// [`FileNode::file_id`]. Off by default: it costs an extra stat per
+    /// directory in the jwalk scanner. Not available over SFTP or for
+    /// non-NTFS volumes on Windows.
+    pub capture_ids: bool,
+    /// Descend into directories on other filesystems (mounted drives, network
+    /// shares) instead of stopping at the scan root's device. Each directory
+    /// is then entered at most once by device and inode, so bind mounts and
+    /// mount loops can't recurse forever; the repeats skipped are reported
+    /// through [`ProgressSink::cycle`]. Unix only.
+    pub cross_filesystems: bool,
+    /// Leave out entries matching these gitignore-style patterns (see
+    /// [`crate::exclude`]); excluded directories aren't descended into.
+    pub exclude: Option<Arc<ExcludeSet>>,
+    /// What the MFT scanner does with NTFS metafiles. Windows only.
+    pub system_files: SystemFiles,
+    /// Scan into trash folders like any other directory. By default each one
+    /// (the Recycle Bin, `~/.Trash`, `~/.local/share/Trash`, and per-volume
+    /// `.Trashes` and `.Trash-<uid>`) becomes a single leaf flagged
+    /// [`FileNode::trash`], so its size shows without it dominating the
+    /// drill-down. Not applied over SFTP.
+    pub expand_trash: bool,
+    /// Read only [`SPINDLE_THREADS`] directories at a time, mostly depth-first,
+    /// so a spinning disk isn't made to seek back and forth between many
+    /// directories read in parallel. `None` turns it on when the scan root is
+    /// on a rotational disk (see [`crate::utils::is_rotational`]). Not applied
+    /// by the MFT scanner, which reads the MFT in one sequential pass anyway,
+    /// or over SFTP.
+    pub spindle_friendly: Option<bool>,
+    /// Count each file's resource fork and extended attributes in its size,
+    /// as `du` does, rather than its data alone. Costs a few syscalls per
+    /// file. macOS only, and not applied by time-limited scans.
+    pub include_xattrs: bool,
+    /// Most directories held open at once, so a wide tree read in parallel
+    /// can't run the process out of file descriptors. `None` allows half the
+    /// `RLIMIT_NOFILE` soft limit, leaving the rest for everything else.
+    /// Applied by the macOS and time-limited scanners, which open directories
+    /// themselves.
+    pub max_open_dirs: Option<usize>,
+}
+
+/// Worker threads of a [`ScanOptions::spindle_friendly`] scan. Two keeps one
+/// read queued behind the one in flight without scattering the head.
+pub const SPINDLE_THREADS: usize = 2;
+
+/// Whether to scan `root` with [`ScanOptions::spindle_friendly`] in effect.
+pub(crate) fn spindle_friendly(root: &Path, options: &ScanOptions) -> bool {
+    options
+        .spindle_friendly
+        .unwrap_or_else(|| is_rotational(root) == Some(true))
+}
+
+/// Run `scan` on a pool of [`SPINDLE_THREADS`] threads when
+/// [`spindle_friendly`] says so, and on the global pool otherwise. Rayon's
+/// workers take their newest task first, so nested directory reads go
+/// depth-first.
+pub(crate) fn in_scan_pool<T: Send>(
+    root: &Path,
+    options: &ScanOptions,
+    scan: impl FnOnce() -> T + Send,
+) -> T {
+    if spindle_friendly(root, options) {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(SPINDLE_THREADS)
+            .build();
+        // Failing to start threads, scan as usual rather than not at all
+        if let Ok(pool) = pool {
+            return pool.install(scan);
+        }
+    }
+    scan()
+}
+
+/// NTFS metafiles (`$MFT`, `$LogFile`, `$Bitmap`, ... in MFT records 0-15,
+/// plus everything under `$Extend`) can run to gigabytes and aren't user
+/// data. Including them is what makes a volume's total match `chkdsk`'s used
+/// space; Explorer never shows them, so its folder totals come out smaller.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SystemFiles {
+    /// List them at the volume root like any other entry.
+    #[default]
+    Include,
+    /// Gather them under a single `(system)` directory at the root.
+    Group,
+    /// Leave them out of the tree and its totals.
+    Exclude,
+}
+
+impl std::str::FromStr for SystemFiles {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, ()> {
+        match s {
+            "include" => Ok(SystemFiles::Include),
+            "group" => Ok(SystemFiles::Group),
+            "exclude" => Ok(SystemFiles::Exclude),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Directory extensions that Finder presents as a single item.
+const BUNDLE_EXTENSIONS: &[&str] = &[
+    "app", "appex", "bundle", "framework", "kext", "plugin", "xpc",
+];
+
+/// Whether a directory name carries one of the known bundle extensions.
+pub(crate) fn is_bundle_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|ext| {
+            BUNDLE_EXTENSIONS
+                .iter()
+                .any(|b| ext.eq_ignore_ascii_case(b))
+        })
+}
+
+/// Turn every bundle directory below `node` into a leaf, keeping its size.
+pub(crate) fn collapse_bundles(node: &mut FileNode) {
+    node.for_each_parent_mut(|node| {
+        for child in node.children.iter_mut() {
+            if child.is_dir && is_bundle_name(&child.name) {
+                child.is_dir = false;
+                child.children = Vec::new();
+            }
+        }
+    });
+}
+
+/// Trash folders in the user's home directory, relative to it (macOS and
+/// freedesktop).
+const HOME_TRASH_DIRS: &[&str] = &[".Trash", ".local/share/Trash"];
+
+/// Directory names that are a trash folder wherever they appear: the Windows
+/// Recycle Bin, and the per-volume trash of macOS and freedesktop.
+pub(crate) fn is_trash_name(name: &str) -> bool {
+    name.eq_ignore_ascii_case("$Recycle.Bin")
+        || name == ".Trashes"
+        || name
+            .strip_prefix(".Trash-")
+            .is_some_and(|uid| !uid.is_empty() && uid.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Replace every trash folder below `tree`, which was scanned from `root`,
+/// with a leaf carrying its totals (see [`ScanOptions::expand_trash`]). The
+/// scan root itself is left alone, so scanning a trash folder directly still
+/// shows its contents.
+pub(crate) fn collapse_trash(tree: &mut FileNode, root: &Path) {
+    fn collapse(node: &mut FileNode) {
+        node.is_dir = false;
+        node.trash = true;
+        node.children = Vec::new();
+    }
+    tree.for_each_parent_mut(|node| {
+        for child in node.children.iter_mut().filter(|c| c.is_dir) {
+            if is_trash_name(&child.name) {
+                collapse(child);
+            }
+        }
+    });
+    let Some(home) = crate::config::home_dir() else {
+        return;
+    };
+    'dirs: for dir in HOME_TRASH_DIRS {
+        let trash = home.join(dir);
+        let Ok(rel) = trash.strip_prefix(root) else {
+            continue;
+        };
+        if rel.as_os_str().is_empty() {
+            continue;
         }
+        let mut node = &mut *tree;
+        for part in rel.components() {
+            let Some(child) = node
+                .children
+                .iter_mut()
+                .find(|c| c.is_dir && c.os_name() == part.as_os_str())
+            else {
+                continue 'dirs;
+            };
+            node = child;
+        }
+        collapse(node);
+    }
+}
+
+/// Build a single-node tree when the scan target is a regular file rather than
+/// a directory. Returns None for directories and paths that can't be stat'ed.
+pub fn scan_file<P: ProgressSink + ?Sized>(path: &Path, progress: &P) -> Option<FileNode> {
+    let meta = std::fs::metadata(path).ok()?;
+    if meta.is_dir() {
+        return None;
     }
+    progress.set_current(path);
+    progress.file(meta.len());
+    Some(
+        FileNode::new_file(root_name(path), meta.len())
+            .with_allocation(allocated_size(&meta), is_compressed(&meta))
+            .with_mtime(mtime_of(&meta)),
+    )
 }
 
-pub fn scan(root: &Path, progress: &ScanProgress) -> FileNode {
+pub fn scan<P: ProgressSink + ?Sized>(root: &Path, progress: &P) -> FileNode {
+    scan_with_options(root, progress, &ScanOptions::default())
+}
+
+/// Like [`scan`], but honouring the given [`ScanOptions`].
+pub fn scan_with_options<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+) -> FileNode {
+    if let Some(node) = scan_file(root, progress) {
+        return node;
+    }
+
+    let block_sizes = options.round_to_blocks.then(BlockSizeCache::new);
+
     // jwalk parallelizes directory reading across threads
-    let flat: Vec<(PathBuf, bool, u64)> = WalkDir::new(root)
-        .skip_hidden(false)
+    let mut walk = WalkDir::new(root).skip_hidden(false);
+    if spindle_friendly(root, options) {
+        walk = walk.parallelism(Parallelism::RayonNewPool(SPINDLE_THREADS));
+    }
+    let bounds = DeviceBounds::new(root, options.cross_filesystems).map(Arc::new);
+    let excluded = Arc::new(AtomicU64::new(0));
+    // Directories jwalk has queued and not yet read, for `pending_dirs`
+    let pending = Arc::new(AtomicU64::new(0));
+    {
+        let filter = bounds.is_some() || options.exclude.is_some();
+        let exclude = options.exclude.clone();
+        let excluded = excluded.clone();
+        let bounds = bounds.clone();
+        let pending = pending.clone();
+        let root = root.to_path_buf();
+        // Drop entries before jwalk records them or queues their contents. A
+        // directory that can't be stat'ed is kept so jwalk reports the error
+        // when reading it. The subdirectories left are what jwalk queues.
+        walk = walk.process_read_dir(move |depth, _, _, children| {
+            // The root itself arrives first, as the only child of depth `None`
+            if depth.is_some() && filter {
+                children.retain(|entry| {
+                    let Ok(e) = entry else {
+                        return true;
+                    };
+                    let is_dir = e.file_type().is_dir();
+                    if let Some(exclude) = &exclude {
+                        let path = e.path();
+                        let rel = path.strip_prefix(&root).unwrap_or(&path);
+                        if exclude.is_excluded(rel, is_dir) {
+                            excluded.fetch_add(1, Ordering::Relaxed);
+                            return false;
+                        }
+                    }
+                    match &bounds {
+                        Some(bounds) if is_dir => {
+                            let path = e.path();
+                            std::fs::symlink_metadata(&path)
+                                .map_or(true, |m| bounds.should_enter(&path, &m))
+                        }
+                        _ => true,
+                    }
+                });
+            }
+            let subdirs = children
+                .iter()
+                .filter(|e| e.as_ref().is_ok_and(|e| e.file_type().is_dir()))
+                .count() as u64;
+            pending.fetch_add(subdirs, Ordering::Relaxed);
+            if depth.is_some() {
+                finish_pending(&pending);
+            }
+        });
+    }
+    // The directory whose files the walk is in the middle of, and their bytes
+    // so far, passed to `dir_files` once the walk moves on
+    let mut run: Option<(Arc<Path>, u64)> = None;
+    let flat: Vec<FlatEntry> = walk
         .into_iter()
+        .take_while(|_| !progress.cancelled())
         .filter_map(|entry| {
             match entry {
                 Ok(e) => {
                     let path = e.path();
                     let is_dir = e.file_type().is_dir();
-                    if is_dir {
-                        progress.dirs_scanned.fetch_add(1, Ordering::Relaxed);
-                        if let Ok(mut cp) = progress.current_path.try_lock() {
-                            *cp = path.to_string_lossy().to_string();
-                        }
+                    // Directories only need a stat when their ID was asked for
+                    let meta = if is_dir && !options.capture_ids {
+                        None
                     } else {
-                        progress.files_scanned.fetch_add(1, Ordering::Relaxed);
+                        e.metadata().ok()
+                    };
+                    // A file deleted since its directory was listed: record
+                    // and skip it rather than list it as empty
+                    if meta.is_none() && !is_dir {
+                        progress.error(&path);
+                        return None;
                     }
-                    let size = if is_dir {
-                        0
-                    } else {
-                        e.metadata().map(|m| m.len()).unwrap_or(0)
+                    let (size, allocated, compressed, mtime) = match &meta {
+                        Some(m) if !is_dir => {
+                            let len = match &block_sizes {
+                                Some(cache) => block_rounded_len(m, &path, cache),
+                                None => m.len(),
+                            };
+                            (len, allocated_size(m), is_compressed(m), mtime_of(m))
+                        }
+                        _ => (0, 0, false, 0),
                     };
-                    Some((path, is_dir, size))
+                    let file_id = meta
+                        .as_ref()
+                        .filter(|_| options.capture_ids)
+                        .and_then(file_id_of);
+                    // A directory that couldn't be listed still arrives, with
+                    // the error attached, and never reaches `process_read_dir`
+                    if e.read_children_error.is_some() {
+                        progress.error(&path);
+                        finish_pending(&pending);
+                    }
+                    if is_dir {
+                        progress.dir();
+                        progress.set_current(&path);
+                        progress.pending_dirs(pending.load(Ordering::Relaxed));
+                    } else {
+                        progress.file(size);
+                        match &mut run {
+                            Some((dir, bytes)) if *dir == e.parent_path => {
+                                *bytes = bytes.saturating_add(size);
+                            }
+                            _ => {
+                                let next = (e.parent_path.clone(), size);
+                                if let Some((dir, bytes)) = run.replace(next) {
+                                    progress.dir_files(&dir, bytes);
+                                }
+                            }
+                        }
+                    }
+                    Some(FlatEntry {
+                        path,
+                        is_dir,
+                        size,
+                        allocated,
+                        compressed,
+                        mtime,
+                        file_id,
+                    })
                 }
-                Err(_) => {
-                    progress.errors.fetch_add(1, Ordering::Relaxed);
+                Err(e) => {
+                    // Depth 0 marks a directory jwalk couldn't read, which
+                    // never reaches `process_read_dir`
+                    if e.depth() == 0 {
+                        finish_pending(&pending);
+                    }
+                    progress.error(e.path().unwrap_or(root));
                     None
                 }
             }
         })
         .collect();
+    if let Some((dir, bytes)) = run {
+        progress.dir_files(&dir, bytes);
+    }
+
+    let excluded = excluded.load(Ordering::Relaxed);
+    if excluded > 0 {
+        progress.excluded(excluded);
+    }
+    if let Some(bounds) = &bounds {
+        bounds.report(progress);
+    }
+
+    let mut tree = build_tree(root, flat);
+    if options.treat_bundles_as_files {
+        collapse_bundles(&mut tree);
+    }
+    if !options.expand_trash {
+        collapse_trash(&mut tree, root);
+    }
+    if options.intern_names {
+        NameInterner::new().intern_dir_names(&mut tree);
+    }
+    tree
+}
+
+/// Scan `root` with the fastest scanner this platform has (the MFT for a
+/// whole NTFS drive, `getattrlistbulk` on macOS, jwalk otherwise), or
+/// best-first within `time_limit` when one is given, and return the tree
+/// along with what the scan found on the way: counts, timing, mount points
+/// and, on Windows, where the change journal stood.
+pub fn scan_root<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+    time_limit: Option<Duration>,
+) -> ScanResult {
+    let Ok(mut scan) = tallied(root, progress, |p| {
+        Ok::<_, Infallible>(match time_limit {
+            Some(limit) => (scan_with_budget(root, p, options, limit), None),
+            None => scan_fastest(root, p, options),
+        })
+    });
+    if time_limit.is_some() && count_unexpanded(&scan.tree) > 0 {
+        scan.truncated = true;
+    }
+    scan
+}
+
+/// Scan `root` with the fastest scanner available, falling back to jwalk
+/// when `root` isn't a whole drive or its MFT can't be read. Also returns a
+/// hint for the user when the fast path was unavailable for a reason they
+/// can fix.
+fn scan_fastest<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+) -> (FileNode, Option<&'static str>) {
+    #[allow(unused_mut)]
+    let mut hint = None;
+    #[cfg(windows)]
+    {
+        // The MFT gives the whole drive's tree, so it only stands in for a
+        // scan of the drive itself; a folder on it is walked
+        let drive_letter = crate::utils::drive_root_letter(root).filter(|_| root.is_dir());
+        if let Some(drive_letter) = drive_letter {
+            match crate::mft_scanner::scan_mft_with_options(drive_letter, progress, options) {
+                Ok(tree) => return (tree, None),
+                Err(e) => hint = e.hint(),
+            }
+        }
+    }
+    (scan_walking(root, progress, options), hint)
+}
 
-    build_tree(root, flat)
+/// Scan `root` by walking its directories, with `getattrlistbulk` on macOS
+/// and jwalk elsewhere. Unlike reading the MFT, this works for any
+/// directory, not just a whole drive.
+pub(crate) fn scan_walking<P: ProgressSink + ?Sized>(
+    root: &Path,
+    progress: &P,
+    options: &ScanOptions,
+) -> FileNode {
+    #[cfg(target_os = "macos")]
+    {
+        crate::mac_scanner::scan_bulk_with_options(root, progress, options)
+    }
+    #[cfg(not(target_os = "macos"))]
+    {
+        scan_with_options(root, progress, options)
+    }
+}
+
+/// Passes everything on to the sink it wraps, keeping what a [`ScanResult`]
+/// needs that the tree doesn't record: the errors and the mount points.
+pub(crate) struct Tally<'a, P: ?Sized> {
+    inner: &'a P,
+    errors: AtomicU64,
+    mounts: Mutex<Vec<MountPoint>>,
+}
+
+impl<P: ProgressSink + ?Sized> ProgressSink for Tally<'_, P> {
+    fn file(&self, size: u64) {
+        self.inner.file(size);
+    }
+
+    fn dir(&self) {
+        self.inner.dir();
+    }
+
+    fn error(&self, path: &Path) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+        self.inner.error(path);
+    }
+
+    fn set_current(&self, path: &Path) {
+        self.inner.set_current(path);
+    }
+
+    fn excluded(&self, count: u64) {
+        self.inner.excluded(count);
+    }
+
+    fn mount(&self, path: &Path, fs_type: Option<&str>) {
+        self.mounts
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(MountPoint {
+                path: path.to_path_buf(),
+                fs_type: fs_type.map(str::to_string),
+            });
+        self.inner.mount(path, fs_type);
+    }
+
+    fn bulk_fallback(&self, path: &Path) {
+        self.inner.bulk_fallback(path);
+    }
+
+    fn cycle(&self, path: &Path) {
+        self.inner.cycle(path);
+    }
+
+    fn fd_exhausted(&self, path: &Path) {
+        self.inner.fd_exhausted(path);
+    }
+
+    fn pending_dirs(&self, count: u64) {
+        self.inner.pending_dirs(count);
+    }
+
+    fn dir_files(&self, dir: &Path, bytes: u64) {
+        self.inner.dir_files(dir, bytes);
+    }
+
+    fn cancelled(&self) -> bool {
+        self.inner.cancelled()
+    }
+}
+
+/// Run `scan` of `root`, reporting to `progress` through a [`Tally`], and
+/// wrap the tree and hint it returns in a [`ScanResult`] with the scan's
+/// counts and timing filled in.
+pub(crate) fn tallied<P, E>(
+    root: &Path,
+    progress: &P,
+    scan: impl FnOnce(&Tally<'_, P>) -> Result<(FileNode, Option<&'static str>), E>,
+) -> Result<ScanResult, E>
+where
+    P: ProgressSink + ?Sized,
+{
+    // Taken first, so replaying the journal later covers changes made
+    // while the scan ran
+    #[cfg(windows)]
+    let journal = crate::usn_journal::journal_cursor(root).ok();
+    let started = Instant::now();
+    let tally = Tally {
+        inner: progress,
+        errors: AtomicU64::new(0),
+        mounts: Mutex::new(Vec::new()),
+    };
+    let (tree, hint) = scan(&tally)?;
+    let elapsed = started.elapsed();
+
+    let (files, dirs) = count_entries(&tree);
+    let mut scan = ScanResult::new(root.to_path_buf(), tree);
+    #[cfg(windows)]
+    {
+        scan.journal = journal;
+    }
+    scan.mounts = tally.mounts.into_inner().unwrap_or_else(|e| e.into_inner());
+    scan.files = files;
+    scan.dirs = dirs;
+    scan.errors = tally.errors.into_inner();
+    scan.elapsed = elapsed;
+    scan.truncated = progress.cancelled();
+    scan.hint = hint;
+    Ok(scan)
+}
+
+/// Count one queued directory as done with.
+fn finish_pending(pending: &AtomicU64) {
+    let _ = pending.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+}
+
+/// Which directories a scan may enter: by default only those on the root's
+/// device; with `cross_filesystems`, any directory not already entered.
+pub(crate) enum DeviceBounds {
+    SameDevice(u64),
+    Anywhere(Mutex<Entered>),
+}
+
+/// What a filesystem-crossing scan has entered so far.
+pub(crate) struct Entered {
+    /// Every directory by (device, inode), so bind mounts and mount loops
+    /// are entered only once and can't recurse forever.
+    dirs: FxHashSet<(u64, u64)>,
+    devices: FxHashSet<u64>,
+    /// The first directory entered on each device other than the root's.
+    /// Parents are always entered before their children, so that's where
+    /// the filesystem is mounted.
+    mounts: Vec<std::path::PathBuf>,
+    /// Directories skipped for having been entered already.
+    cycles: Vec<std::path::PathBuf>,
+}
+
+impl DeviceBounds {
+    /// `None` when there's nothing to check: the platform has no device IDs,
+    /// or the root couldn't be stat'ed.
+    pub(crate) fn new(root: &Path, cross_filesystems: bool) -> Option<Self> {
+        // The root is followed if it's a symlink, as the scanners do
+        let (dev, ino) = std::fs::metadata(root).ok().and_then(|m| dev_ino(&m))?;
+        Some(if cross_filesystems {
+            DeviceBounds::Anywhere(Mutex::new(Entered {
+                dirs: [(dev, ino)].into_iter().collect(),
+                devices: [dev].into_iter().collect(),
+                mounts: Vec::new(),
+                cycles: Vec::new(),
+            }))
+        } else {
+            DeviceBounds::SameDevice(dev)
+        })
+    }
+
+    /// Whether to descend into the directory at `path`, with metadata `meta`.
+    pub(crate) fn should_enter(&self, path: &Path, meta: &std::fs::Metadata) -> bool {
+        let Some((dev, ino)) = dev_ino(meta) else {
+            return true;
+        };
+        match self {
+            DeviceBounds::SameDevice(root_dev) => dev == *root_dev,
+            DeviceBounds::Anywhere(entered) => {
+                let mut entered = entered.lock().unwrap_or_else(|e| e.into_inner());
+                if !entered.dirs.insert((dev, ino)) {
+                    entered.cycles.push(path.to_path_buf());
+                    return false;
+                }
+                if entered.devices.insert(dev) {
+                    entered.mounts.push(path.to_path_buf());
+                }
+                true
+            }
+        }
+    }
+
+    /// Pass each mount point entered, with its filesystem type, and each
+    /// directory skipped as a repeat to `progress`.
+    pub(crate) fn report<P: ProgressSink + ?Sized>(&self, progress: &P) {
+        if let DeviceBounds::Anywhere(entered) = self {
+            let entered = entered.lock().unwrap_or_else(|e| e.into_inner());
+            for path in &entered.mounts {
+                progress.mount(path, fs_type(path).as_deref());
+            }
+            for path in &entered.cycles {
+                progress.cycle(path);
+            }
+        }
+    }
+}
+
+/// Device and inode of an entry, on platforms that expose them through std.
+fn dev_ino(meta: &std::fs::Metadata) -> Option<(u64, u64)> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some((meta.dev(), meta.ino()))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = meta;
+        None
+    }
+}
+
+/// Caps how many directories a scan holds open at once, at
+/// [`ScanOptions::max_open_dirs`]. A thread that would go over waits for
+/// another to close one.
+pub(crate) struct OpenLimit {
+    free: Mutex<usize>,
+    freed: Condvar,
+}
+
+/// Permission to hold one directory open, given back when dropped.
+pub(crate) struct OpenSlot<'a>(&'a OpenLimit);
+
+impl OpenLimit {
+    pub(crate) fn new(options: &ScanOptions) -> Self {
+        let max = options.max_open_dirs.unwrap_or_else(|| {
+            open_file_limit()
+                .map_or(usize::MAX, |n| usize::try_from(n / 2).unwrap_or(usize::MAX))
+        });
+        Self {
+            free: Mutex::new(max.max(1)),
+            freed: Condvar::new(),
+        }
+    }
+
+    /// Wait for a free slot and take it. Hold it only while the directory
+    /// is open, not while recursing, or the scan can deadlock.
+    pub(crate) fn acquire(&self) -> OpenSlot<'_> {
+        let mut free = self.free.lock().unwrap_or_else(|e| e.into_inner());
+        while *free == 0 {
+            free = self.freed.wait(free).unwrap_or_else(|e| e.into_inner());
+        }
+        *free -= 1;
+        OpenSlot(self)
+    }
+}
+
+impl Drop for OpenSlot<'_> {
+    fn drop(&mut self) {
+        *self.0.free.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.freed.notify_one();
+    }
+}
+
+/// Whether `err` means the process or system is out of file descriptors
+/// (`EMFILE` / `ENFILE`).
+pub(crate) fn out_of_descriptors(err: &std::io::Error) -> bool {
+    #[cfg(unix)]
+    {
+        matches!(err.raw_os_error(), Some(libc::EMFILE | libc::ENFILE))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = err;
+        false
+    }
+}
+
+/// File length rounded up to the block size of the device it lives on.
+fn block_rounded_len(meta: &std::fs::Metadata, path: &Path, cache: &BlockSizeCache) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match cache.get(meta.dev(), path) {
+            Some(block) => crate::utils::round_up_to_block(meta.len(), block),
+            None => meta.len(),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, cache);
+        meta.len()
+    }
+}
+
+/// Modification time as recorded in [`FileNode::mtime`].
+pub(crate) fn mtime_of(meta: &std::fs::Metadata) -> u32 {
+    meta.modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |d| epoch_secs(d.as_secs()))
+}
+
+/// Seconds since the Unix epoch, saturated to fit [`FileNode::mtime`].
+pub(crate) fn epoch_secs(secs: u64) -> u32 {
+    u32::try_from(secs).unwrap_or(u32::MAX)
+}
+
+/// Bytes actually allocated on disk for a file. Falls back to the logical
+/// length where the platform doesn't expose block counts.
+pub(crate) fn allocated_size(meta: &std::fs::Metadata) -> u64 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        meta.blocks() * 512
+    }
+    #[cfg(not(unix))]
+    {
+        meta.len()
+    }
+}
+
+/// The entry's inode number where the platform exposes one through std.
+pub(crate) fn file_id_of(meta: &std::fs::Metadata) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Some(meta.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        // `MetadataExt::file_index` is still unstable
+        let _ = meta;
+        None
+    }
+}
+
+/// Whether the filesystem reports the file as transparently compressed.
+pub(crate) fn is_compressed(meta: &std::fs::Metadata) -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        use std::os::macos::fs::MetadataExt;
+        meta.st_flags() & UF_COMPRESSED != 0
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::fs::MetadataExt;
+        const FILE_ATTRIBUTE_COMPRESSED: u32 = 0x800;
+        meta.file_attributes() & FILE_ATTRIBUTE_COMPRESSED != 0
+    }
+    #[cfg(not(any(target_os = "macos", windows)))]
+    {
+        let _ = meta;
+        false
+    }
+}
+
+/// `st_flags` bit set on files stored with APFS/HFS+ transparent compression.
+#[cfg(target_os = "macos")]
+pub(crate) const UF_COMPRESSED: u32 = 0x20;
+
+#[cfg(all(test, unix))]
+pub(crate) mod tests {
+    use super::*;
+    use crate::utils::{canonical_root, root_name};
+
+    /// Counts what a scan reports, and deletes the directory `doomed` (if
+    /// set) as soon as the scanner says it's starting on it, as something
+    /// else on the system might mid-scan.
+    #[derive(Default)]
+    pub(crate) struct Deleting {
+        pub(crate) doomed: Option<std::path::PathBuf>,
+        pub(crate) bytes: AtomicU64,
+        pub(crate) errors: AtomicU64,
+    }
+
+    impl ProgressSink for Deleting {
+        fn file(&self, size: u64) {
+            self.bytes.fetch_add(size, Ordering::Relaxed);
+        }
+        fn dir(&self) {}
+        fn error(&self, _path: &Path) {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+        fn set_current(&self, path: &Path) {
+            if self.doomed.as_deref() == Some(path) {
+                let _ = std::fs::remove_dir_all(path);
+            }
+        }
+    }
+
+    /// A tree of 20 files of 10 bytes in `keep/`, and 50 of 1 byte in each
+    /// of `doomed/` and `doomed/inner/`.
+    pub(crate) fn tree_to_delete_from() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir(root.join("keep")).unwrap();
+        std::fs::create_dir_all(root.join("doomed/inner")).unwrap();
+        for i in 0..50 {
+            if i < 20 {
+                std::fs::write(root.join(format!("keep/{}", i)), [0u8; 10]).unwrap();
+            }
+            std::fs::write(root.join(format!("doomed/{}", i)), [0u8]).unwrap();
+            std::fs::write(root.join(format!("doomed/inner/{}", i)), [0u8]).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn symlinked_root_is_scanned_as_its_target() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        std::fs::create_dir_all(real.join("sub")).unwrap();
+        std::fs::write(real.join("a"), vec![0u8; 1000]).unwrap();
+        std::fs::write(real.join("sub/b"), vec![0u8; 234]).unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        let root = canonical_root(&link);
+        assert_eq!(root, real.canonicalize().unwrap());
+        let scan = scan_root(&root, &ScanProgress::new(), &ScanOptions::default(), None);
+        assert_eq!(scan.root_path, root);
+        assert_eq!(scan.tree.name(), root_name(&root));
+        assert_eq!(scan.tree.size, 1234);
+        assert_eq!((scan.files, scan.dirs), (2, 1));
+    }
+
+    #[test]
+    fn directory_reached_twice_is_a_cycle() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("sub")).unwrap();
+        // Stands in for a bind mount of the root below itself: another path
+        // with the root's (device, inode)
+        let again = root.join("sub/again");
+        std::os::unix::fs::symlink(&root, &again).unwrap();
+        let meta = |path: &Path| std::fs::metadata(path).unwrap();
+
+        let bounds = DeviceBounds::new(&root, true).unwrap();
+        assert!(bounds.should_enter(&root.join("sub"), &meta(&root.join("sub"))));
+        assert!(!bounds.should_enter(&again, &meta(&again)));
+        assert!(!bounds.should_enter(&root.join("sub"), &meta(&root.join("sub"))));
+        let progress = ScanProgress::new();
+        bounds.report(&progress);
+        assert_eq!(progress.cycles_detected.load(Ordering::Relaxed), 2);
+
+        // Staying on one device, there are no repeats to look for
+        let bounds = DeviceBounds::new(&root, false).unwrap();
+        assert!(bounds.should_enter(&again, &meta(&again)));
+    }
+
+    #[test]
+    fn self_referential_symlink_isnt_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::write(root.join("a"), vec![0u8; 100]).unwrap();
+        std::os::unix::fs::symlink(".", root.join("loop")).unwrap();
+        std::os::unix::fs::symlink("self", root.join("self")).unwrap();
+
+        let progress = ScanProgress::new();
+        let options = ScanOptions {
+            cross_filesystems: true,
+            ..ScanOptions::default()
+        };
+        let scan = scan_root(&root, &progress, &options, None);
+        assert_eq!(scan.dirs, 0);
+        assert_eq!(progress.cycles_detected.load(Ordering::Relaxed), 0);
+        let names: Vec<&str> = scan.tree.children.iter().map(|c| c.name()).collect();
+        assert!(names.contains(&"loop") && names.contains(&"self"));
+        assert!(scan.tree.children.iter().all(|c| !c.is_dir));
+    }
+
+    #[test]
+    fn directory_deleted_mid_scan_is_skipped() {
+        let dir = tree_to_delete_from();
+        let root = dir.path().canonicalize().unwrap();
+        let progress = Deleting {
+            doomed: Some(root.join("doomed")),
+            ..Deleting::default()
+        };
+        let tree = scan_with_options(&root, &progress, &ScanOptions::default());
+
+        assert!(!root.join("doomed").exists());
+        let keep = tree.children.iter().find(|c| c.name() == "keep").unwrap();
+        assert_eq!(keep.size, 200);
+        // jwalk may have listed the directory before saying so; whatever it
+        // did read is in the tree, and whatever it didn't is an error
+        assert_eq!(tree.size, progress.bytes.load(Ordering::Relaxed));
+        let read_all = tree.size == 300;
+        assert!(read_all || progress.errors.load(Ordering::Relaxed) > 0);
+        // No file gone by the time it was stat'ed is listed as empty
+        let mut stack = vec![&tree];
+        while let Some(node) = stack.pop() {
+            assert!(node.is_dir || node.size > 0, "{} listed as empty", node.name());
+            stack.extend(&node.children);
+        }
+    }
 }
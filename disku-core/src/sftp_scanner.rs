@@ -0,0 +1,257 @@
+//! Scanning a directory on a remote machine over SFTP, for auditing a server's
+//! disk without installing disku there.
+//!
+//! Authentication goes through the SSH agent, then the usual unencrypted keys
+//! in `~/.ssh`. The server's host key must already be in `~/.ssh/known_hosts`
+//! (connect once with `ssh` to add it).
+
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+
+use crate::exclude::ExcludeSet;
+use crate::scanner::{epoch_secs, ProgressSink, ScanOptions};
+use crate::tree::{build_tree, FileNode, FlatEntry, ScanResult};
+use crate::utils::root_name;
+
+const DEFAULT_PORT: u16 = 22;
+
+/// A remote directory in scp form: `[user@]host:path`. An empty path means the
+/// remote user's home directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SshTarget {
+    pub user: String,
+    pub host: String,
+    pub port: u16,
+    pub path: String,
+}
+
+impl FromStr for SshTarget {
+    type Err = SftpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SftpError::InvalidTarget(s.to_string());
+        let (login, path) = s.split_once(':').ok_or_else(invalid)?;
+        let (user, host) = match login.rsplit_once('@') {
+            Some((user, host)) => (user.to_string(), host),
+            None => (local_user().ok_or_else(invalid)?, login),
+        };
+        if host.is_empty() || user.is_empty() {
+            return Err(invalid());
+        }
+        Ok(SshTarget {
+            user,
+            host: host.to_string(),
+            port: DEFAULT_PORT,
+            path: if path.is_empty() { ".".to_string() } else { path.to_string() },
+        })
+    }
+}
+
+impl std::fmt::Display for SshTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}:{}", self.user, self.host, self.path)
+    }
+}
+
+fn local_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|u| !u.is_empty())
+}
+
+/// Why a remote scan couldn't start. Unreadable directories once the scan is
+/// running are reported through [`ProgressSink::error`] instead.
+#[derive(Debug)]
+pub enum SftpError {
+    /// The target wasn't of the form `[user@]host:path`.
+    InvalidTarget(String),
+    /// The TCP connection failed.
+    Connect(std::io::Error),
+    /// The host isn't in `known_hosts`, or its key doesn't match.
+    HostKey(String),
+    /// Neither the agent nor any default key was accepted.
+    Auth,
+    /// Anything else from libssh2, with its description.
+    Ssh(ssh2::Error),
+}
+
+impl std::fmt::Display for SftpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SftpError::InvalidTarget(t) => {
+                write!(f, "invalid remote target {:?} (expected [user@]host:path)", t)
+            }
+            SftpError::Connect(e) => write!(f, "connection failed: {}", e),
+            SftpError::HostKey(msg) => write!(f, "{}", msg),
+            SftpError::Auth => write!(f, "authentication failed (tried ssh-agent and ~/.ssh keys)"),
+            SftpError::Ssh(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for SftpError {}
+
+impl From<ssh2::Error> for SftpError {
+    fn from(e: ssh2::Error) -> Self {
+        SftpError::Ssh(e)
+    }
+}
+
+/// Connect to `target` and scan its path. The returned root path is the
+/// remote one, resolved to an absolute path by the server.
+pub fn scan_sftp<P: ProgressSink + ?Sized>(
+    target: &SshTarget,
+    progress: &P,
+) -> Result<ScanResult, SftpError> {
+    scan_sftp_with_options(target, progress, &ScanOptions::default())
+}
+
+/// Like [`scan_sftp`], but honouring [`ScanOptions::exclude`]; the other
+/// options don't apply to remote scans.
+pub fn scan_sftp_with_options<P: ProgressSink + ?Sized>(
+    target: &SshTarget,
+    progress: &P,
+    options: &ScanOptions,
+) -> Result<ScanResult, SftpError> {
+    let session = connect(target)?;
+    let sftp = session.sftp()?;
+    let root_path = sftp.realpath(Path::new(&target.path))?;
+
+    let root_stat = sftp.stat(&root_path)?;
+    if !root_stat.is_dir() {
+        let size = root_stat.size.unwrap_or(0);
+        progress.set_current(&root_path);
+        progress.file(size);
+        let root = FileNode::new_file(root_name(&root_path), size)
+            .with_mtime(root_stat.mtime.map_or(0, epoch_secs));
+        return Ok(ScanResult::new(root_path, root));
+    }
+
+    let entries = walk(&sftp, &root_path, options.exclude.as_deref(), progress);
+    let root = build_tree(&root_path, entries);
+    Ok(ScanResult::new(root_path, root))
+}
+
+fn connect(target: &SshTarget) -> Result<Session, SftpError> {
+    let tcp = TcpStream::connect((target.host.as_str(), target.port)).map_err(SftpError::Connect)?;
+    let mut session = Session::new()?;
+    session.set_tcp_stream(tcp);
+    session.handshake()?;
+
+    check_host_key(&session, target)?;
+
+    if session.userauth_agent(&target.user).is_err() {
+        for key in default_keys() {
+            if session.userauth_pubkey_file(&target.user, None, &key, None).is_ok() {
+                break;
+            }
+        }
+    }
+    if !session.authenticated() {
+        return Err(SftpError::Auth);
+    }
+    Ok(session)
+}
+
+/// Refuse hosts we can't verify rather than silently trusting them.
+fn check_host_key(session: &Session, target: &SshTarget) -> Result<(), SftpError> {
+    let (key, _) = session
+        .host_key()
+        .ok_or_else(|| SftpError::HostKey("server sent no host key".to_string()))?;
+    let mut known = session.known_hosts()?;
+    if let Some(file) = ssh_dir().map(|d| d.join("known_hosts")) {
+        // A missing file just means nothing is known yet
+        let _ = known.read_file(&file, KnownHostFileKind::OpenSSH);
+    }
+    match known.check_port(&target.host, target.port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(SftpError::HostKey(format!(
+            "host key for {} does not match known_hosts",
+            target.host
+        ))),
+        CheckResult::NotFound | CheckResult::Failure => Err(SftpError::HostKey(format!(
+            "{} is not in known_hosts; connect once with ssh to add it",
+            target.host
+        ))),
+    }
+}
+
+fn ssh_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".ssh"))
+}
+
+fn default_keys() -> Vec<PathBuf> {
+    let Some(dir) = ssh_dir() else {
+        return Vec::new();
+    };
+    ["id_ed25519", "id_ecdsa", "id_rsa"]
+        .iter()
+        .map(|name| dir.join(name))
+        .filter(|path| path.is_file())
+        .collect()
+}
+
+/// Depth-first walk of the remote tree. One round trip per directory, so this
+/// is sequential; SFTP gains little from parallel requests on one channel.
+fn walk<P: ProgressSink + ?Sized>(
+    sftp: &Sftp,
+    root: &Path,
+    exclude: Option<&ExcludeSet>,
+    progress: &P,
+) -> Vec<FlatEntry> {
+    let mut entries = Vec::new();
+    let mut pending = vec![root.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        if progress.cancelled() {
+            break;
+        }
+        progress.set_current(&dir);
+        let listing = match sftp.readdir(&dir) {
+            Ok(listing) => listing,
+            Err(_) => {
+                progress.error(&dir);
+                continue;
+            }
+        };
+        let mut file_bytes: u64 = 0;
+        for (path, stat) in listing {
+            // readdir attributes are lstat-style, so symlinks are never
+            // followed and count as small files
+            let is_dir = stat.file_type().is_dir();
+            if let Some(exclude) = exclude {
+                let rel = path.strip_prefix(root).unwrap_or(&path);
+                if exclude.is_excluded(rel, is_dir) {
+                    progress.excluded(1);
+                    continue;
+                }
+            }
+            let size = if is_dir { 0 } else { stat.size.unwrap_or(0) };
+            if is_dir {
+                progress.dir();
+                pending.push(path.clone());
+            } else {
+                progress.file(size);
+                file_bytes = file_bytes.saturating_add(size);
+            }
+            entries.push(FlatEntry {
+                path,
+                is_dir,
+                size,
+                allocated: size,
+                compressed: false,
+                mtime: stat.mtime.map_or(0, epoch_secs),
+                // SFTP attributes carry no inode
+                file_id: None,
+            });
+        }
+        progress.dir_files(&dir, file_bytes);
+    }
+    entries
+}
@@ -0,0 +1,247 @@
+//! Saving a finished scan to disk and loading it back without rescanning.
+//!
+//! Snapshots are JSON. Paths ending in `.gz` (e.g. `home.disku.gz`) are
+//! gzip-compressed on save; on load the format is detected from the gzip
+//! magic bytes, so a renamed file still opens. Loading also accepts an ncdu
+//! JSON export (see [`import_ncdu`]), told apart by its leading `[`.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+use crate::tree::{import_ncdu, FileNode, JournalCursor, MountPoint, ScanResult};
+
+/// Bumped whenever the on-disk layout changes incompatibly.
+const SNAPSHOT_VERSION: u32 = 1;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Deepest nesting of JSON arrays and objects a snapshot or ncdu export may
+/// have: about 4,000 directories deep for a snapshot, 8,000 for an export.
+/// Far deeper than real trees go, but shallow enough for [`PARSE_STACK`].
+const MAX_NESTING: usize = 8192;
+
+/// Stack for the thread a tree is parsed on. The parser recurses once per
+/// level, a few KB a time in debug builds; this is only reserved, not used,
+/// unless the input really is that deep.
+const PARSE_STACK: usize = 128 << 20;
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    root_path: &'a Path,
+    tree: &'a FileNode,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    journal: Option<JournalCursor>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    mounts: &'a [MountPoint],
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    version: u32,
+    root_path: PathBuf,
+    tree: FileNode,
+    #[serde(default)]
+    journal: Option<JournalCursor>,
+    #[serde(default)]
+    mounts: Vec<MountPoint>,
+}
+
+/// Write `scan` to `path`, gzip-compressed if the path ends in `.gz`.
+pub fn save_snapshot(scan: &ScanResult, path: &Path) -> io::Result<()> {
+    let file = BufWriter::new(File::create(path)?);
+    let compress = path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gz"));
+    if compress {
+        let mut gz = GzEncoder::new(file, Compression::default());
+        write_snapshot(scan, &mut gz)?;
+        gz.finish()?.flush()
+    } else {
+        let mut file = file;
+        write_snapshot(scan, &mut file)?;
+        file.flush()
+    }
+}
+
+fn write_snapshot<W: Write>(scan: &ScanResult, writer: W) -> io::Result<()> {
+    let snapshot = SnapshotRef {
+        version: SNAPSHOT_VERSION,
+        root_path: &scan.root_path,
+        tree: &scan.tree,
+        journal: scan.journal,
+        mounts: &scan.mounts,
+    };
+    serde_json::to_writer(writer, &snapshot).map_err(io::Error::from)
+}
+
+/// Read a snapshot written by [`save_snapshot`], or an ncdu export,
+/// compressed or not.
+pub fn load_snapshot(path: &Path) -> io::Result<ScanResult> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let gzipped = reader.fill_buf()?.starts_with(&GZIP_MAGIC);
+    if gzipped {
+        read_any(BufReader::new(GzDecoder::new(reader)))
+    } else {
+        read_any(reader)
+    }
+}
+
+/// Read a snapshot (a JSON object) or an ncdu export (a JSON array).
+fn read_any<R: BufRead + Send>(mut reader: R) -> io::Result<ScanResult> {
+    if first_byte(&mut reader)? == Some(b'[') {
+        let tree = import_ncdu(reader)?;
+        return Ok(ScanResult::new(PathBuf::from(tree.name()), tree));
+    }
+    read_snapshot(reader)
+}
+
+/// The first byte of `reader` that isn't whitespace, without consuming it.
+fn first_byte<R: BufRead>(reader: &mut R) -> io::Result<Option<u8>> {
+    loop {
+        let Some(&byte) = reader.fill_buf()?.first() else {
+            return Ok(None);
+        };
+        if !byte.is_ascii_whitespace() {
+            return Ok(Some(byte));
+        }
+        reader.consume(1);
+    }
+}
+
+fn read_snapshot<R: Read + Send>(reader: R) -> io::Result<ScanResult> {
+    let snapshot = parse_nested(reader, |reader| {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        // Trees nest far deeper than serde_json's default limit of 128;
+        // NestingLimit stands in for it
+        de.disable_recursion_limit();
+        Snapshot::deserialize(&mut de).map_err(io::Error::from)
+    })?;
+    if snapshot.version != SNAPSHOT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unsupported snapshot version {}", snapshot.version),
+        ));
+    }
+    let mut scan = ScanResult::new(snapshot.root_path, snapshot.tree);
+    scan.journal = snapshot.journal;
+    scan.mounts = snapshot.mounts;
+    Ok(scan)
+}
+
+/// Run `parse` on `reader` on a thread with room to recurse [`MAX_NESTING`]
+/// levels deep, with input nested any deeper turned away as invalid rather
+/// than left to overflow the stack.
+pub(crate) fn parse_nested<R, T>(
+    reader: R,
+    parse: impl FnOnce(NestingLimit<R>) -> io::Result<T> + Send,
+) -> io::Result<T>
+where
+    R: Read + Send,
+    T: Send,
+{
+    let reader = NestingLimit {
+        inner: reader,
+        depth: 0,
+        in_string: false,
+        escaped: false,
+    };
+    std::thread::scope(|scope| {
+        let parser = std::thread::Builder::new()
+            .name("disku-parse".to_string())
+            .stack_size(PARSE_STACK)
+            .spawn_scoped(scope, || parse(reader))?;
+        parser
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// Passes JSON through unchanged, failing the read that takes it more than
+/// [`MAX_NESTING`] arrays and objects deep. Brackets inside strings don't
+/// count.
+pub(crate) struct NestingLimit<R> {
+    inner: R,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl<R: Read> Read for NestingLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        for &byte in &buf[..n] {
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.in_string = true,
+                b'[' | b'{' => {
+                    self.depth += 1;
+                    if self.depth > MAX_NESTING {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("nested more than {} levels deep", MAX_NESTING),
+                        ));
+                    }
+                }
+                b']' | b'}' => self.depth = self.depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A snapshot of a chain of `depth` directories with a file at the end.
+    fn chain_snapshot(depth: usize) -> String {
+        let node = |name: &str, is_dir: bool| {
+            let sizes = r#""size":1,"allocated":0,"compressed":false"#;
+            format!(r#"{{"name":"{}",{},"is_dir":{},"children":["#, name, sizes, is_dir)
+        };
+        let (dir, file) = (node("d", true), node("f", false) + "]}");
+        format!(
+            r#"{{"version":{},"root_path":"/d","tree":{}{}{}}}"#,
+            SNAPSHOT_VERSION,
+            dir.repeat(depth),
+            file,
+            "]}".repeat(depth)
+        )
+    }
+
+    #[test]
+    fn deep_snapshot_loads() {
+        let scan = read_any(chain_snapshot(4000).as_bytes()).unwrap();
+        assert_eq!(scan.tree.size, 1);
+    }
+
+    #[test]
+    fn too_deep_snapshot_is_an_error() {
+        let err = read_any(chain_snapshot(100_000).as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("nested"), "{}", err);
+    }
+
+    #[test]
+    fn brackets_in_names_dont_count() {
+        // An escaped quote first, so a naive scan would think the string ended
+        let brackets = "[{".repeat(MAX_NESTING);
+        let json = chain_snapshot(1).replace(r#""f""#, &format!(r#""\"{}""#, brackets));
+        let scan = read_any(json.as_bytes()).unwrap();
+        assert_eq!(scan.tree.children[0].name(), format!("\"{}", brackets));
+    }
+}
@@ -1,49 +1,299 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use fuzzy_matcher::skim::SkimMatcherV2;
+use fuzzy_matcher::FuzzyMatcher;
 use rayon::prelude::*;
+use rustc_hash::FxHashSet;
 
-#[derive(Debug, Clone, serde::Serialize)]
+use crate::utils::{categorize, format_cost, format_size, percent, root_name, Category};
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct FileNode {
-    pub name: String,
+    /// Shared so repeated names can point at one allocation (see [`NameInterner`]).
+    pub name: Arc<str>,
+    /// Logical size in bytes (inclusive for directories).
     pub size: u64,
+    /// Bytes the filesystem actually allocated, after compression and block
+    /// slack. Equals `size` when the scanner couldn't tell.
+    pub allocated: u64,
     pub is_dir: bool,
+    /// The filesystem flags this file as transparently compressed (APFS/NTFS).
+    pub compressed: bool,
+    /// Last modification, in seconds since the Unix epoch; 0 when unknown.
+    /// For directories, the newest of any file below (see
+    /// [`update_totals`](Self::update_totals)), so an actively growing tree
+    /// stands out from a stale one. 32 bits (good until 2106) so it fits in
+    /// the node's padding.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub mtime: u32,
+    /// A time-limited scan ran out of time before reading this directory, so
+    /// its contents are unknown and its size (0) is a placeholder. See
+    /// [`crate::budget`].
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unexpanded: bool,
+    /// A trash folder (`~/.Trash`, `$Recycle.Bin`, ...) recorded as a single
+    /// leaf carrying its total size, unless [`ScanOptions::expand_trash`] is
+    /// set.
+    ///
+    /// [`ScanOptions::expand_trash`]: crate::scanner::ScanOptions::expand_trash
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub trash: bool,
+    /// Inode / file ID / MFT record number, for cross-checking against `du`
+    /// and `find`. Only recorded when [`ScanOptions::capture_ids`] is set.
+    ///
+    /// [`ScanOptions::capture_ids`]: crate::scanner::ScanOptions::capture_ids
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<u64>,
+    /// The name exactly as NTFS stores it, kept only when it isn't valid
+    /// UTF-16 (an unpaired surrogate) and `name` is a lossy stand-in with
+    /// U+FFFD in its place. Paths are built from [`FileNode::os_name`], which
+    /// uses it on Windows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utf16_name: Option<Box<[u16]>>,
     pub children: Vec<FileNode>,
 }
 
+/// Frees the tree a level at a time instead of through the compiler's
+/// recursive drop, which a pathologically deep chain of directories would
+/// overflow the stack with.
+impl Drop for FileNode {
+    fn drop(&mut self) {
+        let mut pending = std::mem::take(&mut self.children);
+        while let Some(mut node) = pending.pop() {
+            pending.append(&mut node.children);
+        }
+    }
+}
+
+fn is_zero(n: &u32) -> bool {
+    *n == 0
+}
+
 impl FileNode {
-    pub fn new_file(name: String, size: u64) -> Self {
+    pub fn new_file(name: impl Into<Arc<str>>, size: u64) -> Self {
         Self {
-            name,
+            name: name.into(),
             size,
+            allocated: size,
             is_dir: false,
+            compressed: false,
+            mtime: 0,
+            unexpanded: false,
+            trash: false,
+            file_id: None,
+            utf16_name: None,
             children: Vec::new(),
         }
     }
 
-    pub fn new_dir(name: String) -> Self {
+    pub fn new_dir(name: impl Into<Arc<str>>) -> Self {
         Self {
-            name,
+            name: name.into(),
             size: 0,
+            allocated: 0,
             is_dir: true,
+            compressed: false,
+            mtime: 0,
+            unexpanded: false,
+            trash: false,
+            file_id: None,
+            utf16_name: None,
             children: Vec::new(),
         }
     }
 
+    /// Set the on-disk allocation, flagging files the filesystem compressed.
+    pub fn with_allocation(mut self, allocated: u64, compressed: bool) -> Self {
+        self.allocated = allocated;
+        self.compressed = compressed;
+        self
+    }
+
+    /// Set the modification time (see [`FileNode::mtime`]).
+    pub fn with_mtime(mut self, mtime: u32) -> Self {
+        self.mtime = mtime;
+        self
+    }
+
+    /// Attach the filesystem's identifier for this entry, if one was read.
+    pub fn with_file_id(mut self, file_id: Option<u64>) -> Self {
+        self.file_id = file_id;
+        self
+    }
+
+    /// Number of nodes in this subtree, counting this one.
+    pub fn total_nodes(&self) -> u64 {
+        let (files, dirs) = count_entries(self);
+        1 + files + dirs
+    }
+
+    /// Recompute this directory's totals, and its newest modification time,
+    /// from its immediate children.
+    pub fn update_totals(&mut self) {
+        self.size = saturating_sum(self.children.iter().map(|c| c.size));
+        self.allocated = saturating_sum(self.children.iter().map(|c| c.allocated));
+        self.mtime = self.children.iter().map(|c| c.mtime).max().unwrap_or(0);
+    }
+
+    /// The largest immediate child, whatever order the children are in.
+    pub fn biggest_child(&self) -> Option<&FileNode> {
+        self.children.iter().max_by_key(|c| c.size)
+    }
+
+    /// The entry's name; for scan roots this is usually the scanned path.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The name as the filesystem knows it, for building paths. Differs from
+    /// [`name`](Self::name) only for NTFS names that aren't valid Unicode.
+    pub fn os_name(&self) -> Cow<'_, OsStr> {
+        #[cfg(windows)]
+        if let Some(units) = &self.utf16_name {
+            use std::os::windows::ffi::OsStringExt;
+            return Cow::Owned(std::ffi::OsString::from_wide(units));
+        }
+        Cow::Borrowed(OsStr::new(self.name()))
+    }
+
+    /// Call `f` on this node and on every node below it that has children, a
+    /// level at a time: each level in parallel, then the level below as `f`
+    /// left it. Works through a list of nodes rather than recursing, so a
+    /// pathologically deep tree can't overflow the stack.
+    pub fn for_each_parent_mut(&mut self, f: impl Fn(&mut FileNode) + Sync) {
+        let mut level: Vec<&mut FileNode> = vec![self];
+        while !level.is_empty() {
+            level.par_iter_mut().for_each(|node| f(node));
+            level = level
+                .into_iter()
+                .flat_map(|node| node.children.iter_mut().filter(|c| !c.children.is_empty()))
+                .collect();
+        }
+    }
+
+    /// Call `f` on this node and on every directory below it, each directory
+    /// after everything below it, so `f` sees its children already done (as
+    /// recomputing totals needs). Works through a list rather than recursing,
+    /// lending each directory's children out while they're visited.
+    pub fn for_each_dir_bottom_up(&mut self, mut f: impl FnMut(&mut FileNode)) {
+        /// A directory on the way down: its children still to visit, and
+        /// the ones already done.
+        struct Open {
+            node: FileNode,
+            pending: std::vec::IntoIter<FileNode>,
+            done: Vec<FileNode>,
+        }
+        fn open(mut node: FileNode) -> Open {
+            let children = std::mem::take(&mut node.children);
+            Open {
+                node,
+                done: Vec::with_capacity(children.len()),
+                pending: children.into_iter(),
+            }
+        }
+
+        let mut stack = vec![open(std::mem::replace(self, FileNode::new_dir("")))];
+        while let Some(top) = stack.last_mut() {
+            if let Some(child) = top.pending.next() {
+                if child.is_dir {
+                    stack.push(open(child));
+                } else {
+                    top.done.push(child);
+                }
+                continue;
+            }
+            let Some(Open { mut node, done, .. }) = stack.pop() else {
+                break;
+            };
+            node.children = done;
+            if node.is_dir {
+                f(&mut node);
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.done.push(node),
+                None => *self = node,
+            }
+        }
+    }
+
     pub fn sort_by_size(&mut self) {
-        self.children
-            .par_sort_unstable_by(|a, b| b.size.cmp(&a.size));
-        self.children
-            .par_iter_mut()
-            .for_each(|child| child.sort_by_size());
+        self.for_each_parent_mut(|node| {
+            node.children
+                .par_sort_unstable_by(|a, b| b.size.cmp(&a.size));
+        });
     }
 
     pub fn sort_by_name(&mut self) {
+        self.for_each_parent_mut(|node| {
+            node.children
+                .par_sort_unstable_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+        });
+    }
+
+    /// Sort only this node's own children, largest first, leaving the levels
+    /// below as they are.
+    pub fn sort_children_by_size(&mut self) {
         self.children
-            .par_sort_unstable_by(|a, b| a.name.to_lowercase().cmp(&b.name.to_lowercase()));
+            .par_sort_unstable_by(|a, b| b.size.cmp(&a.size));
+    }
+
+    /// Sort only this node's own children by case-insensitive name.
+    pub fn sort_children_by_name(&mut self) {
         self.children
-            .par_iter_mut()
-            .for_each(|child| child.sort_by_name());
+            .par_sort_by_cached_key(|child| child.name.to_lowercase());
+    }
+
+    /// Most recently modified first, by [`FileNode::mtime`].
+    pub fn sort_by_recency(&mut self) {
+        self.for_each_parent_mut(|node| {
+            node.children
+                .par_sort_unstable_by(|a, b| b.mtime.cmp(&a.mtime));
+        });
+    }
+
+    /// A copy of this subtree with every level's children sorted by exact name
+    /// (directories before files on a tie), independent of scan order.
+    pub fn normalized(&self) -> FileNode {
+        let mut node = self.clone();
+        node.normalize();
+        node
+    }
+
+    fn normalize(&mut self) {
+        self.for_each_parent_mut(|node| {
+            node.children
+                .par_sort_unstable_by(|a, b| a.name.cmp(&b.name).then(b.is_dir.cmp(&a.is_dir)));
+        });
+    }
+
+    /// Compare two trees by name, size and kind at every level, ignoring the
+    /// order children happen to be in. Allocation and compression details are
+    /// not compared, since scanners report those differently. Two scans of the
+    /// same path agree when their root sizes match and this returns true.
+    pub fn structurally_eq(&self, other: &FileNode) -> bool {
+        fn sorted(node: &FileNode) -> Vec<&FileNode> {
+            let mut children: Vec<&FileNode> = node.children.iter().collect();
+            children.sort_unstable_by(|a, b| a.name.cmp(&b.name).then(b.is_dir.cmp(&a.is_dir)));
+            children
+        }
+        let mut pairs = vec![(self, other)];
+        while let Some((a, b)) = pairs.pop() {
+            let same = a.name == b.name
+                && a.size == b.size
+                && a.is_dir == b.is_dir
+                && a.children.len() == b.children.len();
+            if !same {
+                return false;
+            }
+            pairs.extend(sorted(a).into_iter().zip(sorted(b)));
+        }
+        true
     }
 
     /// Remove a child by name and return its size so callers can adjust parent sizes.
@@ -57,6 +307,7 @@ impl FileNode {
             let removed = self.children.remove(pos);
             let freed = removed.size;
             self.size = self.size.saturating_sub(freed);
+            self.allocated = self.allocated.saturating_sub(removed.allocated);
             Some(freed)
         } else {
             None
@@ -64,13 +315,163 @@ impl FileNode {
     }
 }
 
+/// Deduplicates names during a scan so that directories like `node_modules`,
+/// `.git` or `dist`, which repeat throughout big trees, share one allocation.
+///
+/// Safe to use from many rayon workers; lookups take a short lock.
+#[derive(Default)]
+pub struct NameInterner {
+    names: Mutex<FxHashSet<Arc<str>>>,
+}
+
+impl NameInterner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the shared copy of `name`, inserting it on first sight.
+    pub fn intern(&self, name: &str) -> Arc<str> {
+        let mut names = self.names.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(existing) = names.get(name) {
+            return existing.clone();
+        }
+        let shared: Arc<str> = Arc::from(name);
+        names.insert(shared.clone());
+        shared
+    }
+
+    /// Swap every directory name below `node` for its interned copy.
+    pub fn intern_dir_names(&self, node: &mut FileNode) {
+        node.for_each_parent_mut(|node| {
+            for child in node.children.iter_mut().filter(|c| c.is_dir) {
+                child.name = self.intern(&child.name);
+            }
+        });
+    }
+}
+
+/// A scanned tree together with the absolute path it was scanned from.
+///
+/// Scanners name the root node differently (full path, drive, single file), so
+/// real paths are always rebuilt from `root_path` rather than the root's name.
+#[derive(Debug, Clone)]
+pub struct ScanResult {
+    pub root_path: PathBuf,
+    pub tree: FileNode,
+    /// Where the volume's NTFS change journal stood when the scan started, so
+    /// the tree can later be brought up to date without a rescan.
+    pub journal: Option<JournalCursor>,
+    /// Directories where the scan crossed onto another filesystem (only with
+    /// `cross_filesystems`). Kept here rather than on the nodes so ordinary
+    /// nodes don't pay for a rarely used field.
+    pub mounts: Vec<MountPoint>,
+    /// Files and directories in the tree, not counting the root; see
+    /// [`count_entries`]. Like the rest of the counts here, only filled in
+    /// by [`scan_root`](crate::scanner::scan_root) and
+    /// [`scan_root_resumable`](crate::checkpoint::scan_root_resumable), and
+    /// 0 for a tree loaded or built any other way.
+    pub files: u64,
+    pub dirs: u64,
+    /// Entries the scan couldn't read and left out.
+    pub errors: u64,
+    pub elapsed: Duration,
+    /// The tree is missing parts of what's on disk: the scan was cancelled,
+    /// or its time limit ran out with directories still unread.
+    pub truncated: bool,
+    /// Why the fastest scanner couldn't be used, when it's something the
+    /// user can fix (say, running as administrator for the MFT scanner).
+    pub hint: Option<&'static str>,
+}
+
+/// A directory where another filesystem is mounted.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct MountPoint {
+    pub path: PathBuf,
+    /// e.g. `ext4`, `apfs`, `exfat`; `None` when it couldn't be determined.
+    pub fs_type: Option<String>,
+}
+
+/// A position in an NTFS USN change journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct JournalCursor {
+    /// Identifies one incarnation of the journal; it changes when the journal
+    /// is deleted and recreated, invalidating every older USN.
+    pub journal_id: u64,
+    /// First USN not yet reflected in the tree.
+    pub next_usn: i64,
+}
+
+impl ScanResult {
+    pub fn new(root_path: PathBuf, tree: FileNode) -> Self {
+        Self {
+            root_path,
+            tree,
+            journal: None,
+            mounts: Vec::new(),
+            files: 0,
+            dirs: 0,
+            errors: 0,
+            elapsed: Duration::ZERO,
+            truncated: false,
+            hint: None,
+        }
+    }
+
+    /// The filesystem the scan crossed into at `path`, if one is mounted
+    /// exactly there.
+    pub fn mount_at(&self, path: &Path) -> Option<&MountPoint> {
+        self.mounts.iter().find(|m| m.path == path)
+    }
+
+    /// The node reached by following `nav_path` from the root.
+    pub fn node_at(&self, nav_path: &[usize]) -> Option<&FileNode> {
+        let mut node = &self.tree;
+        for &idx in nav_path {
+            node = node.children.get(idx)?;
+        }
+        Some(node)
+    }
+
+    /// Absolute path of the node reached by following `nav_path`; see
+    /// [`path_of`].
+    pub fn path_of(&self, nav_path: &[usize]) -> Option<PathBuf> {
+        path_of(&self.tree, &self.root_path, nav_path)
+    }
+
+    /// nav_path indices of the absolute path `target`; see [`find_nav_path`].
+    pub fn find_nav_path(&self, target: &Path) -> Option<Vec<usize>> {
+        find_nav_path_under(&self.tree, &self.root_path, target)
+    }
+}
+
+/// Real path of the node reached from `root` by following `nav_path`, where
+/// `root_path` is the absolute path of `root` itself. Every path handed to the
+/// filesystem should come from here: each step is pushed as a path component
+/// (so the platform's separator is used, and a root like `C:\` or `/` doesn't
+/// get doubled), from the name the filesystem knows ([`FileNode::os_name`]).
+/// None when `nav_path` leads outside the tree.
+pub fn path_of(root: &FileNode, root_path: &Path, nav_path: &[usize]) -> Option<PathBuf> {
+    let mut path = root_path.to_path_buf();
+    let mut node = root;
+    for &idx in nav_path {
+        node = node.children.get(idx)?;
+        path.push(node.os_name());
+    }
+    Some(path)
+}
+
 /// Given an absolute path to a directory and the tree root, find the nav_path
 /// indices to navigate TO that directory. Returns empty vec if target is the root.
 ///
+/// The root's name is taken as its path; prefer [`ScanResult::find_nav_path`].
+///
 /// Uses case-insensitive comparison on Windows (NTFS is case-insensitive).
 pub fn find_nav_path(root: &FileNode, target: &std::path::Path) -> Option<Vec<usize>> {
-    let root_path = std::path::Path::new(&root.name);
+    find_nav_path_under(root, Path::new(root.name()), target)
+}
 
+/// Like [`find_nav_path`], with the root's absolute path given explicitly.
+pub fn find_nav_path_under(root: &FileNode, root_path: &Path, target: &Path) -> Option<Vec<usize>> {
     // If target IS the root, return empty nav path
     // Case-insensitive comparison for Windows paths
     if root_path
@@ -82,11 +483,11 @@ pub fn find_nav_path(root: &FileNode, target: &std::path::Path) -> Option<Vec<us
 
     // Strip the root prefix to get the relative portion
     let relative = target.strip_prefix(root_path).ok()?;
-    let components: Vec<&str> = relative
+    let components: Vec<&OsStr> = relative
         .components()
         .filter_map(|c| {
             if let std::path::Component::Normal(s) = c {
-                s.to_str()
+                Some(s)
             } else {
                 None
             }
@@ -105,7 +506,7 @@ pub fn find_nav_path(root: &FileNode, target: &std::path::Path) -> Option<Vec<us
         let idx = node
             .children
             .iter()
-            .position(|c| c.name.eq_ignore_ascii_case(comp))?;
+            .position(|c| c.os_name().eq_ignore_ascii_case(comp))?;
         nav_path.push(idx);
         node = &node.children[idx];
     }
@@ -113,32 +514,887 @@ pub fn find_nav_path(root: &FileNode, target: &std::path::Path) -> Option<Vec<us
     Some(nav_path)
 }
 
+/// Combine two scans of overlapping trees into one.
+///
+/// Children are matched by exact name. Directories present in both are merged
+/// recursively; for any other clash (two files, or a file and a directory of
+/// the same name) the entry from `b` replaces the one from `a`. Sizes are never
+/// summed, so pass the newer scan as `b`.
+///
+/// A merged directory is `b`'s entry (name, file ID and the rest) holding the
+/// children of both, with its totals and newest modification time recomputed
+/// from them and its children sorted largest first. It's
+/// [`unexpanded`](FileNode::unexpanded) only if it was in both scans, since
+/// otherwise one of them read what's in it.
+pub fn merge(a: FileNode, b: FileNode) -> FileNode {
+    /// A pair of directories being merged: `merged` starts as `b`'s entry
+    /// holding `a`'s children, and takes `b`'s children one at a time. `slot`
+    /// is where it goes back in the parent's children.
+    struct Merging {
+        merged: FileNode,
+        index: HashMap<Arc<str>, usize>,
+        pending: std::vec::IntoIter<FileNode>,
+        slot: usize,
+    }
+    fn start(mut a: FileNode, mut merged: FileNode, slot: usize) -> Merging {
+        merged.unexpanded &= a.unexpanded;
+        let pending = std::mem::replace(&mut merged.children, std::mem::take(&mut a.children));
+        let index = merged
+            .children
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.name.clone(), i))
+            .collect();
+        Merging {
+            merged,
+            index,
+            pending: pending.into_iter(),
+            slot,
+        }
+    }
+
+    if !(a.is_dir && b.is_dir) {
+        return b;
+    }
+    // Works through a list of directory pairs rather than recursing, so a
+    // pathologically deep tree can't overflow the stack
+    let mut stack = vec![start(a, b, 0)];
+    while let Some(top) = stack.last_mut() {
+        if let Some(child) = top.pending.next() {
+            match top.index.get(&child.name) {
+                Some(&i) if top.merged.children[i].is_dir && child.is_dir => {
+                    let placeholder = FileNode::new_file("", 0);
+                    let existing = std::mem::replace(&mut top.merged.children[i], placeholder);
+                    stack.push(start(existing, child, i));
+                }
+                Some(&i) => top.merged.children[i] = child,
+                None => {
+                    top.index.insert(child.name.clone(), top.merged.children.len());
+                    top.merged.children.push(child);
+                }
+            }
+            continue;
+        }
+        let Some(Merging { mut merged, slot, .. }) = stack.pop() else {
+            break;
+        };
+        merged.update_totals();
+        merged.sort_children_by_size();
+        match stack.last_mut() {
+            Some(parent) => parent.merged.children[slot] = merged,
+            None => return merged,
+        }
+    }
+    unreachable!("the outermost pair is returned once it's merged")
+}
+
+/// Total of `sizes`, stopping at `u64::MAX` rather than overflowing. Real
+/// trees come nowhere near it, but a corrupt snapshot or a double-counting
+/// bug could, and a pinned total is easier to spot than a wrapped one (and
+/// doesn't panic in debug builds).
+pub fn saturating_sum(sizes: impl IntoIterator<Item = u64>) -> u64 {
+    sizes.into_iter().fold(0, u64::saturating_add)
+}
+
+/// Number of files and directories anywhere below `node`, not counting `node`
+/// itself.
+pub fn count_entries(node: &FileNode) -> (u64, u64) {
+    let mut files = 0;
+    let mut dirs = 0;
+    let mut stack = vec![node];
+    while let Some(dir) = stack.pop() {
+        for child in &dir.children {
+            if child.is_dir {
+                dirs += 1;
+                stack.push(child);
+            } else {
+                files += 1;
+            }
+        }
+    }
+    (files, dirs)
+}
+
+/// Drop every file below `node`, leaving just the directory structure: a far
+/// smaller tree when only directory sizes matter. Directory totals still
+/// include the files, so they must not be recomputed with
+/// [`FileNode::update_totals`] afterwards. Trash folders recorded as a single
+/// leaf are kept, as the directories they are.
+pub fn dirs_only(node: &mut FileNode) {
+    node.for_each_parent_mut(|node| {
+        node.children.retain(|c| c.is_dir || c.trash);
+        node.children.shrink_to_fit();
+    });
+}
+
+/// Bytes that deleting the entry at `nav_path` would free: its inclusive size,
+/// less files it shares through hard links with entries elsewhere in the
+/// tree, and counting a file linked more than once inside it only once.
+///
+/// Hard links are recognised by [`FileNode::file_id`], so without
+/// [`ScanOptions::capture_ids`] this is just the inclusive size. Links from
+/// outside the scanned tree aren't known, and filesystem snapshots or
+/// copy-on-write clones that keep the data alive are ignored, so the result
+/// can overstate what's actually freed.
+///
+/// [`ScanOptions::capture_ids`]: crate::scanner::ScanOptions::capture_ids
+pub fn reclaimable(root: &FileNode, nav_path: &[usize]) -> u64 {
+    let Some(target) = nav_path
+        .iter()
+        .try_fold(root, |node, &i| node.children.get(i))
+    else {
+        return 0;
+    };
+
+    // Files with an ID count once each, and only if nothing outside links them
+    let mut linked: HashMap<u64, u64> = HashMap::new();
+    let mut unlinked: u64 = 0;
+    let mut stack = vec![target];
+    while let Some(node) = stack.pop() {
+        match node.file_id {
+            _ if !node.children.is_empty() => stack.extend(&node.children),
+            Some(id) if !node.is_dir => {
+                linked.insert(id, node.size);
+            }
+            _ => unlinked = unlinked.saturating_add(node.size),
+        }
+    }
+    if linked.is_empty() {
+        return unlinked;
+    }
+
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if std::ptr::eq(node, target) {
+            continue;
+        }
+        if !node.is_dir {
+            if let Some(id) = node.file_id {
+                linked.remove(&id);
+            }
+        }
+        stack.extend(&node.children);
+    }
+    unlinked.saturating_add(saturating_sum(linked.values().copied()))
+}
+
+/// Index of the first of `node`'s children that a listing folds into one
+/// "… N more" row: the trailing run of children each under `percent` of
+/// `node`'s size. With children sorted by size that's the whole long tail.
+/// None when fewer than two children would be folded, since a row standing
+/// in for a single entry hides nothing. The tree itself is left alone.
+pub fn small_tail(node: &FileNode, percent: f64) -> Option<usize> {
+    let cutoff = node.size as f64 * percent / 100.0;
+    let kept = node
+        .children
+        .iter()
+        .rposition(|c| c.size as f64 >= cutoff)
+        .map_or(0, |i| i + 1);
+    (node.children.len() - kept >= 2).then_some(kept)
+}
+
+/// The `n` largest of the entries offered to it, for [`largest_dirs`] and
+/// [`top_files`]. Ranked by size, largest first, then by path, so entries of
+/// equal size come out in the same order whatever order the tree was
+/// walked in.
+struct Largest {
+    n: usize,
+    /// Min-heap of the best `n` seen so far; the worst sits on top for
+    /// eviction. Among equal sizes, the later path is the worse one.
+    heap: BinaryHeap<Reverse<(u64, Reverse<String>)>>,
+}
+
+impl Largest {
+    fn new(n: usize) -> Self {
+        Self {
+            n,
+            heap: BinaryHeap::with_capacity(n + 1),
+        }
+    }
+
+    fn offer(&mut self, size: u64, rel: String) {
+        let entry = Reverse((size, Reverse(rel)));
+        if self.heap.len() < self.n {
+            self.heap.push(entry);
+        } else if self.heap.peek().is_some_and(|worst| entry < *worst) {
+            self.heap.pop();
+            self.heap.push(entry);
+        }
+    }
+
+    fn into_ranked(self) -> Vec<(String, u64)> {
+        // Ascending `Reverse`s are best first
+        self.heap
+            .into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((size, Reverse(rel)))| (rel, size))
+            .collect()
+    }
+}
+
+/// Find the `n` largest directories anywhere below `node`, ranked by inclusive
+/// size (largest first, then by path). `node` itself is not included. Paths
+/// are relative to `node`, joined with the platform separator.
+pub fn largest_dirs(node: &FileNode, n: usize) -> Vec<(String, u64)> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut largest = Largest::new(n);
+    let mut stack: Vec<(&FileNode, String)> = node
+        .children
+        .iter()
+        .filter(|c| c.is_dir)
+        .map(|c| (c, c.name.to_string()))
+        .collect();
+
+    while let Some((dir, rel)) = stack.pop() {
+        for child in dir.children.iter().filter(|c| c.is_dir) {
+            let child_rel = format!("{}{}{}", rel, std::path::MAIN_SEPARATOR, child.name);
+            stack.push((child, child_rel));
+        }
+        largest.offer(dir.size, rel);
+    }
+    largest.into_ranked()
+}
+
+/// Find the `n` largest files anywhere below `node`, largest first and then
+/// by path. Paths are relative to `node`, joined with the platform separator.
+pub fn top_files(node: &FileNode, n: usize) -> Vec<(String, u64)> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut largest = Largest::new(n);
+    let mut stack: Vec<(&FileNode, String)> = vec![(node, String::new())];
+
+    while let Some((dir, rel)) = stack.pop() {
+        for child in &dir.children {
+            let child_rel = if rel.is_empty() {
+                child.name.to_string()
+            } else {
+                format!("{}{}{}", rel, std::path::MAIN_SEPARATOR, child.name)
+            };
+            if child.is_dir {
+                stack.push((child, child_rel));
+            } else {
+                largest.offer(child.size, child_rel);
+            }
+        }
+    }
+    largest.into_ranked()
+}
+
+/// Find every entry below `node` at least `min_size` big whose path relative
+/// to `node` matches the glob `pattern`, largest first and then by path.
+/// `node` itself is not included. Patterns follow the [`crate::exclude`]
+/// rules: `*` and `?` stay within one path component while `**` crosses any
+/// number of them, a pattern with no `/` matches at any depth (`*.log` finds
+/// log files everywhere), and a trailing `/` matches directories only. Paths
+/// are joined with the platform separator. An empty pattern finds nothing.
+pub fn glob(
+    node: &FileNode,
+    pattern: &str,
+    min_size: u64,
+) -> Result<Vec<(String, u64)>, globset::Error> {
+    if pattern.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (glob, dir_only) = crate::exclude::compile(pattern)?;
+    let matcher = glob.compile_matcher();
+
+    let mut out = Vec::new();
+    let mut stack: Vec<(&FileNode, String)> = vec![(node, String::new())];
+    while let Some((dir, rel)) = stack.pop() {
+        // Sizes are inclusive, so nothing below a child under `min_size` can
+        // reach it either
+        for child in dir.children.iter().filter(|c| c.size >= min_size) {
+            let child_rel = if rel.is_empty() {
+                child.name.to_string()
+            } else {
+                format!("{}{}{}", rel, std::path::MAIN_SEPARATOR, child.name)
+            };
+            if (child.is_dir || !dir_only) && matcher.is_match(&child_rel) {
+                out.push((child_rel.clone(), child.size));
+            }
+            if child.is_dir {
+                stack.push((child, child_rel));
+            }
+        }
+    }
+    out.sort_unstable_by(|(a, a_size), (b, b_size)| b_size.cmp(a_size).then_with(|| a.cmp(b)));
+    Ok(out)
+}
+
+/// How [`search`] matches names against the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchMode {
+    /// Letters of the query in order, not necessarily adjacent, scored by
+    /// how closely they fit.
+    #[default]
+    Fuzzy,
+    /// The query as a case-insensitive substring.
+    Exact,
+}
+
+impl SearchMode {
+    pub fn next(self) -> Self {
+        match self {
+            SearchMode::Fuzzy => SearchMode::Exact,
+            SearchMode::Exact => SearchMode::Fuzzy,
+        }
+    }
+}
+
+/// One entry found by [`search`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchHit {
+    /// Child indices leading from the searched node to the entry.
+    pub nav_path: Vec<usize>,
+    /// Fuzzy match score, higher is better; always 0 for exact matches.
+    pub score: i64,
+    pub size: u64,
+    /// Char indices of the entry's name that matched the query, for
+    /// highlighting.
+    pub matched: Vec<usize>,
+}
+
+/// Find up to `n` entries anywhere below `node` whose name matches `query`.
+/// Results are ranked by score, then by size (largest first). `node` itself
+/// is not included, and an empty query finds nothing.
+pub fn search(node: &FileNode, query: &str, mode: SearchMode, n: usize) -> Vec<SearchHit> {
+    if n == 0 || query.is_empty() {
+        return Vec::new();
+    }
+    let matcher = SkimMatcherV2::default();
+    let query_chars: Vec<char> = query.chars().map(fold_case).collect();
+
+    // Min-heap of the best `n` seen so far; the worst sits on top for eviction.
+    type Ranked = Reverse<(i64, u64, Vec<usize>, Vec<usize>)>;
+    let mut heap: BinaryHeap<Ranked> = BinaryHeap::with_capacity(n + 1);
+    let mut stack: Vec<(&FileNode, Vec<usize>)> = vec![(node, Vec::new())];
+
+    while let Some((dir, nav_path)) = stack.pop() {
+        for (i, child) in dir.children.iter().enumerate() {
+            let mut child_path = nav_path.clone();
+            child_path.push(i);
+            let found = match mode {
+                SearchMode::Fuzzy => matcher.fuzzy_indices(&child.name, query),
+                SearchMode::Exact => find_ignore_case(&child.name, &query_chars)
+                    .map(|start| (0, (start..start + query_chars.len()).collect())),
+            };
+            if let Some((score, matched)) = found {
+                let beats_worst = heap
+                    .peek()
+                    .is_some_and(|Reverse((s, size, ..))| (score, child.size) > (*s, *size));
+                if heap.len() < n {
+                    heap.push(Reverse((score, child.size, child_path.clone(), matched)));
+                } else if beats_worst {
+                    heap.pop();
+                    heap.push(Reverse((score, child.size, child_path.clone(), matched)));
+                }
+            }
+            if child.is_dir {
+                stack.push((child, child_path));
+            }
+        }
+    }
+
+    let mut out: Vec<SearchHit> = heap
+        .into_iter()
+        .map(|Reverse((score, size, nav_path, matched))| SearchHit {
+            nav_path,
+            score,
+            size,
+            matched,
+        })
+        .collect();
+    out.sort_unstable_by_key(|hit| Reverse((hit.score, hit.size)));
+    out
+}
+
+/// Char index where `query` (already folded by [`fold_case`]) first appears
+/// in `name`, ignoring case.
+fn find_ignore_case(name: &str, query: &[char]) -> Option<usize> {
+    let name: Vec<char> = name.chars().map(fold_case).collect();
+    name.windows(query.len()).position(|window| window == query)
+}
+
+/// Lowercase one char to one char, so char indices into the folded name
+/// still point at the same chars of the original.
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Below this a whole scan counts as tiny.
+const TINY_TREE: u64 = 1024 * 1024;
+/// No child larger than this share of the total counts as dominant.
+const DOMINANT_SHARE: f64 = 0.2;
+
+/// A short note for a scan with nothing worth drilling into: the tree is tiny,
+/// or no child takes a dominant share of it. A flat list otherwise leaves
+/// users wondering whether the scan worked.
+pub fn nothing_big(node: &FileNode) -> Option<&'static str> {
+    if !node.is_dir {
+        return None;
+    }
+    if node.size < TINY_TREE {
+        return Some("Nothing big here: the whole scan is under 1 MB");
+    }
+    let biggest = node.biggest_child()?;
+    (percent(biggest.size, node.size) < DOMINANT_SHARE * 100.0)
+        .then_some("No dominant directories: space is evenly distributed")
+}
+
+/// Directory names that usually hold regenerable build output or caches.
+pub const DEFAULT_CACHE_NAMES: &[&str] = &[
+    "node_modules",
+    "target",
+    "__pycache__",
+    ".pytest_cache",
+    ".mypy_cache",
+    ".tox",
+    ".gradle",
+    ".next",
+    ".parcel-cache",
+];
+
+/// Find directories below `node` whose name is one of `names`, largest first.
+/// Each comes with its nav path and its path relative to `node` (joined with
+/// the platform separator). A match isn't searched further, so a cache nested
+/// inside another is counted as part of the outer one.
+pub fn find_caches<S: AsRef<str>>(node: &FileNode, names: &[S]) -> Vec<(Vec<usize>, String, u64)> {
+    let mut out = Vec::new();
+    let mut stack: Vec<(&FileNode, Vec<usize>, String)> = vec![(node, Vec::new(), String::new())];
+
+    while let Some((dir, nav_path, rel)) = stack.pop() {
+        for (i, child) in dir.children.iter().enumerate().filter(|(_, c)| c.is_dir) {
+            let mut child_nav = nav_path.clone();
+            child_nav.push(i);
+            let child_rel = if rel.is_empty() {
+                child.name.to_string()
+            } else {
+                format!("{}{}{}", rel, std::path::MAIN_SEPARATOR, child.name)
+            };
+            if names.iter().any(|n| n.as_ref() == &*child.name) {
+                out.push((child_nav, child_rel, child.size));
+            } else {
+                stack.push((child, child_nav, child_rel));
+            }
+        }
+    }
+
+    // Ties go by path, so equal caches list the same way every run
+    out.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.1.cmp(&b.1)));
+    out
+}
+
+/// Print an indented `├──`/`└──` tree of `node` with sizes, in the children's
+/// current order. At most `top_n` children are listed per directory (the rest
+/// are summarised on one line) and recursion stops below `max_depth` levels.
+pub fn print_ascii<W: std::io::Write>(
+    node: &FileNode,
+    writer: &mut W,
+    max_depth: usize,
+    top_n: usize,
+) -> std::io::Result<()> {
+    fn print_children<W: std::io::Write>(
+        node: &FileNode,
+        writer: &mut W,
+        prefix: &str,
+        depth: usize,
+        max_depth: usize,
+        top_n: usize,
+    ) -> std::io::Result<()> {
+        if depth >= max_depth {
+            return Ok(());
+        }
+
+        let shown = node.children.len().min(top_n);
+        let hidden = &node.children[shown..];
+        for (i, child) in node.children[..shown].iter().enumerate() {
+            let last = i + 1 == shown && hidden.is_empty();
+            let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+            let suffix = kind_suffix(child);
+            writeln!(
+                writer,
+                "{}{}{}{}  {}",
+                prefix,
+                branch,
+                child.name,
+                suffix,
+                format_size(child.size)
+            )?;
+            if child.is_dir {
+                let child_prefix = format!("{}{}", prefix, indent);
+                print_children(child, writer, &child_prefix, depth + 1, max_depth, top_n)?;
+            }
+        }
+
+        if !hidden.is_empty() {
+            let hidden_size = saturating_sum(hidden.iter().map(|c| c.size));
+            writeln!(
+                writer,
+                "{}└── … {} more  {}",
+                prefix,
+                hidden.len(),
+                format_size(hidden_size)
+            )?;
+        }
+        Ok(())
+    }
+
+    writeln!(writer, "{}  {}", node.name, format_size(node.size))?;
+    print_children(node, writer, "", 0, max_depth, top_n)
+}
+
+/// Print the first `n` immediate children of `node` as an aligned
+/// size / percent / name table, in the children's current order. With a
+/// `cost_per_gb_month`, each line also gets its monthly storage cost.
+pub fn print_top<W: std::io::Write>(
+    node: &FileNode,
+    writer: &mut W,
+    n: usize,
+    cost_per_gb_month: Option<f64>,
+) -> std::io::Result<()> {
+    let cost = |size| match cost_per_gb_month {
+        Some(rate) => format!("{:>12}  ", format_cost(size, rate)),
+        None => String::new(),
+    };
+    match cost_per_gb_month {
+        Some(rate) => writeln!(
+            writer,
+            "{}  {}  {}",
+            node.name,
+            format_size(node.size),
+            format_cost(node.size, rate)
+        )?,
+        None => writeln!(writer, "{}  {}", node.name, format_size(node.size))?,
+    }
+
+    let shown = node.children.len().min(n);
+    for child in &node.children[..shown] {
+        let suffix = kind_suffix(child);
+        writeln!(
+            writer,
+            "{:>9}  {:>5.1}%  {}{}{}",
+            format_size(child.size),
+            percent(child.size, node.size),
+            cost(child.size),
+            child.name,
+            suffix
+        )?;
+    }
+
+    let hidden = &node.children[shown..];
+    if !hidden.is_empty() {
+        let hidden_size = saturating_sum(hidden.iter().map(|c| c.size));
+        writeln!(
+            writer,
+            "{:>9}  {:>5.1}%  {}… {} more",
+            format_size(hidden_size),
+            percent(hidden_size, node.size),
+            cost(hidden_size),
+            hidden.len()
+        )?;
+    }
+    Ok(())
+}
+
+/// Pair the children of `a` and `b` by exact name, in name order. A child
+/// found on only one side is paired with `None`.
+pub fn align<'a>(
+    a: &'a FileNode,
+    b: &'a FileNode,
+) -> Vec<(Option<&'a FileNode>, Option<&'a FileNode>)> {
+    fn by_name(node: &FileNode) -> Vec<&FileNode> {
+        let mut children: Vec<&FileNode> = node.children.iter().collect();
+        children.sort_unstable_by(|x, y| x.name.cmp(&y.name));
+        children
+    }
+    let (left, right) = (by_name(a), by_name(b));
+    let (mut i, mut j) = (0, 0);
+    let mut pairs = Vec::with_capacity(left.len().max(right.len()));
+    while i < left.len() || j < right.len() {
+        let order = match (left.get(i), right.get(j)) {
+            (Some(x), Some(y)) => x.name.cmp(&y.name),
+            (Some(_), None) => Ordering::Less,
+            _ => Ordering::Greater,
+        };
+        match order {
+            Ordering::Less => {
+                pairs.push((Some(left[i]), None));
+                i += 1;
+            }
+            Ordering::Greater => {
+                pairs.push((None, Some(right[j])));
+                j += 1;
+            }
+            Ordering::Equal => {
+                pairs.push((Some(left[i]), Some(right[j])));
+                i += 1;
+                j += 1;
+            }
+        }
+    }
+    pairs
+}
+
+/// Print `a` and `b` side by side: a line with both totals, then their
+/// children paired by [`align`], each with its size in `a`, its size in `b`
+/// and how much bigger or smaller it is in `b`. Biggest differences come
+/// first; with `color`, growth is green and shrinkage red.
+pub fn print_compare<W: std::io::Write>(
+    a: &FileNode,
+    b: &FileNode,
+    writer: &mut W,
+    color: bool,
+) -> std::io::Result<()> {
+    fn size_of(node: Option<&FileNode>) -> u64 {
+        node.map_or(0, |n| n.size)
+    }
+    fn column(node: Option<&FileNode>) -> String {
+        node.map_or_else(|| "—".to_string(), |n| format_size(n.size))
+    }
+    let delta = |from: u64, to: u64| {
+        let (sign, by, paint) = match to.cmp(&from) {
+            Ordering::Equal => return format!("{:>10}", "="),
+            Ordering::Greater => ('+', to - from, "\x1b[32m"),
+            Ordering::Less => ('-', from - to, "\x1b[31m"),
+        };
+        let text = format!("{:>10}", format!("{}{}", sign, format_size(by)));
+        if color {
+            format!("{}{}\x1b[0m", paint, text)
+        } else {
+            text
+        }
+    };
+
+    writeln!(
+        writer,
+        "{:>9}  {:>9}  {}  {}  vs  {}",
+        format_size(a.size),
+        format_size(b.size),
+        delta(a.size, b.size),
+        a.name,
+        b.name
+    )?;
+
+    let mut pairs = align(a, b);
+    pairs.sort_by_key(|&(x, y)| {
+        let (from, to) = (size_of(x), size_of(y));
+        (Reverse(from.abs_diff(to)), Reverse(from.max(to)))
+    });
+    for (x, y) in pairs {
+        let Some(either) = x.or(y) else { continue };
+        writeln!(
+            writer,
+            "{:>9}  {:>9}  {}  {}{}",
+            column(x),
+            column(y),
+            delta(size_of(x), size_of(y)),
+            either.name,
+            kind_suffix(either)
+        )?;
+    }
+    Ok(())
+}
+
+/// A plain-text breakdown of `node` for pasting into a chat or a ticket: a
+/// line with `path` and the total, then its `n` largest children (whatever
+/// order they're in) with size and percent, and the rest summed on one line.
+/// Laid out like [`print_top`].
+pub fn size_breakdown(node: &FileNode, path: &Path, n: usize) -> String {
+    let mut largest: Vec<&FileNode> = node.children.iter().collect();
+    largest.sort_by_key(|c| Reverse(c.size));
+    let shown = largest.len().min(n);
+
+    let mut out = format!("{}  {}\n", path.display(), format_size(node.size));
+    for child in &largest[..shown] {
+        out.push_str(&format!(
+            "{:>9}  {:>5.1}%  {}{}\n",
+            format_size(child.size),
+            percent(child.size, node.size),
+            child.name,
+            kind_suffix(child)
+        ));
+    }
+
+    let hidden = &largest[shown..];
+    if !hidden.is_empty() {
+        let hidden_size = saturating_sum(hidden.iter().map(|c| c.size));
+        out.push_str(&format!(
+            "{:>9}  {:>5.1}%  … {} more\n",
+            format_size(hidden_size),
+            percent(hidden_size, node.size),
+            hidden.len()
+        ));
+    }
+    out
+}
+
+/// What follows an entry's name in the text listings: `/` for a directory,
+/// a note for a collapsed trash folder.
+fn kind_suffix(node: &FileNode) -> &'static str {
+    if node.is_dir {
+        "/"
+    } else if node.trash {
+        " (Trash)"
+    } else {
+        ""
+    }
+}
+
+/// Write `node` as folded stacks, the input of flame graph tools
+/// (`flamegraph.pl`, `inferno-flamegraph`): one `root;dir;file size` line
+/// per file, its path from `node` joined by `;` and its size as the count,
+/// so the graph's widths are disk usage. Files of size 0 are left out, as
+/// they'd draw nothing. A `;` in a name would read as a path separator and a
+/// line break would end the line, so both are written as `_`.
+pub fn export_folded<W: std::io::Write>(node: &FileNode, writer: &mut W) -> std::io::Result<()> {
+    let frame = |name: &str| name.replace([';', '\n', '\r'], "_");
+    if !node.is_dir {
+        if node.size > 0 {
+            writeln!(writer, "{} {}", frame(&node.name), node.size)?;
+        }
+        return Ok(());
+    }
+
+    let mut stack: Vec<(&FileNode, String)> = vec![(node, frame(&node.name))];
+    while let Some((dir, stack_prefix)) = stack.pop() {
+        for child in &dir.children {
+            let folded = format!("{};{}", stack_prefix, frame(&child.name));
+            if child.is_dir {
+                stack.push((child, folded));
+            } else if child.size > 0 {
+                writeln!(writer, "{} {}", folded, child.size)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Logical vs on-disk totals for a subtree; see [`compression_summary`].
+#[derive(Debug, Clone, Copy, Default, serde::Serialize)]
+pub struct CompressionSummary {
+    /// Sum of file lengths.
+    pub logical: u64,
+    /// Sum of allocated bytes.
+    pub on_disk: u64,
+    /// Files the filesystem flagged as compressed.
+    pub compressed_files: u64,
+}
+
+impl CompressionSummary {
+    /// Bytes saved on disk versus the logical size (0 when slack outweighs savings).
+    pub fn savings(&self) -> u64 {
+        self.logical.saturating_sub(self.on_disk)
+    }
+
+    /// On-disk size as a fraction of logical size (1.0 = no compression).
+    pub fn ratio(&self) -> f64 {
+        if self.logical == 0 {
+            1.0
+        } else {
+            self.on_disk as f64 / self.logical as f64
+        }
+    }
+}
+
+/// Total up logical and allocated sizes of every file under `node`, counting
+/// files flagged as transparently compressed.
+pub fn compression_summary(node: &FileNode) -> CompressionSummary {
+    let mut summary = CompressionSummary::default();
+    let mut stack = vec![node];
+    while let Some(n) = stack.pop() {
+        if n.is_dir {
+            stack.extend(n.children.iter());
+        } else {
+            summary.logical = summary.logical.saturating_add(n.size);
+            summary.on_disk = summary.on_disk.saturating_add(n.allocated);
+            if n.compressed {
+                summary.compressed_files += 1;
+            }
+        }
+    }
+    summary
+}
+
+/// Bytes and file count for one [`Category`]; see [`aggregate_by_category`].
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+pub struct CategoryTotal {
+    pub category: Category,
+    pub label: &'static str,
+    pub size: u64,
+    pub files: u64,
+}
+
+/// Total every file under `node` by [`Category`], largest first. Categories
+/// with no files are left out.
+pub fn aggregate_by_category(node: &FileNode) -> Vec<CategoryTotal> {
+    let mut totals: HashMap<Category, (u64, u64)> = HashMap::new();
+    let mut stack = vec![node];
+    while let Some(n) = stack.pop() {
+        if n.is_dir {
+            stack.extend(n.children.iter());
+        } else {
+            let entry = totals.entry(categorize(&n.name)).or_default();
+            entry.0 = entry.0.saturating_add(n.size);
+            entry.1 += 1;
+        }
+    }
+    let mut totals: Vec<CategoryTotal> = totals
+        .into_iter()
+        .map(|(category, (size, files))| CategoryTotal {
+            category,
+            label: category.label(),
+            size,
+            files,
+        })
+        .collect();
+    totals.sort_by(|a, b| b.size.cmp(&a.size).then(a.category.cmp(&b.category)));
+    totals
+}
+
 const MAX_DEPTH: usize = 512;
 
-/// Build a tree from a flat list of (path, is_dir, size) entries.
+/// One entry of a flat scan listing, as produced by walkers like jwalk.
+#[derive(Debug, Clone)]
+pub struct FlatEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    pub size: u64,
+    pub allocated: u64,
+    pub compressed: bool,
+    /// See [`FileNode::mtime`].
+    pub mtime: u32,
+    pub file_id: Option<u64>,
+}
+
+/// Build a tree from a flat list of entries.
 /// Used by the jwalk fallback scanner.
-pub fn build_tree(root_path: &Path, entries: Vec<(PathBuf, bool, u64)>) -> FileNode {
-    let root_name = root_path.to_string_lossy().to_string();
-    let mut root = FileNode::new_dir(root_name);
+pub fn build_tree(root_path: &Path, entries: Vec<FlatEntry>) -> FileNode {
+    let mut root = FileNode::new_dir(root_name(root_path));
 
-    let mut dir_children: HashMap<PathBuf, Vec<(PathBuf, bool, u64)>> = HashMap::new();
+    let mut dir_children: HashMap<PathBuf, Vec<FlatEntry>> = HashMap::new();
 
-    for (path, is_dir, size) in &entries {
-        if path == root_path {
+    for entry in entries {
+        if entry.path == root_path {
+            root.file_id = entry.file_id;
             continue;
         }
-        if let Some(parent) = path.parent() {
+        if let Some(parent) = entry.path.parent() {
             dir_children
                 .entry(parent.to_path_buf())
                 .or_default()
-                .push((path.clone(), *is_dir, *size));
+                .push(entry);
         }
     }
 
     fn build_recursive(
         node: &mut FileNode,
         node_path: &Path,
-        dir_children: &HashMap<PathBuf, Vec<(PathBuf, bool, u64)>>,
+        dir_children: &HashMap<PathBuf, Vec<FlatEntry>>,
         depth: usize,
     ) {
         if !node.is_dir || depth >= MAX_DEPTH {
@@ -146,26 +1402,429 @@ pub fn build_tree(root_path: &Path, entries: Vec<(PathBuf, bool, u64)>) -> FileN
         }
 
         if let Some(children) = dir_children.get(node_path) {
-            for (path, is_dir, size) in children {
+            for entry in children {
+                let path = &entry.path;
                 let name = path
                     .file_name()
                     .map(|n| n.to_string_lossy().to_string())
                     .unwrap_or_else(|| path.to_string_lossy().to_string());
-                if *is_dir {
-                    let mut child = FileNode::new_dir(name);
+                if entry.is_dir {
+                    let mut child = FileNode::new_dir(name).with_file_id(entry.file_id);
                     build_recursive(&mut child, path, dir_children, depth + 1);
-                    child.size = child.children.iter().map(|c| c.size).sum();
+                    child.update_totals();
                     node.children.push(child);
                 } else {
-                    node.children.push(FileNode::new_file(name, *size));
+                    node.children.push(
+                        FileNode::new_file(name, entry.size)
+                            .with_allocation(entry.allocated, entry.compressed)
+                            .with_mtime(entry.mtime)
+                            .with_file_id(entry.file_id),
+                    );
                 }
             }
         }
 
-        node.size = node.children.iter().map(|c| c.size).sum();
+        node.update_totals();
     }
 
     build_recursive(&mut root, root_path, &dir_children, 0);
     root.sort_by_size();
     root
 }
+
+/// Read a JSON export written by `ncdu -o` (format 1.x) into a tree. The root
+/// is named with the full path ncdu scanned, as disku's own scanners name it.
+///
+/// Apparent sizes become [`FileNode::size`], disk usage
+/// [`FileNode::allocated`], inode numbers [`FileNode::file_id`], and (in
+/// exports made with `ncdu -e`) modification times [`FileNode::mtime`]. Entries
+/// ncdu excluded (by pattern, or for being on another filesystem) are left
+/// out, and directories' own sizes are dropped, as disku's scanners do. Only
+/// the JSON format is understood, not ncdu 2's binary export, and compressed
+/// exports need decompressing first. Exports nested more than a few thousand
+/// directories deep are turned away as invalid data.
+pub fn import_ncdu<R: std::io::Read + Send>(reader: R) -> std::io::Result<FileNode> {
+    use serde::Deserialize;
+
+    let mut root = crate::snapshot::parse_nested(reader, |reader| {
+        let mut de = serde_json::Deserializer::from_reader(reader);
+        // Trees nest far deeper than serde_json's default limit of 128;
+        // the nesting limit stands in for it
+        de.disable_recursion_limit();
+        let NcduExport(root) = NcduExport::deserialize(&mut de).map_err(std::io::Error::from)?;
+        de.end().map_err(std::io::Error::from)?;
+        Ok(root)
+    })?;
+    root.sort_by_size();
+    Ok(root)
+}
+
+/// The top level of an ncdu export: `[major, minor, {metadata}, root]`.
+struct NcduExport(FileNode);
+
+/// One entry of an ncdu export: an info object for a file, or an array of
+/// the info object followed by the children for a directory. `None` for
+/// entries ncdu excluded.
+struct NcduEntry(Option<FileNode>);
+
+/// The fields of an ncdu info object disku uses; sizes of 0 are omitted.
+#[derive(serde::Deserialize)]
+struct NcduInfo {
+    name: String,
+    #[serde(default)]
+    asize: u64,
+    #[serde(default)]
+    dsize: u64,
+    /// Only in exports made with `-e` (extended information).
+    #[serde(default)]
+    mtime: u64,
+    ino: Option<u64>,
+    excluded: Option<String>,
+}
+
+impl NcduInfo {
+    fn into_node(self, is_dir: bool) -> Option<FileNode> {
+        if self.excluded.is_some() {
+            return None;
+        }
+        let node = if is_dir {
+            FileNode::new_dir(self.name)
+        } else {
+            FileNode::new_file(self.name, self.asize)
+                .with_allocation(self.dsize, false)
+                .with_mtime(u32::try_from(self.mtime).unwrap_or(u32::MAX))
+        };
+        Some(node.with_file_id(self.ino))
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for NcduExport {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{Error, IgnoredAny, SeqAccess, Visitor};
+
+        struct ExportVisitor;
+
+        impl<'de> Visitor<'de> for ExportVisitor {
+            type Value = NcduExport;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("an ncdu export")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NcduExport, A::Error> {
+                let major: u32 = seq
+                    .next_element()?
+                    .ok_or_else(|| Error::invalid_length(0, &self))?;
+                if major != 1 {
+                    return Err(Error::custom(format!(
+                        "unsupported ncdu export version {}",
+                        major
+                    )));
+                }
+                // Minor version and metadata (program, timestamp)
+                for i in 1..3 {
+                    seq.next_element::<IgnoredAny>()?
+                        .ok_or_else(|| Error::invalid_length(i, &self))?;
+                }
+                let NcduEntry(root) = seq
+                    .next_element()?
+                    .ok_or_else(|| Error::invalid_length(3, &self))?;
+                let root = root.ok_or_else(|| Error::custom("the export's root is excluded"))?;
+                while seq.next_element::<IgnoredAny>()?.is_some() {}
+                Ok(NcduExport(root))
+            }
+        }
+
+        deserializer.deserialize_seq(ExportVisitor)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for NcduEntry {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::value::MapAccessDeserializer;
+        use serde::de::{Error, MapAccess, SeqAccess, Visitor};
+        use serde::Deserialize;
+
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = NcduEntry;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("an ncdu file object or directory array")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<NcduEntry, A::Error> {
+                let info = NcduInfo::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(NcduEntry(info.into_node(false)))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<NcduEntry, A::Error> {
+                let info: NcduInfo = seq
+                    .next_element()?
+                    .ok_or_else(|| Error::invalid_length(0, &self))?;
+                let mut dir = info.into_node(true);
+                while let Some(NcduEntry(child)) = seq.next_element()? {
+                    if let (Some(dir), Some(child)) = (dir.as_mut(), child) {
+                        dir.children.push(child);
+                    }
+                }
+                if let Some(dir) = dir.as_mut() {
+                    dir.update_totals();
+                }
+                Ok(NcduEntry(dir))
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory named `name` holding `children`, with its totals filled in.
+    fn dir(name: &str, children: Vec<FileNode>) -> FileNode {
+        let mut dir = FileNode::new_dir(name);
+        dir.children = children;
+        dir.update_totals();
+        dir
+    }
+
+    fn names(node: &FileNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name()).collect()
+    }
+
+    #[test]
+    fn merge_disjoint_subtrees_keeps_both() {
+        let a = dir("root", vec![dir("x", vec![FileNode::new_file("f", 10)])]);
+        let b = dir("root", vec![dir("y", vec![FileNode::new_file("g", 30)])]);
+        let merged = merge(a, b);
+        assert_eq!(names(&merged), ["y", "x"]);
+        assert_eq!(merged.size, 40);
+        assert_eq!(merged.children[1].size, 10);
+    }
+
+    #[test]
+    fn merge_overlapping_subtrees_takes_b_on_clashes() {
+        let a = dir(
+            "root",
+            vec![
+                dir("shared", vec![FileNode::new_file("old", 5), FileNode::new_file("same", 7)]),
+                FileNode::new_file("was_file", 1),
+                FileNode::new_file("only_a", 2),
+            ],
+        );
+        let b = dir(
+            "root",
+            vec![
+                dir("shared", vec![FileNode::new_file("same", 70), FileNode::new_file("new", 100)]),
+                dir("was_file", vec![FileNode::new_file("inner", 3)]),
+            ],
+        );
+        let merged = merge(a, b);
+
+        assert_eq!(names(&merged), ["shared", "was_file", "only_a"]);
+        let shared = &merged.children[0];
+        assert_eq!(names(shared), ["new", "same", "old"]);
+        assert_eq!(shared.children[1].size, 70);
+        assert_eq!(shared.size, 175);
+        assert!(merged.children[1].is_dir);
+        assert_eq!(merged.size, 175 + 3 + 2);
+    }
+
+    #[test]
+    fn merged_directory_takes_b_entry() {
+        let mut a = dir("root", vec![FileNode::new_file("f", 1).with_mtime(100)]);
+        a.file_id = Some(1);
+        a.unexpanded = true;
+        let mut b = dir("root", vec![FileNode::new_file("g", 1).with_mtime(200)]);
+        b.file_id = Some(2);
+        b.unexpanded = true;
+        let merged = merge(a.clone(), b.clone());
+        assert_eq!(merged.file_id, Some(2));
+        assert_eq!(merged.mtime, 200);
+        assert!(merged.unexpanded);
+
+        // Read in one of the scans, so its contents are known
+        a.unexpanded = false;
+        assert!(!merge(a.clone(), b.clone()).unexpanded);
+        a.unexpanded = true;
+        b.unexpanded = false;
+        assert!(!merge(a, b).unexpanded);
+    }
+
+    #[test]
+    fn sums_saturate_near_u64_max() {
+        assert_eq!(saturating_sum([u64::MAX - 1, 1]), u64::MAX);
+        assert_eq!(saturating_sum([u64::MAX - 1, 2, 3]), u64::MAX);
+        assert_eq!(saturating_sum([u64::MAX / 2, u64::MAX / 2]), u64::MAX - 1);
+        assert_eq!(saturating_sum(std::iter::empty()), 0);
+
+        let mut dir = FileNode::new_dir("d");
+        dir.children.push(FileNode::new_file("a", u64::MAX - 10).with_allocation(u64::MAX, false));
+        dir.children.push(FileNode::new_file("b", 100).with_allocation(4096, false));
+        dir.update_totals();
+        assert_eq!((dir.size, dir.allocated), (u64::MAX, u64::MAX));
+
+        let mut root = FileNode::new_dir("root");
+        root.children.push(dir);
+        root.children.push(FileNode::new_file("c", 5));
+        root.update_totals();
+        assert_eq!(root.size, u64::MAX);
+    }
+
+    /// A chain of `depth` directories with a 1-byte file at the end, totals
+    /// not yet filled in.
+    fn chain(depth: usize) -> FileNode {
+        let mut node = FileNode::new_file("f", 1);
+        for _ in 0..depth {
+            let mut dir = FileNode::new_dir("d");
+            dir.children.push(node);
+            node = dir;
+        }
+        node
+    }
+
+    #[test]
+    fn deep_chain_doesnt_overflow_the_stack() {
+        const DEPTH: usize = 10_000;
+        // Test threads get a small stack, so recursing once per level
+        // would overflow it
+        let mut tree = chain(DEPTH);
+        tree.for_each_dir_bottom_up(FileNode::update_totals);
+        assert_eq!(tree.size, 1);
+
+        tree.sort_by_size();
+        tree.sort_by_name();
+        tree.sort_by_recency();
+        assert_eq!(count_entries(&tree), (1, DEPTH as u64 - 1));
+        assert_eq!(crate::budget::count_unexpanded(&tree), 0);
+
+        let mut folded = Vec::new();
+        export_folded(&tree, &mut folded).unwrap();
+        assert_eq!(folded.iter().filter(|&&b| b == b'\n').count(), 1);
+
+        let mut other = chain(DEPTH);
+        other.for_each_dir_bottom_up(FileNode::update_totals);
+        assert!(tree.structurally_eq(&other));
+        let merged = merge(tree, other);
+        assert_eq!(merged.size, 1);
+
+        let mut dirs = merged;
+        dirs_only(&mut dirs);
+        assert_eq!(count_entries(&dirs), (0, DEPTH as u64 - 1));
+    }
+
+    /// An ncdu export of a chain of `depth` directories with a file at the end.
+    fn ncdu_chain(depth: usize) -> String {
+        format!(
+            r#"[1,2,{{}},{}{{"name":"f","asize":7}}{}]"#,
+            r#"[{"name":"d"},"#.repeat(depth),
+            "]".repeat(depth)
+        )
+    }
+
+    #[test]
+    fn deep_ncdu_export_imports() {
+        let root = import_ncdu(ncdu_chain(5000).as_bytes()).unwrap();
+        assert_eq!(root.size, 7);
+    }
+
+    /// Three equal directories each holding one equal file, plus two more
+    /// equal files at the top, with the children in the order `order` picks.
+    fn equal_sizes(order: [usize; 5]) -> FileNode {
+        let entries: Vec<FileNode> = ["b", "c", "a", "z", "y"]
+            .iter()
+            .enumerate()
+            .map(|(i, name)| {
+                if i < 3 {
+                    let mut dir = FileNode::new_dir(*name);
+                    dir.children.push(FileNode::new_file("f", 10));
+                    dir.update_totals();
+                    dir
+                } else {
+                    FileNode::new_file(*name, 10)
+                }
+            })
+            .collect();
+        let mut root = FileNode::new_dir("root");
+        root.children = order.iter().map(|&i| entries[i].clone()).collect();
+        root.update_totals();
+        root
+    }
+
+    #[test]
+    fn equal_sizes_rank_the_same_in_any_order() {
+        let orders = [[0, 1, 2, 3, 4], [4, 3, 2, 1, 0], [2, 4, 0, 3, 1]];
+        let trees: Vec<FileNode> = orders.into_iter().map(equal_sizes).collect();
+        let sep = std::path::MAIN_SEPARATOR;
+        let path = |dir: &str| format!("{}{}f", dir, sep);
+
+        for tree in &trees {
+            let expected: Vec<(String, u64)> = vec![
+                (path("a"), 10),
+                (path("b"), 10),
+                (path("c"), 10),
+                ("y".to_string(), 10),
+            ];
+            assert_eq!(top_files(tree, 4), expected);
+            let expected: Vec<(String, u64)> = vec![("a".to_string(), 10), ("b".to_string(), 10)];
+            assert_eq!(largest_dirs(tree, 2), expected);
+        }
+        let globbed: Vec<_> = trees.iter().map(|t| glob(t, "*", 0).unwrap()).collect();
+        assert!(globbed.windows(2).all(|w| w[0] == w[1]));
+        let names: Vec<&str> = globbed[0].iter().map(|(p, _)| p.as_str()).collect();
+        assert_eq!(names[..2], ["a", &path("a")][..]);
+    }
+
+    #[test]
+    fn too_deep_ncdu_export_is_an_error() {
+        let err = import_ncdu(ncdu_chain(100_000).as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    fn nested() -> FileNode {
+        dir("root", vec![dir("Users", vec![dir("me", vec![FileNode::new_file("notes.txt", 1)])])])
+    }
+
+    #[test]
+    fn equal_caches_are_listed_by_path() {
+        let cache = |size| dir("cache", vec![FileNode::new_file("f", size)]);
+        let tree = dir(
+            "root",
+            vec![dir("b", vec![cache(10)]), dir("a", vec![cache(10)]), dir("c", vec![cache(20)])],
+        );
+        let found = find_caches(&tree, &["cache"]);
+        let path = |p: &str| p.replace('/', std::path::MAIN_SEPARATOR_STR);
+        let expected = [
+            (vec![2, 0], path("c/cache"), 20),
+            (vec![1, 0], path("a/cache"), 10),
+            (vec![0, 0], path("b/cache"), 10),
+        ];
+        assert_eq!(found, expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn path_of_under_unix_roots() {
+        let tree = nested();
+        let path = |root: &str, nav: &[usize]| path_of(&tree, Path::new(root), nav);
+        assert_eq!(path("/", &[]), Some(PathBuf::from("/")));
+        assert_eq!(path("/", &[0, 0, 0]), Some(PathBuf::from("/Users/me/notes.txt")));
+        assert_eq!(path("/data", &[0, 0]), Some(PathBuf::from("/data/Users/me")));
+        assert_eq!(path("/data", &[0, 1]), None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn path_of_under_windows_roots() {
+        let tree = nested();
+        let path = |root: &str, nav: &[usize]| path_of(&tree, Path::new(root), nav);
+        assert_eq!(path(r"C:\", &[]), Some(PathBuf::from(r"C:\")));
+        assert_eq!(path(r"C:\", &[0, 0, 0]), Some(PathBuf::from(r"C:\Users\me\notes.txt")));
+        assert_eq!(path(r"D:\data", &[0, 0]), Some(PathBuf::from(r"D:\data\Users\me")));
+        assert_eq!(path(r"C:\", &[1]), None);
+    }
+}
@@ -0,0 +1,226 @@
+//! Squarified treemap layout, and an SVG rendering of it for reports.
+
+use std::cmp::Reverse;
+use std::io::{self, Write};
+
+use crate::tree::{FileNode, ScanResult};
+use crate::utils::{format_size, percent};
+
+/// A rectangle in layout space.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Tile {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+
+/// Lay out `sizes` (largest first, all non-zero) inside `bounds` with the
+/// squarified treemap algorithm. Returns one tile per input in the same order;
+/// tiles cover `bounds` exactly, with no gaps or overlaps.
+pub fn squarify(sizes: &[u64], bounds: Tile) -> Vec<Tile> {
+    let total = crate::tree::saturating_sum(sizes.iter().copied());
+    if total == 0 || bounds.w <= 0.0 || bounds.h <= 0.0 {
+        return vec![Tile::default(); sizes.len()];
+    }
+
+    let mut space = bounds;
+    let scale = space.w * space.h / total as f64;
+    let areas: Vec<f64> = sizes.iter().map(|&s| s as f64 * scale).collect();
+
+    let mut out = Vec::with_capacity(sizes.len());
+    let mut start = 0;
+    while start < areas.len() {
+        let side = space.w.min(space.h);
+        let mut end = start + 1;
+        while end < areas.len() && worst(&areas[start..=end], side) <= worst(&areas[start..end], side) {
+            end += 1;
+        }
+        space.lay_row(&areas[start..end], &mut out);
+        start = end;
+    }
+    out
+}
+
+/// Worst aspect ratio among `row` when laid along a side of length `side`.
+fn worst(row: &[f64], side: f64) -> f64 {
+    let sum: f64 = row.iter().sum();
+    if sum <= 0.0 || side <= 0.0 {
+        return f64::INFINITY;
+    }
+    let side2 = side * side;
+    let sum2 = sum * sum;
+    row.iter()
+        .map(|&a| (side2 * a / sum2).max(sum2 / (side2 * a)))
+        .fold(0.0, f64::max)
+}
+
+impl Tile {
+    /// Place one row of areas along the shorter side and shrink to the rest.
+    fn lay_row(&mut self, row: &[f64], out: &mut Vec<Tile>) {
+        let sum: f64 = row.iter().sum();
+        if self.w >= self.h {
+            // Column on the left edge
+            let col_w = if self.h > 0.0 { sum / self.h } else { 0.0 };
+            let mut y = self.y;
+            for &a in row {
+                let h = if col_w > 0.0 { a / col_w } else { 0.0 };
+                out.push(Tile { x: self.x, y, w: col_w, h });
+                y += h;
+            }
+            self.x += col_w;
+            self.w -= col_w;
+        } else {
+            // Row along the top edge
+            let row_h = if self.w > 0.0 { sum / self.w } else { 0.0 };
+            let mut x = self.x;
+            for &a in row {
+                let w = if row_h > 0.0 { a / row_h } else { 0.0 };
+                out.push(Tile { x, y: self.y, w, h: row_h });
+                x += w;
+            }
+            self.y += row_h;
+            self.h -= row_h;
+        }
+    }
+}
+
+/// Most children drawn in an exported treemap; the rest would be specks.
+const SVG_MAX_ITEMS: usize = 1000;
+const SVG_FONT_SIZE: f64 = 12.0;
+/// Rough advance of one character at [`SVG_FONT_SIZE`] in a sans-serif face.
+const SVG_CHAR_WIDTH: f64 = 7.0;
+const SVG_PADDING: f64 = 4.0;
+
+/// Write the children of the directory at `nav_path` as a `width` x `height`
+/// SVG treemap. Each block is shaded by its share of the directory (steel blue
+/// for slivers, warming to red for the biggest) and labelled with its name and size
+/// where they fit; tiny blocks get no text, and long names are cut short.
+/// Hovering a block shows its full name, size and share.
+pub fn export_treemap_svg<W: Write>(
+    scan: &ScanResult,
+    nav_path: &[usize],
+    width: u32,
+    height: u32,
+    writer: &mut W,
+) -> io::Result<()> {
+    let node = scan
+        .node_at(nav_path)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no such directory in the scan"))?;
+    let total = node.size;
+
+    let mut order: Vec<&FileNode> = node.children.iter().filter(|c| c.size > 0).collect();
+    order.sort_unstable_by_key(|c| Reverse(c.size));
+    order.truncate(SVG_MAX_ITEMS);
+    let sizes: Vec<u64> = order.iter().map(|c| c.size).collect();
+    let bounds = Tile {
+        x: 0.0,
+        y: 0.0,
+        w: width as f64,
+        h: height as f64,
+    };
+    let tiles = squarify(&sizes, bounds);
+
+    let title = scan.path_of(nav_path).unwrap_or_else(|| scan.root_path.clone());
+    writeln!(
+        writer,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="0 0 {w} {h}" font-family="sans-serif" font-size="{f}">"#,
+        w = width,
+        h = height,
+        f = SVG_FONT_SIZE,
+    )?;
+    writeln!(
+        writer,
+        "<title>{} ({})</title>",
+        escape(&title.display().to_string()),
+        format_size(total)
+    )?;
+    writeln!(writer, r##"<rect width="100%" height="100%" fill="#1e1e24"/>"##)?;
+
+    for (child, tile) in order.iter().zip(tiles) {
+        if tile.w < 1.0 || tile.h < 1.0 {
+            continue;
+        }
+        let share = percent(child.size, total);
+        let suffix = if child.is_dir { "/" } else if child.trash { " (Trash)" } else { "" };
+        writeln!(writer, "<g>")?;
+        writeln!(
+            writer,
+            "<title>{}{} {} ({:.1}%)</title>",
+            escape(&child.name),
+            suffix,
+            format_size(child.size),
+            share
+        )?;
+        writeln!(
+            writer,
+            r##"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}" stroke="#1e1e24" stroke-width="1"/>"##,
+            tile.x,
+            tile.y,
+            tile.w,
+            tile.h,
+            shade(share)
+        )?;
+
+        let room = ((tile.w - 2.0 * SVG_PADDING) / SVG_CHAR_WIDTH).floor() as usize;
+        if room >= 3 && tile.h >= SVG_FONT_SIZE + 2.0 * SVG_PADDING {
+            let label = fit(&format!("{}{}", child.name, suffix), room);
+            let x = tile.x + SVG_PADDING;
+            let y = tile.y + SVG_PADDING + SVG_FONT_SIZE;
+            writeln!(
+                writer,
+                r##"<text x="{:.1}" y="{:.1}" fill="#f0f0f0">{}</text>"##,
+                x,
+                y,
+                escape(&label)
+            )?;
+            if tile.h >= 2.0 * SVG_FONT_SIZE + 3.0 * SVG_PADDING {
+                let detail = fit(&format!("{} {:.1}%", format_size(child.size), share), room);
+                writeln!(
+                    writer,
+                    r##"<text x="{:.1}" y="{:.1}" fill="#c8c8c8">{}</text>"##,
+                    x,
+                    y + SVG_FONT_SIZE + SVG_PADDING,
+                    escape(&detail)
+                )?;
+            }
+        }
+        writeln!(writer, "</g>")?;
+    }
+    writeln!(writer, "</svg>")
+}
+
+/// Fill color for a block holding `share` percent of its directory.
+fn shade(share: f64) -> String {
+    // Square root so mid-sized blocks don't all look the same pale shade
+    let t = (share / 100.0).clamp(0.0, 1.0).sqrt();
+    let lerp = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", lerp(70, 200), lerp(100, 70), lerp(150, 90))
+}
+
+/// `text` cut to at most `max` characters, with a trailing `…` when cut.
+fn fit(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        text.to_string()
+    } else {
+        let mut out: String = text.chars().take(max.saturating_sub(1)).collect();
+        out.push('…');
+        out
+    }
+}
+
+/// Escape the characters XML gives meaning to.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
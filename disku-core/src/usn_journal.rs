@@ -0,0 +1,314 @@
+//! Bringing an earlier scan of an NTFS volume up to date from the USN change
+//! journal, instead of rescanning everything.
+//!
+//! The journal says *which* paths changed, not what they look like now, so
+//! each changed path is simply looked at again: files are re-stat'ed, missing
+//! entries removed, and directories new to the tree (created, or renamed in
+//! from elsewhere) scanned in full. Directory totals are then recomputed.
+//!
+//! Like [`crate::mft_scanner`], this needs an elevated process.
+
+use std::collections::BTreeSet;
+use std::mem::size_of;
+use std::path::{Component, Path, PathBuf};
+
+use ntfs_reader::journal::{HistorySize, Journal, JournalOptions, NextUsn};
+use ntfs_reader::volume::Volume;
+use windows::core::HSTRING;
+use windows::Win32::Foundation::{CloseHandle, GENERIC_READ, HANDLE};
+use windows::Win32::Storage::FileSystem::{
+    CreateFileW, FILE_FLAGS_AND_ATTRIBUTES, FILE_SHARE_DELETE, FILE_SHARE_READ, FILE_SHARE_WRITE,
+    OPEN_EXISTING,
+};
+use windows::Win32::System::Ioctl::{FSCTL_QUERY_USN_JOURNAL, USN_JOURNAL_DATA_V0};
+use windows::Win32::System::IO::DeviceIoControl;
+
+use crate::scanner::{
+    allocated_size, is_compressed, mtime_of, scan_with_options, ProgressSink, ScanOptions,
+};
+use crate::tree::{FileNode, JournalCursor, ScanResult};
+
+/// `ERROR_JOURNAL_NOT_ACTIVE`: the volume has no change journal.
+const ERROR_JOURNAL_NOT_ACTIVE: i32 = 1179;
+/// `ERROR_JOURNAL_ENTRY_DELETED`: the requested USN has been purged.
+const ERROR_JOURNAL_ENTRY_DELETED: i32 = 1181;
+
+/// Why a scan couldn't be refreshed from the journal. In every case the
+/// caller's recourse is a full rescan.
+#[derive(Debug)]
+pub enum JournalError {
+    /// The scan never recorded a journal position (not NTFS, or not elevated
+    /// at scan time).
+    NoCursor,
+    /// The volume has no active journal, or it couldn't be opened.
+    Unavailable(String),
+    /// The journal was deleted and recreated since the scan.
+    Recreated,
+    /// Records since the scan have been purged; the journal wrapped.
+    Wrapped,
+}
+
+impl std::fmt::Display for JournalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JournalError::NoCursor => write!(f, "scan has no change journal position"),
+            JournalError::Unavailable(msg) => write!(f, "change journal unavailable: {}", msg),
+            JournalError::Recreated => write!(f, "change journal was recreated since the scan"),
+            JournalError::Wrapped => write!(f, "change journal no longer reaches back to the scan"),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+impl From<std::io::Error> for JournalError {
+    fn from(e: std::io::Error) -> Self {
+        match e.raw_os_error() {
+            Some(ERROR_JOURNAL_ENTRY_DELETED) => JournalError::Wrapped,
+            Some(ERROR_JOURNAL_NOT_ACTIVE) => {
+                JournalError::Unavailable("no journal on this volume".to_string())
+            }
+            _ => JournalError::Unavailable(e.to_string()),
+        }
+    }
+}
+
+/// Drive letter of an absolute `X:\...` path.
+fn drive_letter(path: &Path) -> Option<char> {
+    let s = path.to_string_lossy();
+    let bytes = s.as_bytes();
+    (bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic())
+        .then(|| bytes[0] as char)
+}
+
+fn volume_path(letter: char) -> String {
+    format!("\\\\.\\{}:", letter)
+}
+
+/// Current state of the journal for the volume holding `path`. Take this
+/// *before* scanning so changes made during the scan are replayed later.
+pub fn journal_cursor(path: &Path) -> Result<JournalCursor, JournalError> {
+    let letter = drive_letter(path)
+        .ok_or_else(|| JournalError::Unavailable("not a drive path".to_string()))?;
+    let data = query_journal(letter)?;
+    Ok(JournalCursor {
+        journal_id: data.UsnJournalID,
+        next_usn: data.NextUsn,
+    })
+}
+
+fn query_journal(letter: char) -> Result<USN_JOURNAL_DATA_V0, JournalError> {
+    let volume = HSTRING::from(volume_path(letter));
+    let mut data = USN_JOURNAL_DATA_V0::default();
+    unsafe {
+        let handle: HANDLE = CreateFileW(
+            &volume,
+            GENERIC_READ.0,
+            FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE,
+            None,
+            OPEN_EXISTING,
+            FILE_FLAGS_AND_ATTRIBUTES(0),
+            None,
+        )
+        .map_err(std::io::Error::from)?;
+        let mut returned = 0u32;
+        let result = DeviceIoControl(
+            handle,
+            FSCTL_QUERY_USN_JOURNAL,
+            None,
+            0,
+            Some(&mut data as *mut _ as *mut std::ffi::c_void),
+            size_of::<USN_JOURNAL_DATA_V0>() as u32,
+            Some(&mut returned),
+            None,
+        );
+        let _ = CloseHandle(handle);
+        result.map_err(std::io::Error::from)?;
+    }
+    Ok(data)
+}
+
+/// Apply every journal record since `scan.journal` to the tree and advance the
+/// cursor. Only changes under `scan.root_path` are considered; new
+/// directories are scanned with `options`.
+pub fn apply_journal<P: ProgressSink + ?Sized>(
+    scan: &mut ScanResult,
+    options: &ScanOptions,
+    progress: &P,
+) -> Result<(), JournalError> {
+    let cursor = scan.journal.ok_or(JournalError::NoCursor)?;
+    let letter = drive_letter(&scan.root_path)
+        .ok_or_else(|| JournalError::Unavailable("not a drive path".to_string()))?;
+
+    let data = query_journal(letter)?;
+    if data.UsnJournalID != cursor.journal_id {
+        return Err(JournalError::Recreated);
+    }
+    if cursor.next_usn < data.FirstUsn {
+        return Err(JournalError::Wrapped);
+    }
+
+    let changed = changed_paths(letter, cursor.next_usn, data.NextUsn)?;
+
+    let root_parts = relative_parts(&scan.root_path);
+    for parts in changed {
+        // Case-insensitive prefix match, as NTFS names are
+        let under_root = parts.len() > root_parts.len()
+            && root_parts
+                .iter()
+                .zip(&parts)
+                .all(|(a, b)| a.eq_ignore_ascii_case(b));
+        if under_root {
+            apply_change(scan, &parts[root_parts.len()..], options, progress);
+        }
+    }
+
+    refresh_totals(&mut scan.tree);
+    scan.tree.sort_by_size();
+    scan.journal = Some(JournalCursor {
+        journal_id: cursor.journal_id,
+        next_usn: data.NextUsn,
+    });
+    Ok(())
+}
+
+/// Volume-relative paths touched by records in `from..end`, shallowest first
+/// so parents are settled before their children.
+fn changed_paths(letter: char, from: i64, end: i64) -> Result<Vec<Vec<String>>, JournalError> {
+    let volume = Volume::new(volume_path(letter))
+        .map_err(|e| JournalError::Unavailable(e.to_string()))?;
+    let mut journal = Journal::new(
+        volume,
+        JournalOptions {
+            next_usn: NextUsn::Custom(from),
+            // Renames are handled by re-checking both names; no pairing needed
+            max_history_size: HistorySize::Limited(1),
+            ..JournalOptions::default()
+        },
+    )?;
+
+    let mut paths = BTreeSet::new();
+    while journal.get_next_usn() < end {
+        let before = journal.get_next_usn();
+        for record in journal.read_sized::<65536>()? {
+            // Records whose parent no longer resolves come back as a bare
+            // name; the parent's own record covers them
+            if record.path.has_root() {
+                paths.insert(relative_parts(&record.path));
+            }
+        }
+        if journal.get_next_usn() == before {
+            break;
+        }
+    }
+
+    let mut paths: Vec<Vec<String>> = paths.into_iter().filter(|p| !p.is_empty()).collect();
+    paths.sort_by_key(|p| p.len());
+    Ok(paths)
+}
+
+/// The normal components of `path`, without drive or root.
+fn relative_parts(path: &Path) -> Vec<String> {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Re-check one changed path (relative to the scan root) and update the tree.
+fn apply_change<P: ProgressSink + ?Sized>(
+    scan: &mut ScanResult,
+    parts: &[String],
+    options: &ScanOptions,
+    progress: &P,
+) {
+    let real: PathBuf = parts.iter().fold(scan.root_path.clone(), |p, c| p.join(c));
+    let Some((name, parents)) = parts.split_last() else {
+        return;
+    };
+    progress.set_current(&real);
+
+    match std::fs::symlink_metadata(&real) {
+        Ok(meta) if meta.is_dir() => {
+            let parent = dir_mut(&mut scan.tree, parents);
+            let existing = parent
+                .children
+                .iter()
+                .position(|c| c.name.eq_ignore_ascii_case(name));
+            if existing.is_some_and(|i| parent.children[i].is_dir) {
+                // Its contents get their own records
+                return;
+            }
+            let mut node = scan_with_options(&real, progress, options);
+            node.name = name.as_str().into();
+            match existing {
+                Some(i) => parent.children[i] = node,
+                None => parent.children.push(node),
+            }
+        }
+        Ok(meta) => {
+            let node = FileNode::new_file(name.as_str(), meta.len())
+                .with_allocation(allocated_size(&meta), is_compressed(&meta))
+                .with_mtime(mtime_of(&meta));
+            let parent = dir_mut(&mut scan.tree, parents);
+            match parent
+                .children
+                .iter()
+                .position(|c| c.name.eq_ignore_ascii_case(name))
+            {
+                Some(i) => parent.children[i] = node,
+                None => parent.children.push(node),
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            if let Some(parent) = existing_dir_mut(&mut scan.tree, parents) {
+                parent.remove_child_by_name(name);
+            }
+        }
+        Err(_) => progress.error(&real),
+    }
+}
+
+/// The directory at `parts` below `root`, creating any that are missing (or
+/// replacing files that have since become directories).
+fn dir_mut<'a>(root: &'a mut FileNode, parts: &[String]) -> &'a mut FileNode {
+    let mut node = root;
+    for part in parts {
+        let idx = match node
+            .children
+            .iter()
+            .position(|c| c.name.eq_ignore_ascii_case(part))
+        {
+            Some(i) => {
+                if !node.children[i].is_dir {
+                    node.children[i] = FileNode::new_dir(part.as_str());
+                }
+                i
+            }
+            None => {
+                node.children.push(FileNode::new_dir(part.as_str()));
+                node.children.len() - 1
+            }
+        };
+        node = &mut node.children[idx];
+    }
+    node
+}
+
+fn existing_dir_mut<'a>(root: &'a mut FileNode, parts: &[String]) -> Option<&'a mut FileNode> {
+    let mut node = root;
+    for part in parts {
+        node = node
+            .children
+            .iter_mut()
+            .find(|c| c.is_dir && c.name.eq_ignore_ascii_case(part))?;
+    }
+    Some(node)
+}
+
+/// Recompute every directory's totals bottom-up.
+fn refresh_totals(node: &mut FileNode) {
+    node.for_each_dir_bottom_up(FileNode::update_totals);
+}
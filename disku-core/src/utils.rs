@@ -17,14 +17,625 @@ pub fn format_size(bytes: u64) -> String {
     }
 }
 
+/// `part` as a percentage of `total`, clamped to 0..=100. An empty total (a
+/// directory of zero-byte files) yields 0.0 rather than NaN.
 pub fn percent(part: u64, total: u64) -> f64 {
     if total == 0 {
         0.0
     } else {
-        (part as f64 / total as f64) * 100.0
+        ((part as f64 / total as f64) * 100.0).min(100.0)
     }
 }
 
+/// `n` with thousands separators: `8,432`.
+pub fn format_count(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Estimated monthly cost of storing `bytes` at `rate` per GB-month, such as
+/// `$4.60/mo`: whole dollars from $1,000 up, and `<$0.01/mo` for anything
+/// under a cent. A GB is 1024³ bytes, as in [`format_size`].
+pub fn format_cost(bytes: u64, rate: f64) -> String {
+    let cost = bytes as f64 / (1u64 << 30) as f64 * rate;
+    if cost > 0.0 && cost < 0.005 {
+        "<$0.01/mo".to_string()
+    } else if cost >= 1000.0 {
+        format!("${}/mo", format_count(cost.round() as u64))
+    } else {
+        format!("${:.2}/mo", cost)
+    }
+}
+
+/// Human-readable elapsed time: `350ms`, `2.5s`, `4m 12s`, `1h 23m 4s`.
+/// Negative and non-finite inputs read as zero.
+pub fn format_duration(secs: f64) -> String {
+    let secs = if secs.is_finite() { secs.max(0.0) } else { 0.0 };
+    // Thresholds sit just below each unit so rounding never prints `1000ms`
+    // or `60.0s`
+    if secs < 0.9995 {
+        return format!("{:.0}ms", secs * 1000.0);
+    }
+    if secs < 59.95 {
+        return format!("{:.1}s", secs);
+    }
+    let total = secs.round() as u64;
+    let (h, m, s) = (total / 3600, total % 3600 / 60, total % 60);
+    if h > 0 {
+        format!("{}h {}m {}s", h, m, s)
+    } else {
+        format!("{}m {}s", m, s)
+    }
+}
+
+/// How long before `now` a Unix timestamp was: `just now`, `5 minutes ago`,
+/// `3 days ago`, `2 years ago`. Times after `now` (clock skew) read as
+/// `just now`; a zero timestamp means unknown and reads as `-`.
+pub fn format_relative_time(time: u64, now: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
+    const MONTH: u64 = 30 * DAY;
+    const YEAR: u64 = 365 * DAY;
+
+    if time == 0 {
+        return "-".to_string();
+    }
+    let age = now.saturating_sub(time);
+    let (count, unit) = if age >= YEAR {
+        (age / YEAR, "year")
+    } else if age >= MONTH {
+        (age / MONTH, "month")
+    } else if age >= DAY {
+        (age / DAY, "day")
+    } else if age >= HOUR {
+        (age / HOUR, "hour")
+    } else if age >= MINUTE {
+        (age / MINUTE, "minute")
+    } else {
+        return "just now".to_string();
+    };
+    let plural = if count == 1 { "" } else { "s" };
+    format!("{} {}{} ago", count, unit, plural)
+}
+
+/// A Unix timestamp as UTC in ISO 8601: `2024-03-09T14:05:00Z`.
+pub fn format_utc(secs: u64) -> String {
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // Days since 1970-01-01 to a civil date, counting in 400-year eras that
+    // start on 1 March so leap days fall at the end of each year
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60
+    )
+}
+
+/// Decode a UTF-16 file name (as NTFS stores them) for display. Surrogate
+/// pairs decode normally; a name with an unpaired surrogate comes back with
+/// U+FFFD in its place, along with the original units so paths to it can
+/// still be built.
+pub fn decode_utf16_name(units: &[u16]) -> (String, Option<Box<[u16]>>) {
+    match String::from_utf16(units) {
+        Ok(name) => (name, None),
+        Err(_) => (String::from_utf16_lossy(units), Some(units.into())),
+    }
+}
+
+/// The form of a scan root every front end records: absolute, with symlinks
+/// (including the root itself) resolved, so the tree's name and every path
+/// built from it describe where the data really lives. Paths that can't be
+/// resolved are returned unchanged and left to the scanner to report.
+///
+/// A leading `~` stands for the home directory, for paths that never went
+/// through a shell (the GUI's path box, a quoted argument), and an empty path
+/// for the current directory.
+///
+/// On Windows the `\\?\` prefix `canonicalize` adds to drive paths is
+/// dropped, since the drive-letter checks and display expect `C:\...`.
+pub fn canonical_root(path: &std::path::Path) -> std::path::PathBuf {
+    let path = if path.as_os_str().is_empty() {
+        std::path::Path::new(".")
+    } else {
+        path
+    };
+    let expanded = expand_home(path);
+    let path = expanded.as_deref().unwrap_or(path);
+    let Ok(canonical) = path.canonicalize() else {
+        return path.to_path_buf();
+    };
+    #[cfg(windows)]
+    {
+        let s = canonical.to_string_lossy();
+        if let Some(rest) = s.strip_prefix(r"\\?\") {
+            let b = rest.as_bytes();
+            if b.len() >= 2 && b[1] == b':' && b[0].is_ascii_alphabetic() {
+                return std::path::PathBuf::from(rest);
+            }
+        }
+    }
+    canonical
+}
+
+/// The drive letter of `path` when it names a whole drive (`C:\`, `C:/` or
+/// `C:`), and None for anything below one or any other kind of path. Only
+/// a whole drive can be read from its MFT.
+pub fn drive_root_letter(path: &std::path::Path) -> Option<char> {
+    let s = path.to_str()?;
+    match s.as_bytes() {
+        [letter, b':'] | [letter, b':', b'\\' | b'/'] if letter.is_ascii_alphabetic() => {
+            Some(*letter as char)
+        }
+        _ => None,
+    }
+}
+
+/// `path` with a leading `~` component replaced by the home directory; None
+/// when it has none, or there's no home directory to put there.
+fn expand_home(path: &std::path::Path) -> Option<std::path::PathBuf> {
+    let rest = path.strip_prefix("~").ok()?;
+    Some(crate::config::home_dir()?.join(rest))
+}
+
+/// Name for the root node of a tree scanned from `root`. It's the path
+/// itself, since paths below the root are built from its name, with trailing
+/// separators and `.` components dropped (`/data/` and `/data/.` are both
+/// `/data`). A filesystem root keeps its separator (`/`, and `C:\` as the MFT
+/// scanner names drives), and an empty path is `.` rather than a name of
+/// zero length. Every scanner names its root this way.
+pub fn root_name(root: &std::path::Path) -> String {
+    let normalized: std::path::PathBuf = root.components().collect();
+    if normalized.as_os_str().is_empty() {
+        return ".".to_string();
+    }
+    normalized.to_string_lossy().to_string()
+}
+
+/// Operating system directories protected along with everything in them, as
+/// compared by [`is_protected_path`].
+const PROTECTED_TREES: &[&str] = &[
+    "/bin",
+    "/boot",
+    "/dev",
+    "/etc",
+    "/lib",
+    "/lib32",
+    "/lib64",
+    "/proc",
+    "/sbin",
+    "/sys",
+    "/usr",
+    "/system",
+    "/private/etc",
+    "/private/var/db",
+    "c:/windows",
+    "c:/program files",
+    "c:/program files (x86)",
+    "c:/programdata",
+    "c:/recovery",
+    "c:/$recycle.bin",
+    "c:/boot",
+    "c:/bootmgr",
+    "c:/efi",
+];
+
+/// Directories protected themselves, though what's inside may go.
+const PROTECTED_DIRS: &[&str] = &[
+    "/applications",
+    "/library",
+    "/media",
+    "/mnt",
+    "/opt",
+    "/private",
+    "/root",
+    "/srv",
+    "/var",
+    "/volumes",
+];
+
+/// Directories holding home directories: protected, as is each home
+/// directory in them, but not what's inside a home directory.
+const HOME_PARENTS: &[&str] = &["/home", "/users", "c:/users"];
+
+/// Whether deleting `path` would take out something the system or its users
+/// can't do without: a filesystem or drive root, an operating system
+/// directory or anything in it (`/usr`, `/System`, `C:\Windows`), a
+/// top-level container like `/var` or `/Applications`, or a home directory
+/// or the directory holding them. `extra` adds the user's own prefixes
+/// (`cleanup.protected_paths`), protected with everything below them. Paths
+/// should be absolute; they're compared case-insensitively, with either kind
+/// of separator, so one list serves every platform.
+pub fn is_protected_path(path: &std::path::Path, extra: &[std::path::PathBuf]) -> bool {
+    fn normalize(path: &std::path::Path) -> String {
+        let path = path.to_string_lossy().replace('\\', "/").to_lowercase();
+        match path.trim_end_matches('/') {
+            "" => "/".to_string(),
+            trimmed => trimmed.to_string(),
+        }
+    }
+    fn within(path: &str, prefix: &str) -> bool {
+        let prefix = prefix.trim_end_matches('/');
+        path == prefix || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+    }
+
+    let path = normalize(path);
+    // `/`, and drive roots like `C:` (`C:\` once trimmed)
+    if path == "/" || (path.len() == 2 && path.ends_with(':')) {
+        return true;
+    }
+    let parent = path.rsplit_once('/').map_or("", |(parent, _)| parent);
+    PROTECTED_TREES.iter().any(|tree| within(&path, tree))
+        || PROTECTED_DIRS.contains(&path.as_str())
+        || HOME_PARENTS.iter().any(|&home| path == home || parent == home)
+        || extra.iter().any(|prefix| within(&path, &normalize(prefix)))
+}
+
+/// Build a `file://` URL for an absolute path, percent-encoding everything
+/// outside the unreserved set. Windows paths become `file:///C:/...`.
+pub fn file_url(path: &std::path::Path) -> String {
+    let raw = path.to_string_lossy();
+    let mut normalized = raw.replace('\\', "/");
+    if !normalized.starts_with('/') {
+        normalized.insert(0, '/');
+    }
+
+    let mut url = String::with_capacity(normalized.len() + 8);
+    url.push_str("file://");
+    for b in normalized.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                url.push(b as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    url
+}
+
+/// Broad kind of content a file holds, judged from its extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize)]
+pub enum Category {
+    Video,
+    Image,
+    Audio,
+    Document,
+    Code,
+    Archive,
+    DiskImage,
+    Executable,
+    Other,
+}
+
+impl Category {
+    pub fn label(self) -> &'static str {
+        match self {
+            Category::Video => "Videos",
+            Category::Image => "Photos & images",
+            Category::Audio => "Music & audio",
+            Category::Document => "Documents",
+            Category::Code => "Code",
+            Category::Archive => "Archives",
+            Category::DiskImage => "Disk images",
+            Category::Executable => "Programs",
+            Category::Other => "Other",
+        }
+    }
+}
+
+/// Extensions (lowercase, without the dot) for each category. Anything not
+/// listed here is [`Category::Other`]; add a row or an extension to extend it.
+const CATEGORY_EXTENSIONS: &[(Category, &[&str])] = &[
+    (
+        Category::Video,
+        &["mp4", "mkv", "mov", "avi", "wmv", "webm", "m4v", "flv", "mpg", "mpeg", "3gp"],
+    ),
+    (
+        Category::Image,
+        &[
+            "jpg", "jpeg", "png", "gif", "heic", "heif", "webp", "bmp", "tif", "tiff", "raw",
+            "cr2", "cr3", "nef", "arw", "dng", "psd", "svg", "ico",
+        ],
+    ),
+    (
+        Category::Audio,
+        &["mp3", "flac", "wav", "aac", "m4a", "ogg", "opus", "wma", "aiff", "alac"],
+    ),
+    (
+        Category::Document,
+        &[
+            "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "rtf",
+            "txt", "md", "epub", "pages", "numbers", "key", "csv",
+        ],
+    ),
+    (
+        Category::Code,
+        &[
+            "rs", "c", "h", "cpp", "hpp", "cc", "cs", "go", "java", "kt", "swift", "py", "rb",
+            "js", "jsx", "ts", "tsx", "svelte", "vue", "php", "sh", "ps1", "lua", "json",
+            "toml", "yaml", "yml", "xml", "html", "css", "scss", "sql",
+        ],
+    ),
+    (
+        Category::Archive,
+        &["zip", "tar", "gz", "tgz", "bz2", "xz", "zst", "7z", "rar", "lz4"],
+    ),
+    (
+        Category::DiskImage,
+        &["iso", "dmg", "img", "vhd", "vhdx", "vmdk", "qcow2", "vdi"],
+    ),
+    (
+        Category::Executable,
+        &["exe", "msi", "dll", "so", "dylib", "app", "pkg", "deb", "rpm", "apk", "appimage"],
+    ),
+];
+
+/// Which [`Category`] a file name falls into, by extension (case-insensitive).
+pub fn categorize(name: &str) -> Category {
+    let Some(ext) = std::path::Path::new(name).extension().and_then(|e| e.to_str()) else {
+        return Category::Other;
+    };
+    CATEGORY_EXTENSIONS
+        .iter()
+        .find(|(_, exts)| exts.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+        .map_or(Category::Other, |&(category, _)| category)
+}
+
+/// Names of the immediate subdirectories of `dir`, sorted case-insensitively.
+/// Unreadable directories yield an empty list.
+pub fn list_subdirs(dir: &std::path::Path) -> Vec<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort_by_key(|n| n.to_lowercase());
+    names
+}
+
+/// Filesystem block size (`statvfs` `f_bsize`) per device, so each device is
+/// queried once per scan rather than once per file.
+#[derive(Default)]
+pub struct BlockSizeCache {
+    sizes: std::sync::Mutex<rustc_hash::FxHashMap<u64, Option<u64>>>,
+}
+
+impl BlockSizeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Block size of the filesystem on device `dev`, statting `path` (any
+    /// path on that device) the first time the device is seen.
+    pub fn get(&self, dev: u64, path: &std::path::Path) -> Option<u64> {
+        let mut sizes = self.sizes.lock().unwrap_or_else(|e| e.into_inner());
+        *sizes.entry(dev).or_insert_with(|| statvfs_block_size(path))
+    }
+}
+
+/// Round `size` up to a whole number of `block`-byte blocks, like `du`.
+pub fn round_up_to_block(size: u64, block: u64) -> u64 {
+    if block == 0 {
+        size
+    } else {
+        size.div_ceil(block) * block
+    }
+}
+
+/// Preferred I/O block size of the filesystem holding `path`.
+#[cfg(unix)]
+pub(crate) fn statvfs_block_size(path: &std::path::Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if ret != 0 {
+        return None;
+    }
+
+    let stat = unsafe { stat.assume_init() };
+    // `c_ulong` is only 32 bits on some targets
+    #[allow(clippy::unnecessary_cast)]
+    let block = stat.f_bsize as u64;
+    Some(block).filter(|&b| b > 0)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn statvfs_block_size(_path: &std::path::Path) -> Option<u64> {
+    None
+}
+
+/// The process's soft limit on open file descriptors (`ulimit -n`), or
+/// `None` when it's unlimited or the platform has no such limit.
+#[cfg(unix)]
+pub(crate) fn open_file_limit() -> Option<u64> {
+    let mut limit = std::mem::MaybeUninit::<libc::rlimit>::uninit();
+    if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, limit.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let limit = unsafe { limit.assume_init() };
+    // `rlim_t` is only 32 bits on some targets
+    #[allow(clippy::unnecessary_cast)]
+    let soft = limit.rlim_cur as u64;
+    (limit.rlim_cur != libc::RLIM_INFINITY).then_some(soft)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn open_file_limit() -> Option<u64> {
+    None
+}
+
+/// Filesystem type (`ext4`, `apfs`, `exfat`, ...) of the mount containing
+/// `path`.
+#[cfg(target_os = "linux")]
+pub fn fs_type(path: &std::path::Path) -> Option<String> {
+    // The longest mount point containing `path` is the one it's on; a later
+    // line for the same mount point is mounted over the earlier ones
+    let mounts = std::fs::read_to_string("/proc/self/mounts").ok()?;
+    let mut best: Option<(usize, &str)> = None;
+    for line in mounts.lines() {
+        let mut fields = line.split(' ');
+        let (Some(_), Some(mount), Some(fs)) = (fields.next(), fields.next(), fields.next()) else {
+            continue;
+        };
+        let mount = unescape_mount_path(mount);
+        if path.starts_with(&mount) {
+            let depth = mount.components().count();
+            if best.is_none_or(|(d, _)| depth >= d) {
+                best = Some((depth, fs));
+            }
+        }
+    }
+    best.map(|(_, fs)| fs.to_string())
+}
+
+/// `/proc/mounts` writes space, tab, newline and backslash in paths as
+/// three-digit octal escapes (`\040` for a space).
+#[cfg(target_os = "linux")]
+fn unescape_mount_path(field: &str) -> std::path::PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+
+    let bytes = field.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes.get(i + 1..i + 4).filter(|d| d.iter().all(|b| (b'0'..=b'7').contains(b)));
+        match octal {
+            Some(digits) if bytes[i] == b'\\' => {
+                out.push(digits.iter().fold(0u8, |n, d| n.wrapping_mul(8) + (d - b'0')));
+                i += 4;
+            }
+            _ => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    std::ffi::OsString::from_vec(out).into()
+}
+
+#[cfg(target_os = "macos")]
+pub fn fs_type(path: &std::path::Path) -> Option<String> {
+    use std::ffi::{CStr, CString};
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statfs>::uninit();
+    let ret = unsafe { libc::statfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if ret != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    let name = unsafe { CStr::from_ptr(stat.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn fs_type(_path: &std::path::Path) -> Option<String> {
+    None
+}
+
+/// Whether `path` is on a spinning disk, from the `queue/rotational` flag of
+/// its block device in sysfs. `None` when there's no block device to ask
+/// (network and virtual filesystems) and on other platforms.
+#[cfg(target_os = "linux")]
+pub fn is_rotational(path: &std::path::Path) -> Option<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let dev = std::fs::metadata(path).ok()?.dev();
+    let (major, minor) = (libc::major(dev), libc::minor(dev));
+    let device = std::fs::canonicalize(format!("/sys/dev/block/{}:{}", major, minor)).ok()?;
+    // A partition has no queue of its own; the whole disk above it does
+    [device.as_path(), device.parent()?]
+        .iter()
+        .find_map(|dir| std::fs::read_to_string(dir.join("queue/rotational")).ok())
+        .map(|flag| flag.trim() == "1")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_rotational(_path: &std::path::Path) -> Option<bool> {
+    None
+}
+
+/// Niceness of a low-priority scan: the lowest CPU priority there is.
+#[cfg(unix)]
+const LOW_PRIORITY_NICE: libc::c_int = 19;
+
+/// Drop to the lowest CPU priority and, on Linux, the idle I/O class, so a
+/// big scan yields the disk to everything else. Call it before starting the
+/// scan's threads: on Linux both are per-thread settings that only threads
+/// created afterwards inherit.
+#[cfg(target_os = "linux")]
+pub fn lower_priority() -> std::io::Result<()> {
+    // ioprio_set(2) has no libc wrapper
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+
+    set_nice(LOW_PRIORITY_NICE)?;
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_ioprio_set,
+            IOPRIO_WHO_PROCESS,
+            0,
+            IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+        )
+    };
+    if ret == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Drop the whole process to the lowest CPU priority, which macOS also uses
+/// to throttle its disk I/O.
+#[cfg(target_os = "macos")]
+pub fn lower_priority() -> std::io::Result<()> {
+    set_nice(LOW_PRIORITY_NICE)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn lower_priority() -> std::io::Result<()> {
+    Err(std::io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn set_nice(nice: libc::c_int) -> std::io::Result<()> {
+    // Who 0 is the caller: the current thread on Linux, the process elsewhere
+    if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } == -1 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct DriveInfo {
     pub path: String,
@@ -32,47 +643,73 @@ pub struct DriveInfo {
     pub free: u64,
 }
 
-/// Detect available drives/volumes on the current platform.
-#[cfg(windows)]
+/// Reorder `drives` (as returned by [`detect_drives`]) by `by`. The sort is
+/// stable, so ties keep their detected order.
+pub fn sort_drives(drives: &mut [DriveInfo], by: crate::config::DriveSort) {
+    use crate::config::DriveSort;
+    use std::cmp::Reverse;
+
+    match by {
+        DriveSort::Detected => {}
+        DriveSort::Free => drives.sort_by_key(|d| Reverse(d.free)),
+        DriveSort::Total => drives.sort_by_key(|d| Reverse(d.total)),
+        DriveSort::Used => drives.sort_by(|a, b| {
+            let used = |d: &DriveInfo| percent(d.total.saturating_sub(d.free), d.total);
+            used(b).total_cmp(&used(a))
+        }),
+        DriveSort::Path => drives.sort_by_key(|d| d.path.to_lowercase()),
+    }
+}
+
+/// Detect available drives/volumes and their space. Each volume's space is
+/// asked for in turn, so one slow network volume holds up the whole list;
+/// [`detect_drive_paths`] and [`drive_info`] let a caller show the paths
+/// first and fill in the space as it comes.
+#[cfg(any(windows, target_os = "macos", target_os = "linux"))]
 pub fn detect_drives() -> Vec<DriveInfo> {
+    detect_drive_paths().iter().filter_map(|path| drive_info(path)).collect()
+}
+
+/// The drive roots (`C:\`) on this machine, without asking any of them for
+/// their space.
+#[cfg(windows)]
+pub fn detect_drive_paths() -> Vec<String> {
+    let mask = unsafe { windows_get_logical_drives() };
+    (0..26u32)
+        .filter(|i| mask & (1 << i) != 0)
+        .map(|i| format!("{}:\\", (b'A' + i as u8) as char))
+        .collect()
+}
+
+/// Total and free space of the drive at `path`, or `None` when it has none to
+/// report (an empty card reader). Blocks for as long as the drive takes to
+/// answer, which for a network drive can be a long time.
+#[cfg(windows)]
+pub fn drive_info(path: &str) -> Option<DriveInfo> {
     use std::ffi::OsString;
     use std::os::windows::ffi::OsStrExt;
 
-    let mask = unsafe { windows_get_logical_drives() };
-    let mut drives = Vec::new();
-
-    for i in 0..26u32 {
-        if mask & (1 << i) != 0 {
-            let letter = (b'A' + i as u8) as char;
-            let root = format!("{}:\\", letter);
-
-            let wide: Vec<u16> = OsString::from(&root)
-                .encode_wide()
-                .chain(std::iter::once(0))
-                .collect();
-
-            let mut free_bytes: u64 = 0;
-            let mut total_bytes: u64 = 0;
-            let ok = unsafe {
-                GetDiskFreeSpaceExW(
-                    wide.as_ptr(),
-                    std::ptr::null_mut(),
-                    &mut total_bytes,
-                    &mut free_bytes,
-                )
-            };
-
-            if ok != 0 {
-                drives.push(DriveInfo {
-                    path: root,
-                    total: total_bytes,
-                    free: free_bytes,
-                });
-            }
-        }
-    }
+    let wide: Vec<u16> = OsString::from(path)
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
 
-    drives
+    let mut free_bytes: u64 = 0;
+    let mut total_bytes: u64 = 0;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            std::ptr::null_mut(),
+            &mut total_bytes,
+            &mut free_bytes,
+        )
+    };
+
+    (ok != 0).then(|| DriveInfo {
+        path: path.to_string(),
+        total: total_bytes,
+        free: free_bytes,
+    })
 }
 
 #[cfg(windows)]
@@ -84,6 +721,31 @@ extern "system" {
         lpTotalNumberOfBytes: *mut u64,
         lpTotalNumberOfFreeBytes: *mut u64,
     ) -> i32;
+    fn GetCurrentProcess() -> *mut std::ffi::c_void;
+    // The FILETIMEs are taken as u64s: same layout, little-endian
+    fn GetProcessTimes(
+        hProcess: *mut std::ffi::c_void,
+        lpCreationTime: *mut u64,
+        lpExitTime: *mut u64,
+        lpKernelTime: *mut u64,
+        lpUserTime: *mut u64,
+    ) -> i32;
+    fn K32GetProcessMemoryInfo(
+        Process: *mut std::ffi::c_void,
+        ppsmemCounters: *mut ProcessMemoryCounters,
+        cb: u32,
+    ) -> i32;
+}
+
+/// `PROCESS_MEMORY_COUNTERS`, with the fields after the one we need lumped
+/// together.
+#[cfg(windows)]
+#[repr(C)]
+struct ProcessMemoryCounters {
+    cb: u32,
+    _page_fault_count: u32,
+    peak_working_set_size: usize,
+    _rest: [usize; 7],
 }
 
 #[cfg(windows)]
@@ -91,22 +753,18 @@ unsafe fn windows_get_logical_drives() -> u32 {
     unsafe { GetLogicalDrives() }
 }
 
-/// Detect mounted volumes on macOS.
+/// The root and the volumes mounted under `/Volumes` on macOS, without
+/// asking any of them for their space.
 #[cfg(target_os = "macos")]
-pub fn detect_drives() -> Vec<DriveInfo> {
-    let mut drives = Vec::new();
-
+pub fn detect_drive_paths() -> Vec<String> {
     // Always include root
-    if let Some(info) = statvfs_drive("/") {
-        drives.push(info);
-    }
+    let mut paths = vec!["/".to_string()];
 
     // Enumerate /Volumes
     if let Ok(entries) = std::fs::read_dir("/Volumes") {
         for entry in entries {
             let Ok(entry) = entry else { continue };
             let path = entry.path();
-            let path_str = path.to_string_lossy().to_string();
 
             // Skip symlinks that point back to root
             if let Ok(target) = std::fs::read_link(&path) {
@@ -115,65 +773,321 @@ pub fn detect_drives() -> Vec<DriveInfo> {
                 }
             }
 
-            if let Some(info) = statvfs_drive(&path_str) {
-                // Avoid duplicate of root
-                if info.total == drives.first().map(|d| d.total).unwrap_or(0)
-                    && info.free == drives.first().map(|d| d.free).unwrap_or(0)
-                {
-                    continue;
-                }
-                drives.push(info);
-            }
+            paths.push(path.to_string_lossy().to_string());
         }
     }
 
-    drives
+    paths
 }
 
-/// Detect mounted filesystems on Linux.
+/// Total and free space of the volume mounted at `path`, or `None` when it
+/// can't be read or is the root volume seen again. Blocks for as long as the
+/// volume takes to answer, which for a network volume can be a long time.
+#[cfg(target_os = "macos")]
+pub fn drive_info(path: &str) -> Option<DriveInfo> {
+    let info = statvfs_drive(path)?;
+    if path != "/" {
+        // Avoid duplicate of root
+        let root = statvfs_drive("/");
+        if root.is_some_and(|root| root.total == info.total && root.free == info.free) {
+            return None;
+        }
+    }
+    Some(info)
+}
+
+/// Mount points of the block devices on Linux, each volume once (see
+/// [`dedupe_mounts`]), without asking any of them for their space.
 #[cfg(target_os = "linux")]
-pub fn detect_drives() -> Vec<DriveInfo> {
-    let mut drives = Vec::new();
-    let mut seen_devs = std::collections::HashSet::new();
+pub fn detect_drive_paths() -> Vec<String> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mut paths = Vec::new();
 
     if let Ok(content) = std::fs::read_to_string("/proc/mounts") {
-        for line in content.lines() {
-            let parts: Vec<&str> = line.split_whitespace().collect();
-            if parts.len() < 2 {
-                continue;
-            }
-            let device = parts[0];
-            let mount_point = parts[1];
+        let mounts = block_device_mounts(&content);
+        let dev_of = |path: &std::path::Path| std::fs::metadata(path).ok().map(|m| m.dev());
+        paths = dedupe_mounts(mounts, dev_of)
+            .iter()
+            .map(|mount_point| mount_point.to_string_lossy().to_string())
+            .collect();
+    }
 
-            // Only real block devices
-            if !device.starts_with("/dev/") {
-                continue;
-            }
-            // Skip snap/loop
-            if device.contains("loop") {
-                continue;
-            }
-            if seen_devs.contains(device) {
-                continue;
-            }
-            seen_devs.insert(device.to_string());
+    // Fallback: at least show root
+    if paths.is_empty() {
+        paths.push("/".to_string());
+    }
+
+    paths
+}
+
+/// Total and free space of the volume mounted at `path`, or `None` when it
+/// can't be read or is empty. Blocks for as long as the volume takes to
+/// answer.
+#[cfg(target_os = "linux")]
+pub fn drive_info(path: &str) -> Option<DriveInfo> {
+    statvfs_drive(path).filter(|info| info.total > 0)
+}
 
-            if let Some(info) = statvfs_drive(mount_point) {
-                if info.total > 0 {
-                    drives.push(info);
+/// The mounts of real block devices in `content`, a `/proc/mounts` listing,
+/// as (device, mount point) in listed order. Pseudo filesystems, overlays
+/// and loop devices (snaps, mounted images) are left out.
+#[cfg(target_os = "linux")]
+fn block_device_mounts(content: &str) -> Vec<(&str, std::path::PathBuf)> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let (device, mount_point) = (fields.next()?, fields.next()?);
+            (device.starts_with("/dev/") && !device.contains("loop"))
+                .then(|| (device, unescape_mount_path(mount_point)))
+        })
+        .collect()
+}
+
+/// One mount point per volume out of `mounts` (as from
+/// [`block_device_mounts`]). Bind mounts, and the same device mounted twice,
+/// show the same space again; mounts count as one when they share a device
+/// or `dev_of` (the `st_dev` of the mount point) says they're on the same
+/// filesystem. The shortest mount point of each stands for it, in the order
+/// the volumes were first listed.
+#[cfg(target_os = "linux")]
+fn dedupe_mounts(
+    mounts: Vec<(&str, std::path::PathBuf)>,
+    dev_of: impl Fn(&std::path::Path) -> Option<u64>,
+) -> Vec<std::path::PathBuf> {
+    // Each volume kept so far: its devices, `st_dev`s and best mount point
+    let mut volumes: Vec<(Vec<&str>, Vec<u64>, std::path::PathBuf)> = Vec::new();
+    for (device, path) in mounts {
+        let dev = dev_of(&path);
+        let same = volumes.iter_mut().find(|(devices, devs, _)| {
+            devices.contains(&device) || dev.is_some_and(|d| devs.contains(&d))
+        });
+        match same {
+            Some((devices, devs, best)) => {
+                devices.push(device);
+                devs.extend(dev);
+                let length = |p: &std::path::Path| (p.components().count(), p.as_os_str().len());
+                if length(&path) < length(best) {
+                    *best = path;
                 }
             }
+            None => volumes.push((vec![device], dev.into_iter().collect(), path)),
         }
     }
+    volumes.into_iter().map(|(_, _, path)| path).collect()
+}
 
-    // Fallback: at least show root
-    if drives.is_empty() {
-        if let Some(info) = statvfs_drive("/") {
-            drives.push(info);
+/// Bytes in use on the volume holding `path`, counting space reserved for the
+/// superuser as free: what a complete scan of the whole volume would come to
+/// on disk, less filesystem metadata. [`DriveInfo::free`] leaves the reserve
+/// out, which on Linux overstates use by a few percent.
+#[cfg(unix)]
+pub fn volume_usage(path: &std::path::Path) -> Option<u64> {
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    let ret = unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) };
+    if ret != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    // Block counts are only 32 bits on some targets
+    #[allow(clippy::unnecessary_cast)]
+    let (blocks, free, block) = (stat.f_blocks as u64, stat.f_bfree as u64, stat.f_frsize as u64);
+    Some(blocks.saturating_sub(free) * block)
+}
+
+#[cfg(windows)]
+pub fn volume_usage(path: &std::path::Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+
+    let wide: Vec<u16> = path
+        .as_os_str()
+        .encode_wide()
+        .chain(std::iter::once(0))
+        .collect();
+    let mut total_bytes: u64 = 0;
+    let mut free_bytes: u64 = 0;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(
+            wide.as_ptr(),
+            std::ptr::null_mut(),
+            &mut total_bytes,
+            &mut free_bytes,
+        )
+    };
+    (ok != 0).then(|| total_bytes.saturating_sub(free_bytes))
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn volume_usage(_path: &std::path::Path) -> Option<u64> {
+    None
+}
+
+/// Space on the volume holding `path` that macOS counts as used but can
+/// reclaim on demand (local Time Machine snapshots, iCloud files kept
+/// offline, caches): Finder's "Available" less what `statvfs` calls free.
+/// A scan can't see it, so it's the part of a whole drive's used space that
+/// [`volume_usage`] counts and the tree doesn't explain. `None` elsewhere.
+#[cfg(target_os = "macos")]
+pub fn purgeable_space(path: &std::path::Path) -> Option<u64> {
+    use std::ffi::c_void;
+    use std::mem::MaybeUninit;
+    use std::os::unix::ffi::OsStrExt;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        static kCFURLVolumeAvailableCapacityForImportantUsageKey: *const c_void;
+        fn CFURLCreateFromFileSystemRepresentation(
+            allocator: *const c_void,
+            buffer: *const u8,
+            len: isize,
+            is_directory: u8,
+        ) -> *const c_void;
+        fn CFURLCopyResourcePropertyForKey(
+            url: *const c_void,
+            key: *const c_void,
+            value: *mut *const c_void,
+            error: *mut *const c_void,
+        ) -> u8;
+        fn CFNumberGetValue(number: *const c_void, the_type: isize, value: *mut c_void) -> u8;
+        fn CFRelease(cf: *const c_void);
+    }
+    const K_CF_NUMBER_SINT64_TYPE: isize = 4;
+
+    let bytes = path.as_os_str().as_bytes();
+    let len = isize::try_from(bytes.len()).ok()?;
+    let url = unsafe {
+        CFURLCreateFromFileSystemRepresentation(std::ptr::null(), bytes.as_ptr(), len, 1)
+    };
+    if url.is_null() {
+        return None;
+    }
+    let mut number: *const c_void = std::ptr::null();
+    let found = unsafe {
+        let key = kCFURLVolumeAvailableCapacityForImportantUsageKey;
+        let found = CFURLCopyResourcePropertyForKey(url, key, &mut number, std::ptr::null_mut());
+        CFRelease(url);
+        found != 0 && !number.is_null()
+    };
+    if !found {
+        return None;
+    }
+    let mut available: i64 = 0;
+    let read = unsafe {
+        let read = CFNumberGetValue(
+            number,
+            K_CF_NUMBER_SINT64_TYPE,
+            (&mut available as *mut i64).cast(),
+        );
+        CFRelease(number);
+        read != 0
+    };
+    if !read {
+        return None;
+    }
+
+    let c_path = std::ffi::CString::new(bytes).ok()?;
+    let mut stat = MaybeUninit::<libc::statvfs>::uninit();
+    if unsafe { libc::statvfs(c_path.as_ptr(), stat.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stat = unsafe { stat.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    let free = stat.f_bavail as u64 * stat.f_frsize as u64;
+    Some(u64::try_from(available).ok()?.saturating_sub(free))
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn purgeable_space(_path: &std::path::Path) -> Option<u64> {
+    None
+}
+
+/// CPU time and memory this process has used so far; see
+/// [`resource_usage`].
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceUsage {
+    /// Time spent running the process's own code.
+    pub user: std::time::Duration,
+    /// Time the kernel spent on its behalf. For a scan that's mostly reading
+    /// directories and file metadata, so a scan that is mostly waiting on
+    /// the disk shows little of either.
+    pub system: std::time::Duration,
+    /// Largest resident set size so far, in bytes.
+    pub peak_rss: u64,
+}
+
+impl ResourceUsage {
+    /// User and system time together.
+    pub fn cpu(&self) -> std::time::Duration {
+        self.user + self.system
+    }
+
+    /// The CPU time used between `earlier` and this reading, with this
+    /// reading's peak RSS.
+    pub fn since(&self, earlier: &ResourceUsage) -> ResourceUsage {
+        ResourceUsage {
+            user: self.user.saturating_sub(earlier.user),
+            system: self.system.saturating_sub(earlier.system),
+            peak_rss: self.peak_rss,
         }
     }
+}
+
+/// This process's CPU time and peak memory so far, from `getrusage`
+/// (`GetProcessTimes` on Windows). Set against the wall time of a scan, the
+/// CPU time tells whether it was held up by the disk or by processing.
+#[cfg(unix)]
+pub fn resource_usage() -> Option<ResourceUsage> {
+    use std::mem::MaybeUninit;
+    use std::time::Duration;
+
+    let mut usage = MaybeUninit::<libc::rusage>::uninit();
+    if unsafe { libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let usage = unsafe { usage.assume_init() };
+    let time = |t: libc::timeval| Duration::new(t.tv_sec as u64, t.tv_usec as u32 * 1000);
+    // ru_maxrss is in bytes on macOS and kilobytes elsewhere
+    let unit = if cfg!(target_os = "macos") { 1 } else { 1024 };
+    Some(ResourceUsage {
+        user: time(usage.ru_utime),
+        system: time(usage.ru_stime),
+        peak_rss: usage.ru_maxrss as u64 * unit,
+    })
+}
+
+#[cfg(windows)]
+pub fn resource_usage() -> Option<ResourceUsage> {
+    use std::time::Duration;
+
+    let process = unsafe { GetCurrentProcess() };
+    let (mut created, mut exited, mut kernel, mut user) = (0u64, 0u64, 0u64, 0u64);
+    let ok = unsafe {
+        GetProcessTimes(process, &mut created, &mut exited, &mut kernel, &mut user)
+    };
+    if ok == 0 {
+        return None;
+    }
+    let mut counters: ProcessMemoryCounters = unsafe { std::mem::zeroed() };
+    counters.cb = std::mem::size_of::<ProcessMemoryCounters>() as u32;
+    let ok = unsafe { K32GetProcessMemoryInfo(process, &mut counters, counters.cb) };
+    // Process times count 100ns ticks
+    let time = |ticks: u64| Duration::from_nanos(ticks.saturating_mul(100));
+    Some(ResourceUsage {
+        user: time(user),
+        system: time(kernel),
+        peak_rss: if ok != 0 { counters.peak_working_set_size as u64 } else { 0 },
+    })
+}
 
-    drives
+#[cfg(not(any(unix, windows)))]
+pub fn resource_usage() -> Option<ResourceUsage> {
+    None
 }
 
 /// Use statvfs to get total/free bytes for a mount point.
@@ -200,3 +1114,127 @@ fn statvfs_drive(path: &str) -> Option<DriveInfo> {
         free,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn drive_root_letter_only_matches_whole_drives() {
+        assert_eq!(drive_root_letter(Path::new(r"C:\")), Some('C'));
+        assert_eq!(drive_root_letter(Path::new("d:/")), Some('d'));
+        assert_eq!(drive_root_letter(Path::new("E:")), Some('E'));
+        assert_eq!(drive_root_letter(Path::new(r"C:\Users")), None);
+        assert_eq!(drive_root_letter(Path::new(r"\\?\C:\")), None);
+        assert_eq!(drive_root_letter(Path::new("/")), None);
+        assert_eq!(drive_root_letter(Path::new("1:")), None);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn bind_mounts_count_once() {
+        let proc_mounts = "\
+/dev/mapper/root /home/me/Data\\040Store ext4 rw 0 0
+proc /proc proc rw 0 0
+/dev/nvme0n1p2 /boot ext4 rw 0 0
+/dev/dm-0 / ext4 rw 0 0
+tmpfs /tmp tmpfs rw 0 0
+/dev/loop3 /snap/core/1 squashfs ro 0 0
+/dev/nvme0n1p2 /srv/boot-copy ext4 rw 0 0
+/dev/sdb1 /mnt/usb vfat rw 0 0
+";
+        let mounts = block_device_mounts(proc_mounts);
+        assert_eq!(mounts.len(), 5);
+        assert_eq!(mounts[0].1, Path::new("/home/me/Data Store"));
+
+        // `/dev/mapper/root` and `/dev/dm-0` name the same device; only
+        // `st_dev` can tell
+        let dev_of = |path: &Path| match path.to_str()? {
+            "/" | "/home/me/Data Store" => Some(1),
+            "/boot" | "/srv/boot-copy" => Some(2),
+            _ => None,
+        };
+        let volumes = dedupe_mounts(mounts, dev_of);
+        assert_eq!(volumes, [Path::new("/"), Path::new("/boot"), Path::new("/mnt/usb")]);
+    }
+
+    #[test]
+    fn durations_switch_units_at_the_boundaries() {
+        assert_eq!(format_duration(0.0), "0ms");
+        assert_eq!(format_duration(0.35), "350ms");
+        assert_eq!(format_duration(0.9994), "999ms");
+        assert_eq!(format_duration(0.9995), "1.0s");
+        assert_eq!(format_duration(59.94), "59.9s");
+        assert_eq!(format_duration(59.95), "1m 0s");
+        assert_eq!(format_duration(252.0), "4m 12s");
+        assert_eq!(format_duration(3599.4), "59m 59s");
+        assert_eq!(format_duration(3599.5), "1h 0m 0s");
+        assert_eq!(format_duration(4984.0), "1h 23m 4s");
+        assert_eq!(format_duration(-1.0), "0ms");
+        assert_eq!(format_duration(f64::NAN), "0ms");
+        assert_eq!(format_duration(f64::INFINITY), "0ms");
+    }
+
+    #[test]
+    fn root_names() {
+        let name = |root: &str| root_name(Path::new(root));
+        assert_eq!(name(r"C:\"), r"C:\");
+        assert_eq!(name("."), ".");
+        assert_eq!(name(""), ".");
+        // Only canonical_root expands `~`; root_name takes it as a name
+        assert_eq!(name("~"), "~");
+        assert_eq!(name("~/"), "~");
+        #[cfg(unix)]
+        {
+            assert_eq!(name("/"), "/");
+            assert_eq!(name("/data/"), "/data");
+            assert_eq!(name("/data/."), "/data");
+        }
+    }
+
+    #[test]
+    fn percent_of_an_empty_directory_is_zero() {
+        use crate::tree::FileNode;
+        let mut dir = FileNode::new_dir("empty");
+        dir.children = vec![FileNode::new_file("a", 0), FileNode::new_file("b", 0)];
+        dir.update_totals();
+        for child in &dir.children {
+            let pct = percent(child.size, dir.size);
+            assert_eq!(pct, 0.0);
+            assert!(pct.is_finite());
+        }
+        assert_eq!(percent(0, 0), 0.0);
+        assert_eq!(percent(5, 0), 0.0);
+        assert_eq!(percent(150, 100), 100.0);
+        assert_eq!(percent(1, 4), 25.0);
+    }
+
+    #[test]
+    fn utf16_names_decode_surrogate_pairs_and_keep_unpaired_ones() {
+        let emoji: Vec<u16> = "photo 📷.jpg".encode_utf16().collect();
+        assert_eq!(decode_utf16_name(&emoji), ("photo 📷.jpg".to_string(), None));
+
+        // A high surrogate with no low one after it, then a lone low one
+        let broken = [0x61, 0xD83D, 0x62, 0xDCF7];
+        let (name, units) = decode_utf16_name(&broken);
+        assert_eq!(name, "a\u{FFFD}b\u{FFFD}");
+        assert_eq!(units.as_deref(), Some(&broken[..]));
+    }
+
+    #[test]
+    fn os_name_keeps_the_original_utf16_units() {
+        let broken = [0x61, 0xD800];
+        let (name, units) = decode_utf16_name(&broken);
+        let mut node = crate::tree::FileNode::new_file(name, 1);
+        node.utf16_name = units;
+        #[cfg(windows)]
+        {
+            use std::os::windows::ffi::OsStrExt;
+            let wide: Vec<u16> = node.os_name().encode_wide().collect();
+            assert_eq!(wide, broken);
+        }
+        #[cfg(not(windows))]
+        assert_eq!(node.os_name(), std::ffi::OsStr::new("a\u{FFFD}"));
+    }
+}
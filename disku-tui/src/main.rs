@@ -1,25 +1,121 @@
+mod suspend;
+mod theme;
+mod treemap;
 mod ui;
 
-use std::io;
-use std::path::PathBuf;
+use std::collections::{HashSet, VecDeque};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::Ordering;
+use std::sync::{mpsc, Arc};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{
+    self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEventKind, MouseButton,
+    MouseEventKind,
+};
 use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::execute;
 use ratatui::backend::CrosstermBackend;
 use ratatui::Terminal;
+use serde::Serialize;
 
-use disku_core::scanner::{scan, ScanProgress};
-use disku_core::tree::FileNode;
-use ui::{draw, draw_drive_picker, draw_scanning, draw_start_screen, App};
-use disku_core::utils::detect_drives;
+use disku_core::budget::count_unexpanded;
+use disku_core::checkpoint::scan_root_resumable;
+use disku_core::config::{self, Config, DriveSort, Palette, SortKey, MAX_PERCENT_DECIMALS};
+use disku_core::delete::{DeleteMode, TRASH_SUPPORTED};
+use disku_core::exclude::{read_patterns, ExcludeSet};
+use disku_core::progress_channel::{ChannelProgress, ProgressUpdate};
+use disku_core::progress_log::{ProgressLog, LOG_INTERVAL};
+use disku_core::scanner::{scan_root, ProgressSink, ScanOptions, ScanProgress};
+use disku_core::snapshot::{load_snapshot, save_snapshot};
+use disku_core::tree::{
+    count_entries, dirs_only, export_folded, print_ascii, print_compare, print_top, size_breakdown,
+    FileNode, ScanResult,
+};
+use disku_core::treemap::export_treemap_svg;
+use theme::Theme;
+use ui::{
+    draw, draw_drive_picker, draw_scan_preview, draw_scanning, draw_start_screen, scan_summary,
+    strip_colors, App, ScanDebug,
+};
+use disku_core::utils::{
+    canonical_root, detect_drive_paths, detect_drives, drive_info, format_duration, lower_priority,
+    purgeable_space, resource_usage, sort_drives, volume_usage, DriveInfo, ResourceUsage,
+};
 
 fn main() -> io::Result<()> {
+    let config = Config::load().unwrap_or_else(|e| {
+        eprintln!("warning: ignoring config file: {}", e);
+        Config::default()
+    });
+    let args = parse_args(&config);
+    let options = args.options.clone();
+    // Before any scan thread exists, so they all inherit it
+    if args.low_priority {
+        if let Err(e) = lower_priority() {
+            eprintln!("warning: couldn't lower scan priority: {}", e);
+        }
+    }
+
+    let loaded = args.load.as_deref().map(|snap| {
+        let scan = load_snapshot(snap).unwrap_or_else(|e| {
+            eprintln!("error: loading {}: {}", snap.display(), e);
+            std::process::exit(1);
+        });
+        if args.refresh {
+            refresh_scan(scan, &args)
+        } else {
+            scan
+        }
+    });
+
+    if args.json {
+        return print_json(loaded, &args);
+    }
+
+    if let Some(other) = &args.compare {
+        return print_comparison(other, &args);
+    }
+
+    if args.output_tree
+        || args.top.is_some()
+        || args.treemap_svg.is_some()
+        || args.folded.is_some()
+    {
+        let mut scan = match (loaded, args.ssh.as_deref()) {
+            (Some(scan), _) => scan,
+            (None, Some(target)) => {
+                let progress = ScanProgress::new();
+                let _log = start_log(&args, &progress);
+                scan_remote_for_report(target, &args, &progress, &progress)
+            }
+            (None, None) => {
+                let Some(path) = args.path.clone() else {
+                    let flag = if args.output_tree {
+                        "--output-tree"
+                    } else if args.top.is_some() {
+                        "--top"
+                    } else if args.treemap_svg.is_some() {
+                        "--treemap-svg"
+                    } else {
+                        "--folded"
+                    };
+                    eprintln!("error: {} requires a PATH", flag);
+                    std::process::exit(1);
+                };
+                scan_for_report(path, &args)
+            }
+        };
+        if args.dirs_only {
+            dirs_only(&mut scan.tree);
+        }
+        return print_report(&scan, &args);
+    }
+
     // If a path was passed as CLI arg, use it directly
-    let explicit_path = std::env::args().nth(1).map(PathBuf::from);
+    let explicit_path = args.path.clone();
 
     // Set up terminal
     enable_raw_mode()?;
@@ -27,10 +123,59 @@ fn main() -> io::Result<()> {
     execute!(stdout, EnterAlternateScreen)?;
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
+    let color = args.color;
+    suspend::install();
+
+    if let Some(scan) = loaded {
+        let app = new_app(scan, None, &args, &config);
+        return run_app(&mut terminal, app).map(drop);
+    }
+
+    if let Some(target) = args.ssh.clone() {
+        let progress = ScanProgress::new();
+        let p = progress.clone();
+        let options = args.options.clone();
+        let log = start_log(&args, &progress);
+        let started = Instant::now();
+        let usage = resource_usage();
+        let scan_handle = thread::spawn(move || scan_remote(&target, &p, &options));
+        if !wait_for_scan(&mut terminal, &progress, &scan_handle, args.color, args.debug)? {
+            return cleanup_terminal();
+        }
+        let scan_secs = started.elapsed().as_secs_f64();
+        let scan_cpu_secs = usage_since(usage).map(|u| u.cpu().as_secs_f64());
+        let scan = match scan_handle.join().expect("scan thread panicked") {
+            Ok(scan) => scan,
+            Err(e) => {
+                cleanup_terminal()?;
+                eprintln!("error: {}", e);
+                std::process::exit(1);
+            }
+        };
+        drop(log);
+        let notice = args.save.as_ref().and_then(|out| {
+            save_snapshot(&scan, out)
+                .err()
+                .map(|e| format!("snapshot not saved: {}", e))
+        });
+        let mut app = new_app(scan, notice, &args, &config);
+        app.scan_secs = Some(scan_secs);
+        app.scan_cpu_secs = scan_cpu_secs;
+        app.excluded = progress.excluded.load(Ordering::Relaxed);
+        app.cycles = progress.cycles_detected.load(Ordering::Relaxed);
+        app.errors = progress.errors.load(Ordering::Relaxed);
+        // file:// links would point at this machine, not the remote one, and
+        // the same goes for anything we might delete
+        app.hyperlinks = false;
+        app.read_only = Some("remote scan");
+        return run_app(&mut terminal, app).map(drop);
+    }
 
     // Determine root path: either from CLI arg, or start screen -> drive picker
-    let root_path = if let Some(path) = explicit_path {
-        path.canonicalize().unwrap_or(path)
+    let (root_path, drive) = if let Some(path) = explicit_path {
+        let root_path = canonical_root(&path);
+        let drive = drive_at(&root_path);
+        (root_path, drive)
     } else {
         // Show start screen
         let menu_items = if cfg!(windows) {
@@ -43,7 +188,10 @@ fn main() -> io::Result<()> {
         let menu_choice = loop {
             let sel = menu_sel;
             let items = &menu_items;
-            terminal.draw(|f| draw_start_screen(f, sel, items))?;
+            terminal.draw(|f| {
+                draw_start_screen(f, sel, items);
+                finish_frame(f, color);
+            })?;
 
             if event::poll(Duration::from_millis(50))? {
                 if let Event::Key(key) = event::read()? {
@@ -76,19 +224,65 @@ fn main() -> io::Result<()> {
 
         match menu_choice {
             0 => {
-                // Scan Drive/Volume -- show drive picker
-                let drives = detect_drives();
-                if drives.is_empty() {
-                    cleanup_terminal()?;
-                    eprintln!("No drives found.");
-                    return Ok(());
+                // Scan Drive/Volume -- show drive picker. Each drive's
+                // space is asked for on a thread of its own, so the picker
+                // shows at once and a hung network volume only holds up its
+                // own row; rows show a spinner until theirs answers.
+                let paths = detect_drive_paths();
+                let (info_tx, info_rx) = mpsc::channel();
+                for path in &paths {
+                    let tx = info_tx.clone();
+                    let path = path.clone();
+                    thread::spawn(move || {
+                        let info = drive_info(&path);
+                        let _ = tx.send((path, info));
+                    });
                 }
+                let mut pending: HashSet<String> = paths.iter().cloned().collect();
+                let mut detected: Vec<DriveInfo> = paths
+                    .into_iter()
+                    .map(|path| DriveInfo {
+                        path,
+                        total: 0,
+                        free: 0,
+                    })
+                    .collect();
+                let mut sort = config.ui.drive_sort;
+                let (mut drives, mut selected) = sorted_drives(&detected, sort, None);
+                let opened = Instant::now();
 
-                let mut selected: usize = 0;
                 let chosen = loop {
+                    let mut answered = false;
+                    for (path, info) in info_rx.try_iter() {
+                        pending.remove(&path);
+                        match info {
+                            Some(info) => {
+                                if let Some(drive) = detected.iter_mut().find(|d| d.path == path) {
+                                    *drive = info;
+                                }
+                            }
+                            None => detected.retain(|d| d.path != path),
+                        }
+                        answered = true;
+                    }
+                    if answered {
+                        let current = drives.get(selected).map(|d| d.path.clone());
+                        (drives, selected) = sorted_drives(&detected, sort, current.as_deref());
+                    }
+                    if drives.is_empty() {
+                        cleanup_terminal()?;
+                        eprintln!("No drives found.");
+                        return Ok(());
+                    }
+
                     let drives_ref = &drives;
+                    let pending_ref = &pending;
                     let sel = selected;
-                    terminal.draw(|f| draw_drive_picker(f, drives_ref, sel))?;
+                    let spin = (opened.elapsed().as_millis() / 80) as usize;
+                    terminal.draw(|f| {
+                        draw_drive_picker(f, drives_ref, pending_ref, spin, sel, sort);
+                        finish_frame(f, color);
+                    })?;
 
                     if event::poll(Duration::from_millis(50))? {
                         if let Event::Key(key) = event::read()? {
@@ -110,23 +304,37 @@ fn main() -> io::Result<()> {
                                         selected += 1;
                                     }
                                 }
+                                KeyCode::Char('s') => {
+                                    let current = drives[selected].path.clone();
+                                    sort = sort.next();
+                                    (drives, selected) =
+                                        sorted_drives(&detected, sort, Some(&current));
+                                    // Best effort: an unwritable config just
+                                    // means the choice isn't remembered
+                                    let _ = config::persist("ui", "drive_sort", sort.as_str());
+                                }
                                 KeyCode::Enter => {
-                                    break drives[selected].path.clone();
+                                    break drives.swap_remove(selected);
                                 }
                                 _ => {}
                             }
                         }
                     }
                 };
-                PathBuf::from(chosen)
+                // A drive still being asked for its space is scanned without
+                // knowing it
+                let known = !pending.contains(&chosen.path);
+                (canonical_root(Path::new(&chosen.path)), known.then_some(chosen))
             }
             1 => {
                 // Scan Directory -- prompt for path input
-                let mut input = String::new();
+                let mut input = ui::PathInput::default();
                 loop {
+                    input.poll();
                     let input_ref = &input;
                     terminal.draw(|f| {
                         ui::draw_path_input(f, input_ref);
+                        finish_frame(f, color);
                     })?;
 
                     if event::poll(Duration::from_millis(50))? {
@@ -140,25 +348,24 @@ fn main() -> io::Result<()> {
                                     return Ok(());
                                 }
                                 KeyCode::Enter => {
-                                    let p = PathBuf::from(&input);
-                                    if p.is_dir() {
+                                    // Accept directories and single files alike
+                                    let p = PathBuf::from(&input.text);
+                                    if p.exists() {
                                         break;
                                     }
                                     // If invalid, just keep looping
                                 }
-                                KeyCode::Backspace => {
-                                    input.pop();
-                                }
-                                KeyCode::Char(c) => {
-                                    input.push(c);
-                                }
+                                KeyCode::Tab => input.complete(),
+                                KeyCode::Backspace => input.pop(),
+                                KeyCode::Char(c) => input.push(c),
                                 _ => {}
                             }
                         }
                     }
                 }
-                let p = PathBuf::from(&input);
-                p.canonicalize().unwrap_or(p)
+                let root_path = canonical_root(Path::new(&input.text));
+                let drive = drive_at(&root_path);
+                (root_path, drive)
             }
             _ => {
                 // Quit
@@ -168,78 +375,383 @@ fn main() -> io::Result<()> {
         }
     };
 
-    // Scan in background thread
-    let progress = ScanProgress::new();
-    let scan_files = progress.files_scanned.clone();
-    let scan_errors = progress.errors.clone();
-    let scan_path = root_path.clone();
-
-    let scan_handle = thread::spawn(move || {
-        let p = ScanProgress {
-            files_scanned: scan_files,
-            dirs_scanned: progress.dirs_scanned.clone(),
-            errors: scan_errors,
-            current_path: progress.current_path.clone(),
+    // Scan in background thread. `R` in the browser comes back here for a
+    // fresh scan of the same root, which then opens where the last one was.
+    let mut drive = drive;
+    let mut previous: Option<App> = None;
+    loop {
+        let progress = ScanProgress::new().with_live_totals(&root_path);
+        let p = progress.clone();
+        let log = start_log(&args, &progress);
+        let scan_path = root_path.clone();
+        let options = options.clone();
+
+        // A rescan starts over rather than from the checkpoint
+        let resume = args.resume.clone().filter(|_| previous.is_none());
+        let time_limit = args.time_limit;
+
+        let usage = resource_usage();
+        let scan_handle = thread::spawn(move || match &resume {
+            Some(checkpoint) => scan_root_resumable(&scan_path, checkpoint, &p, &options),
+            None => Ok(scan_root(&scan_path, &p, &options, time_limit)),
+        });
+        if !wait_for_scan(&mut terminal, &progress, &scan_handle, args.color, args.debug)? {
+            return cleanup_terminal();
+        }
+        let scan_cpu_secs = usage_since(usage).map(|u| u.cpu().as_secs_f64());
+
+        let scan = match scan_handle.join().expect("scan thread panicked") {
+            Ok(scan) => scan,
+            Err(e) => {
+                cleanup_terminal()?;
+                eprintln!("error: resuming scan: {}", e);
+                std::process::exit(1);
+            }
         };
+        drop(log);
+        let (scan_secs, errors) = (scan.elapsed.as_secs_f64(), scan.errors);
 
-        // Platform-specific fast path, falling back to jwalk
-        #[cfg(windows)]
-        {
-            let path_str = scan_path.to_string_lossy();
-            if path_str.len() >= 2 && path_str.as_bytes()[1] == b':' {
-                let drive_letter = path_str.chars().next().unwrap();
-                if let Some(root) = disku_core::mft_scanner::scan_mft(drive_letter, &p) {
-                    return root;
-                }
+        let mut notice = match progress.fd_exhausted.load(Ordering::Relaxed) {
+            0 => scan.hint.map(str::to_string),
+            n => Some(format!("{} directories skipped: too many open files", n)),
+        };
+        if let Some(out) = &args.save {
+            if let Err(e) = save_snapshot(&scan, out) {
+                notice = Some(format!("snapshot not saved: {}", e));
             }
         }
 
-        #[cfg(target_os = "macos")]
-        {
-            return disku_core::mac_scanner::scan_bulk(&scan_path, &p);
+        let mut app = new_app(scan, notice, &args, &config);
+        app.scan_secs = Some(scan_secs);
+        app.scan_cpu_secs = scan_cpu_secs;
+        app.excluded = progress.excluded.load(Ordering::Relaxed);
+        app.cycles = progress.cycles_detected.load(Ordering::Relaxed);
+        app.errors = errors;
+        app.rescannable = true;
+        if let Some(previous) = &previous {
+            // Free space has changed if anything was deleted
+            drive = drive.and_then(|_| drive_at(&root_path));
+            app.restore_view(previous);
+        }
+        app.unaccounted = drive.as_ref().and_then(|_| unaccounted(&root_path, &app.scan.tree));
+        app.purgeable = drive.as_ref().and_then(|_| purgeable_space(&root_path));
+        app.drive = drive.clone();
+        previous = run_app(&mut terminal, app)?;
+        if previous.is_none() {
+            return Ok(());
         }
+    }
+}
 
-        // Universal fallback (Windows non-NTFS, Linux, etc.)
-        #[allow(unreachable_code)]
-        scan(&scan_path, &p)
-    });
+/// How much of the used space on the volume at `root` is missing from `tree`,
+/// a scan of it: the volume's usage less the tree's on-disk total and any
+/// purgeable space, which is reported on its own.
+fn unaccounted(root: &Path, tree: &FileNode) -> Option<u64> {
+    let purgeable = purgeable_space(root).unwrap_or(0);
+    volume_usage(root).map(|used| used.saturating_sub(tree.allocated).saturating_sub(purgeable))
+}
+
+/// The detected drive mounted at `root`, when a scan of `root` covers a
+/// whole drive.
+fn drive_at(root: &Path) -> Option<DriveInfo> {
+    detect_drive_paths()
+        .into_iter()
+        .find(|path| canonical_root(Path::new(path)) == root)
+        .and_then(|path| drive_info(&path))
+}
+
+/// `detected` sorted by `sort` for the drive picker, and the row the drive at
+/// `current` ended up on (the first when it's gone or not given).
+fn sorted_drives(
+    detected: &[DriveInfo],
+    sort: DriveSort,
+    current: Option<&str>,
+) -> (Vec<DriveInfo>, usize) {
+    let mut drives = detected.to_vec();
+    sort_drives(&mut drives, sort);
+    let selected = current
+        .and_then(|current| drives.iter().position(|d| d.path == current))
+        .unwrap_or(0);
+    (drives, selected)
+}
 
-    // Show scanning progress
+/// Show scanning progress until `scan_handle` finishes, with the scanner's
+/// queue depth when `debug` is set. Once the scan has found any bytes, that's
+/// a live preview of where they are, from `progress`'s live totals. Returns
+/// `false` if the user quit first.
+fn wait_for_scan<T>(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    progress: &ScanProgress,
+    scan_handle: &thread::JoinHandle<T>,
+    color: bool,
+    debug: bool,
+) -> io::Result<bool> {
+    let mut rate = RateMeter::default();
+    let mut watch = StallWatch::new();
+    let mut tick: usize = 0;
+    let mut scan_debug = debug.then(|| ScanDebug {
+        pending_dirs: 0,
+        peak_pending: 0,
+        // What rayon's global pool, which the scanners run on, defaults to
+        threads: thread::available_parallelism().map_or(1, |n| n.get()),
+    });
     loop {
         let files = progress.files_scanned.load(Ordering::Relaxed);
+        let dirs = progress.dirs_scanned.load(Ordering::Relaxed);
         let errors = progress.errors.load(Ordering::Relaxed);
+        let per_sec = rate.sample(files + dirs);
+        let stalled = watch.stalled(files + dirs);
+        let current = progress
+            .current_path
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone();
+        let preview = progress
+            .live
+            .as_ref()
+            .and_then(|live| live.lock().unwrap_or_else(|e| e.into_inner()).snapshot());
 
-        terminal.draw(|f| draw_scanning(f, files, errors))?;
+        if let Some(d) = &mut scan_debug {
+            d.pending_dirs = progress.pending_dirs.load(Ordering::Relaxed);
+            d.peak_pending = d.peak_pending.max(d.pending_dirs);
+        }
+
+        terminal.draw(|f| {
+            let debug = scan_debug.as_ref();
+            match &preview {
+                Some(preview) => {
+                    draw_scan_preview(f, preview, files, per_sec, &current, stalled, debug)
+                }
+                None => {
+                    let stalled_at = stalled.then_some(current.as_str());
+                    draw_scanning(f, files, errors, per_sec, stalled_at, tick, debug);
+                }
+            }
+            finish_frame(f, color);
+        })?;
+        tick = tick.wrapping_add(1);
 
         if scan_handle.is_finished() {
-            break;
+            return Ok(true);
         }
 
+        if suspend::requested(None) {
+            suspend::suspend(terminal, false)?;
+            continue;
+        }
         if event::poll(Duration::from_millis(100))? {
             if let Event::Key(key) = event::read()? {
-                if key.kind == KeyEventKind::Press
-                    && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
-                {
-                    cleanup_terminal()?;
-                    return Ok(());
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if suspend::requested(Some(&key)) {
+                    suspend::suspend(terminal, false)?;
+                } else if matches!(key.code, KeyCode::Char('q') | KeyCode::Esc) {
+                    return Ok(false);
                 }
             }
         }
     }
+}
+
+/// Entries per second over a short rolling window of progress samples.
+#[derive(Default)]
+struct RateMeter {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateMeter {
+    /// Long enough to smooth over bursty directories, short enough to show a
+    /// stall within a couple of seconds.
+    const WINDOW: Duration = Duration::from_secs(2);
+    /// Below this much history the rate is mostly noise.
+    const MIN_SPAN: Duration = Duration::from_millis(300);
+
+    /// Record the running `count` and return the current rate, once there is
+    /// enough history for one.
+    fn sample(&mut self, count: u64) -> Option<f64> {
+        let now = Instant::now();
+        self.samples.push_back((now, count));
+        while self
+            .samples
+            .front()
+            .is_some_and(|&(t, _)| now.duration_since(t) > Self::WINDOW)
+        {
+            self.samples.pop_front();
+        }
+        let &(first_t, first) = self.samples.front()?;
+        let span = now.duration_since(first_t);
+        (span >= Self::MIN_SPAN).then(|| count.saturating_sub(first) as f64 / span.as_secs_f64())
+    }
+}
 
-    let root: FileNode = scan_handle.join().expect("scan thread panicked");
+/// Notices when the entry count stops moving, as it does while a scanner
+/// waits on a hung network mount.
+struct StallWatch {
+    count: u64,
+    since: Instant,
+}
 
-    // Run the interactive TUI
-    let mut app = App::new(root);
+impl StallWatch {
+    /// Slow disks pause for a second or two; this long means something's stuck.
+    const AFTER: Duration = Duration::from_secs(10);
 
+    fn new() -> Self {
+        Self {
+            count: 0,
+            since: Instant::now(),
+        }
+    }
+
+    /// Record the running `count`; true once it hasn't moved for [`Self::AFTER`].
+    fn stalled(&mut self, count: u64) -> bool {
+        if count != self.count {
+            self.count = count;
+            self.since = Instant::now();
+        }
+        self.since.elapsed() >= Self::AFTER
+    }
+}
+
+/// Build the browser state for a finished scan, applying display preferences.
+fn new_app(mut scan: ScanResult, notice: Option<String>, args: &Args, config: &Config) -> App {
+    if args.dirs_only {
+        dirs_only(&mut scan.tree);
+    }
+    let note = unexpanded_note(&scan.tree)
+        .or_else(|| disku_core::tree::nothing_big(&scan.tree).map(str::to_string));
+    let mut app = App::new(scan);
+    app.notice = notice.or(note);
+    app.hyperlinks = args.hyperlinks.unwrap_or_else(terminal_supports_hyperlinks);
+    app.color = args.color;
+    app.delete_mode = DeleteMode::new(args.permanent);
+    if !args.permanent && !TRASH_SUPPORTED && app.notice.is_none() {
+        app.notice = Some("no Trash here: deletes are permanent".to_string());
+    }
+    app.cache_names = config.cleanup.cache_names.clone();
+    app.protected_paths = config.cleanup.protected_paths.clone();
+    app.allow_protected = args.allow_protected;
+    app.both_sizes = config.ui.both_sizes;
+    app.item_counts = config.ui.item_counts;
+    app.truncation = config.ui.truncate;
+    app.theme = Theme::new(args.palette);
+    app.cost_per_gb_month = args.cost_per_gb_month;
+    app.group_below = args.group_below;
+    app.percent_decimals = args.percent_decimals;
+    app.bookmarks = config.ui.bookmarks.clone();
+    if args.dirs_only {
+        app.read_only = Some("directories only");
+    }
+    if config.ui.sort != SortKey::Size {
+        app.set_sort(config.ui.sort);
+    }
+    app
+}
+
+/// Run the interactive browser until the user quits, then restore the
+/// terminal. If they asked for a rescan (`R`) instead, the terminal is left
+/// as it is and the app is handed back, to have its view carried over.
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    mut app: App,
+) -> io::Result<Option<App>> {
     loop {
-        terminal.draw(|f| draw(f, &mut app))?;
+        terminal.draw(|f| {
+            draw(f, &mut app);
+            finish_frame(f, app.color);
+        })?;
 
+        if suspend::requested(None) {
+            suspend::suspend(terminal, app.treemap)?;
+            continue;
+        }
         if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
+            let ev = event::read()?;
+            if let Event::Mouse(mouse) = ev {
+                if app.treemap && mouse.kind == MouseEventKind::Down(MouseButton::Left) {
+                    app.click_treemap(mouse.column, mouse.row);
+                }
+                continue;
+            }
+            if let Event::Key(key) = ev {
                 if key.kind != KeyEventKind::Press {
                     continue;
                 }
+                if suspend::requested(Some(&key)) {
+                    suspend::suspend(terminal, app.treemap)?;
+                    continue;
+                }
+                if app.confirming_delete {
+                    match key.code {
+                        KeyCode::Char('y') | KeyCode::Char('Y') => app.delete_selected(),
+                        _ => app.cancel_delete(),
+                    }
+                    continue;
+                }
+                if app.categories.is_some() {
+                    if matches!(
+                        key.code,
+                        KeyCode::Char('q') | KeyCode::Char('c') | KeyCode::Esc
+                    ) {
+                        app.close_categories();
+                    }
+                    continue;
+                }
+                if app.caches.is_some() {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Char('C') | KeyCode::Esc => {
+                            app.close_caches()
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => app.caches_move_up(),
+                        KeyCode::Down | KeyCode::Char('j') => app.caches_move_down(),
+                        KeyCode::Char(' ') => app.toggle_cache(),
+                        KeyCode::Char('a') => app.toggle_all_caches(),
+                        KeyCode::Enter => app.remove_checked_caches(),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.search.is_some() {
+                    match key.code {
+                        KeyCode::Esc => app.close_search(),
+                        KeyCode::Up => app.search_move_up(),
+                        KeyCode::Down => app.search_move_down(),
+                        KeyCode::Tab => app.toggle_search_mode(),
+                        KeyCode::Backspace => app.search_pop(),
+                        KeyCode::Enter => app.jump_to_search_hit(),
+                        KeyCode::Char(c) => app.search_push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.bookmark_list.is_some() {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Char('B') | KeyCode::Esc => {
+                            app.close_bookmarks()
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => app.bookmarks_move_up(),
+                        KeyCode::Down | KeyCode::Char('j') => app.bookmarks_move_down(),
+                        KeyCode::Enter => app.jump_to_bookmark(),
+                        KeyCode::Char('b') => {
+                            app.remove_bookmark();
+                            save_bookmarks(&mut app);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.largest.is_some() {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Char('L') | KeyCode::Esc => {
+                            app.close_largest_dirs()
+                        }
+                        KeyCode::Up | KeyCode::Char('k') => app.largest_move_up(),
+                        KeyCode::Down | KeyCode::Char('j') => app.largest_move_down(),
+                        KeyCode::Enter => app.jump_to_largest(),
+                        _ => {}
+                    }
+                    continue;
+                }
                 match key.code {
                     KeyCode::Char('q') | KeyCode::Esc => break,
                     KeyCode::Up | KeyCode::Char('k') => app.move_up(),
@@ -247,6 +759,49 @@ fn main() -> io::Result<()> {
                     KeyCode::Enter => app.enter(),
                     KeyCode::Backspace => app.go_back(),
                     KeyCode::Char('s') => app.toggle_sort(),
+                    KeyCode::Char('o') => app.toggle_own_sizes(),
+                    KeyCode::Char('a') => app.toggle_both_sizes(),
+                    KeyCode::Char('n') => app.toggle_item_counts(),
+                    KeyCode::Char('h') => app.toggle_heatmap(),
+                    KeyCode::Char('%') => app.toggle_percent_of_root(),
+                    KeyCode::Char('/') => app.open_search(),
+                    KeyCode::Char('L') => app.open_largest_dirs(),
+                    KeyCode::Char('b') => {
+                        app.toggle_bookmark();
+                        save_bookmarks(&mut app);
+                    }
+                    KeyCode::Char('B') => app.open_bookmarks(),
+                    KeyCode::Char('c') => app.open_categories(),
+                    KeyCode::Char('C') => app.open_caches(),
+                    KeyCode::Char('d') => app.request_delete(),
+                    KeyCode::Char('F') => app.jump_to_largest_file(),
+                    KeyCode::Char('R') if app.rescannable => return Ok(Some(app)),
+                    KeyCode::Char('R') => {
+                        app.notice = Some("only a scan run here can be rescanned".to_string());
+                    }
+                    KeyCode::Char('e') if app.treemap => {
+                        let out = PathBuf::from(TREEMAP_EXPORT_FILE);
+                        app.notice = Some(match write_treemap_svg(&app.scan, &app.nav_path, &out) {
+                            Ok(()) => format!("saved treemap to {}", out.display()),
+                            Err(e) => format!("couldn't save {}: {}", out.display(), e),
+                        });
+                    }
+                    KeyCode::Char('y') => {
+                        app.notice = Some(match copy_breakdown(&app.scan, &app.nav_path) {
+                            Ok(()) => "copied size breakdown to clipboard".to_string(),
+                            Err(e) => format!("couldn't copy: {}", e),
+                        });
+                    }
+                    KeyCode::Char('t') => {
+                        app.toggle_treemap();
+                        // Only grab the mouse while it's useful; it disables
+                        // the terminal's own text selection
+                        if app.treemap {
+                            execute!(terminal.backend_mut(), EnableMouseCapture)?;
+                        } else {
+                            execute!(terminal.backend_mut(), DisableMouseCapture)?;
+                        }
+                    }
                     _ => {}
                 }
             }
@@ -254,11 +809,901 @@ fn main() -> io::Result<()> {
     }
 
     cleanup_terminal()?;
-    Ok(())
+    Ok(None)
+}
+
+/// Write the app's bookmarks to the config file, saying so in the footer if
+/// that fails.
+fn save_bookmarks(app: &mut App) {
+    let paths: Vec<_> = app.bookmarks.iter().map(|p| p.to_string_lossy()).collect();
+    if let Err(e) = config::persist_list("ui", "bookmarks", &paths) {
+        app.notice = Some(format!("bookmarks not saved: {}", e));
+    }
+}
+
+/// What to tell the user when a `--time-limit` scan left directories unread.
+fn unexpanded_note(tree: &FileNode) -> Option<String> {
+    match count_unexpanded(tree) {
+        0 => None,
+        1 => Some("time limit reached: 1 directory not scanned; sizes are lower bounds".to_string()),
+        n => Some(format!(
+            "time limit reached: {} directories not scanned; sizes are lower bounds",
+            n
+        )),
+    }
+}
+
+/// Start the `--log` progress log for a scan reporting to `progress`, when
+/// one was asked for. The file opened in [`parse_args`]; if it can't be
+/// opened again now, the scan goes ahead without it.
+fn start_log(args: &Args, progress: &ScanProgress) -> Option<ProgressLog> {
+    let path = args.log.as_ref()?;
+    ProgressLog::start(path, progress, LOG_INTERVAL).ok()
+}
+
+/// Scan `path` without any UI for the non-interactive modes, saving a
+/// snapshot if one was requested.
+fn scan_for_report(path: PathBuf, args: &Args) -> ScanResult {
+    let progress = ScanProgress::new();
+    let _log = start_log(args, &progress);
+    scan_local_for_report(path, args, &progress, &progress)
+}
+
+/// [`scan_for_report`], reporting progress to `sink`. `counters` are the
+/// ones `sink` keeps, read back for the summary.
+fn scan_local_for_report<P: ProgressSink + ?Sized>(
+    path: PathBuf,
+    args: &Args,
+    sink: &P,
+    counters: &ScanProgress,
+) -> ScanResult {
+    let root_path = canonical_root(&path);
+    let started = Instant::now();
+    let usage = resource_usage();
+    let scan = match &args.resume {
+        Some(checkpoint) => scan_root_resumable(&root_path, checkpoint, sink, &args.options)
+            .unwrap_or_else(|e| {
+                eprintln!("error: resuming scan: {}", e);
+                std::process::exit(1);
+            }),
+        None => scan_root(&root_path, sink, &args.options, args.time_limit),
+    };
+    let whole_drive = drive_at(&root_path).is_some();
+    let unaccounted = whole_drive.then(|| unaccounted(&root_path, &scan.tree)).flatten();
+    let purgeable = whole_drive.then(|| purgeable_space(&root_path)).flatten();
+    report_scan_time(started, usage, counters, unaccounted, purgeable);
+    if let Some(hint) = scan.hint {
+        eprintln!("note: {}", hint);
+    }
+    if let Some(note) = unexpanded_note(&scan.tree) {
+        eprintln!("note: {}", note);
+    }
+    // A cancelled scan is incomplete; keep it out of snapshots
+    if !sink.cancelled() {
+        save_if_requested(&scan, args);
+    }
+    scan
+}
+
+/// Scan `[user@]host:path` for the non-interactive modes like
+/// [`scan_local_for_report`], exiting if the remote scan fails.
+fn scan_remote_for_report<P: ProgressSink + ?Sized>(
+    target: &str,
+    args: &Args,
+    sink: &P,
+    counters: &ScanProgress,
+) -> ScanResult {
+    let started = Instant::now();
+    let usage = resource_usage();
+    let scan = scan_remote(target, sink, &args.options).unwrap_or_else(|e| {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    });
+    report_scan_time(started, usage, counters, None, None);
+    if !sink.cancelled() {
+        save_if_requested(&scan, args);
+    }
+    scan
+}
+
+/// `--refresh`: bring a loaded snapshot up to date from the NTFS change
+/// journal, falling back to a full rescan when the journal can't be used.
+fn refresh_scan(scan: ScanResult, args: &Args) -> ScanResult {
+    #[cfg(windows)]
+    {
+        let mut scan = scan;
+        let started = Instant::now();
+        match disku_core::usn_journal::apply_journal(&mut scan, &args.options, &ScanProgress::new())
+        {
+            Ok(()) => {
+                let secs = started.elapsed().as_secs_f64();
+                eprintln!("refreshed in {}", format_duration(secs));
+                save_if_requested(&scan, args);
+                scan
+            }
+            Err(e) => {
+                eprintln!("note: {}; rescanning", e);
+                scan_for_report(scan.root_path, args)
+            }
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        eprintln!("note: --refresh needs an NTFS change journal (Windows only); rescanning");
+        scan_for_report(scan.root_path, args)
+    }
+}
+
+/// Tell the user how long a report-mode scan took, with the CPU time used
+/// since `usage`, and what it missed of a whole drive's `unaccounted` space
+/// apart from what's `purgeable`. Goes to stderr so piped output stays clean.
+fn report_scan_time(
+    started: Instant,
+    usage: Option<ResourceUsage>,
+    progress: &ScanProgress,
+    unaccounted: Option<u64>,
+    purgeable: Option<u64>,
+) {
+    let secs = started.elapsed().as_secs_f64();
+    let used = usage_since(usage);
+    let cpu_secs = used.map(|u| u.cpu().as_secs_f64());
+    let excluded = progress.excluded.load(Ordering::Relaxed);
+    let errors = progress.errors.load(Ordering::Relaxed);
+    let cycles = progress.cycles_detected.load(Ordering::Relaxed);
+    eprintln!(
+        "{}",
+        scan_summary(secs, cpu_secs, excluded, errors, cycles, unaccounted, purgeable)
+    );
+    if let Some(used) = used {
+        eprintln!(
+            "cpu: {} user, {} system",
+            format_duration(used.user.as_secs_f64()),
+            format_duration(used.system.as_secs_f64())
+        );
+    }
+    match progress.bulk_fallbacks.load(Ordering::Relaxed) {
+        0 => {}
+        n => eprintln!("note: {} directories re-read after a malformed bulk listing", n),
+    }
+    match progress.fd_exhausted.load(Ordering::Relaxed) {
+        0 => {}
+        n => eprintln!(
+            "note: {} directories skipped: too many open files \
+             (raise ulimit -n or lower --max-open-dirs)",
+            n
+        ),
+    }
+}
+
+/// What the process has used since `before` was read with
+/// [`resource_usage`], when the platform reports it.
+fn usage_since(before: Option<ResourceUsage>) -> Option<ResourceUsage> {
+    Some(resource_usage()?.since(&before?))
+}
+
+/// Write `--save` for the non-interactive modes, reporting failure on stderr.
+fn save_if_requested(scan: &ScanResult, args: &Args) {
+    if let Some(out) = &args.save {
+        if let Err(e) = save_snapshot(scan, out) {
+            eprintln!("error: saving {}: {}", out.display(), e);
+        }
+    }
+}
+
+/// Scan a `[user@]host:path` target over SFTP.
+#[cfg(feature = "sftp")]
+fn scan_remote<P: ProgressSink + ?Sized>(
+    target: &str,
+    p: &P,
+    options: &ScanOptions,
+) -> Result<ScanResult, String> {
+    use disku_core::sftp_scanner::{scan_sftp_with_options, SshTarget};
+
+    let target: SshTarget = target.parse().map_err(|e| format!("{}", e))?;
+    scan_sftp_with_options(&target, p, options).map_err(|e| format!("{}: {}", target, e))
+}
+
+#[cfg(not(feature = "sftp"))]
+fn scan_remote<P: ProgressSink + ?Sized>(
+    _target: &str,
+    _p: &P,
+    _options: &ScanOptions,
+) -> Result<ScanResult, String> {
+    Err("this disku was built without SFTP support (rebuild with --features sftp)".to_string())
+}
+
+/// Non-interactive `--output-tree` / `--top` / `--treemap-svg` / `--folded`
+/// modes: print or write the report and exit.
+fn print_report(scan: &ScanResult, args: &Args) -> io::Result<()> {
+    let root = &scan.tree;
+
+    if let Some(out) = &args.treemap_svg {
+        if let Err(e) = write_treemap_svg(scan, &[], out) {
+            eprintln!("error: writing {}: {}", out.display(), e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(out) = &args.folded {
+        if let Err(e) = write_folded(root, out) {
+            eprintln!("error: writing {}: {}", out.display(), e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut out = io::stdout().lock();
+    let printed = match args.top {
+        Some(n) => print_top(root, &mut out, n, args.cost_per_gb_month),
+        None => print_ascii(root, &mut out, args.depth, args.per_dir),
+    };
+    match printed {
+        // Piping into `head` and friends closes stdout early; that's fine
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        r => r,
+    }
+}
+
+/// `--compare`: scan `PATH` and `other` one after the other, then print them
+/// side by side with [`print_compare`].
+fn print_comparison(other: &Path, args: &Args) -> io::Result<()> {
+    let Some(path) = args.path.clone() else {
+        eprintln!("error: --compare requires a PATH");
+        std::process::exit(1);
+    };
+    let progress = ScanProgress::new();
+    let _log = start_log(args, &progress);
+    let mut finished = scan_many(&[path, other.to_path_buf()], args, &progress, &progress);
+    for done in &mut finished {
+        if args.dirs_only {
+            dirs_only(&mut done.scan.tree);
+        }
+    }
+    let [a, b] = &finished[..] else {
+        unreachable!("a ScanProgress is never cancelled")
+    };
+
+    let mut out = io::stdout().lock();
+    match print_compare(&a.scan.tree, &b.scan.tree, &mut out, args.color) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        r => r,
+    }
+}
+
+/// How often `--json` reports progress.
+const JSON_PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+/// Progress lines that may wait for a slow reader before newer ones are
+/// dropped.
+const JSON_PROGRESS_BACKLOG: usize = 4;
+
+/// One line of `--json` output.
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum JsonLine<'a> {
+    Progress(&'a ProgressUpdate),
+    Done {
+        path: &'a Path,
+        /// How long the scan took; absent for a loaded snapshot.
+        seconds: Option<f64>,
+        size: u64,
+        allocated: u64,
+        files: u64,
+        dirs: u64,
+        errors: u64,
+        excluded: u64,
+        /// Directories skipped as already entered.
+        cycles: u64,
+        tree: &'a FileNode,
+    },
+}
+
+/// `--json`: scan with progress streamed to stdout as JSON lines, then print
+/// the finished scan as a last `done` line (one per drive with `--drives`). A
+/// reader that goes away (a closed pipe) cancels the scan.
+fn print_json(loaded: Option<ScanResult>, args: &Args) -> io::Result<()> {
+    let (progress, updates) = ChannelProgress::new(JSON_PROGRESS_INTERVAL, JSON_PROGRESS_BACKLOG);
+    let writer = thread::spawn(move || -> io::Result<()> {
+        let mut out = io::stdout().lock();
+        for update in updates {
+            write_json_line(&mut out, &JsonLine::Progress(&update))?;
+        }
+        Ok(())
+    });
+
+    let started = Instant::now();
+    let counters = &progress.counters;
+    let log = start_log(args, counters);
+    let mut finished = match (loaded, args.ssh.as_deref(), args.path.clone()) {
+        (Some(scan), _, _) => vec![Finished::new(scan, None, counters)],
+        (None, Some(target), _) => {
+            let scan = scan_remote_for_report(target, args, &progress, counters);
+            vec![Finished::new(scan, Some(started.elapsed().as_secs_f64()), counters)]
+        }
+        (None, None, Some(path)) => {
+            let scan = scan_local_for_report(path, args, &progress, counters);
+            vec![Finished::new(scan, Some(started.elapsed().as_secs_f64()), counters)]
+        }
+        (None, None, None) if !args.drives.is_empty() => {
+            scan_many(&args.drives, args, &progress, counters)
+        }
+        (None, None, None) => {
+            eprintln!("error: --json requires a PATH");
+            std::process::exit(1);
+        }
+    };
+
+    drop(log);
+    // Closes the channel, so the writer finishes once it has caught up
+    drop(progress);
+    let written = writer.join().expect("progress writer panicked").and_then(|()| {
+        let mut out = io::stdout().lock();
+        for Finished { scan, seconds, errors, excluded, cycles } in &mut finished {
+            let (files, dirs) = count_entries(&scan.tree);
+            if args.dirs_only {
+                dirs_only(&mut scan.tree);
+            }
+            let done = JsonLine::Done {
+                path: &scan.root_path,
+                seconds: *seconds,
+                size: scan.tree.size,
+                allocated: scan.tree.allocated,
+                files,
+                dirs,
+                errors: *errors,
+                excluded: *excluded,
+                cycles: *cycles,
+                tree: &scan.tree,
+            };
+            write_json_line(&mut out, &done)?;
+        }
+        Ok(())
+    });
+    match written {
+        // The reader stopped listening, and the scan was cancelled for it
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        r => r,
+    }
+}
+
+/// A scan ready for its `done` line.
+struct Finished {
+    scan: ScanResult,
+    /// How long the scan took; `None` for a loaded snapshot.
+    seconds: Option<f64>,
+    errors: u64,
+    excluded: u64,
+    cycles: u64,
+}
+
+impl Finished {
+    /// `scan`, with the errors, exclusions and cycles `counters` recorded
+    /// for it.
+    fn new(scan: ScanResult, seconds: Option<f64>, counters: &ScanProgress) -> Self {
+        Self {
+            scan,
+            seconds,
+            errors: counters.errors.load(Ordering::Relaxed),
+            excluded: counters.excluded.load(Ordering::Relaxed),
+            cycles: counters.cycles_detected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Scan each of `paths` in turn, as [`scan_local_for_report`] does, sharing
+/// `sink` so progress runs on across them. Each result gets only its own
+/// errors, exclusions and cycles. Stops once the sink is cancelled.
+fn scan_many<P: ProgressSink + ?Sized>(
+    paths: &[PathBuf],
+    args: &Args,
+    sink: &P,
+    counters: &ScanProgress,
+) -> Vec<Finished> {
+    let mut finished: Vec<Finished> = Vec::new();
+    for path in paths {
+        if sink.cancelled() {
+            break;
+        }
+        let errors_before = counters.errors.load(Ordering::Relaxed);
+        let excluded_before = counters.excluded.load(Ordering::Relaxed);
+        let cycles_before = counters.cycles_detected.load(Ordering::Relaxed);
+        let started = Instant::now();
+        let scan = scan_local_for_report(path.clone(), args, sink, counters);
+        let mut done = Finished::new(scan, Some(started.elapsed().as_secs_f64()), counters);
+        done.errors -= errors_before;
+        done.excluded -= excluded_before;
+        done.cycles -= cycles_before;
+        finished.push(done);
+    }
+    finished
+}
+
+/// Write `line` as one line of JSON and flush it, so a reader sees each
+/// line as soon as it's complete.
+fn write_json_line<W: Write>(out: &mut W, line: &JsonLine) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, line)?;
+    out.write_all(b"\n")?;
+    out.flush()
+}
+
+/// Where `e` in the treemap view saves it, relative to the working directory.
+const TREEMAP_EXPORT_FILE: &str = "disku-treemap.svg";
+
+/// Children listed by `y`'s copied size breakdown.
+const BREAKDOWN_ENTRIES: usize = 10;
+
+/// Put a [`size_breakdown`] of the directory at `nav_path` on the clipboard.
+/// Goes through the terminal (OSC 52) rather than a clipboard API, so it also
+/// works over SSH, but only in terminals that allow it.
+fn copy_breakdown(scan: &ScanResult, nav_path: &[usize]) -> io::Result<()> {
+    let (Some(node), Some(path)) = (scan.node_at(nav_path), scan.path_of(nav_path)) else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "directory not in the scan"));
+    };
+    let text = size_breakdown(node, &path, BREAKDOWN_ENTRIES);
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    stdout.flush()
+}
+
+/// Standard (RFC 4648) base64 with padding, as OSC 52 wants.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = (chunk.iter().enumerate())
+            .fold(0u32, |n, (i, &b)| n | (u32::from(b) << (16 - 8 * i)));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Size of an exported treemap, in pixels.
+const SVG_WIDTH: u32 = 1200;
+const SVG_HEIGHT: u32 = 800;
+
+/// Write the treemap of the directory at `nav_path` to the SVG file `out`.
+fn write_treemap_svg(scan: &ScanResult, nav_path: &[usize], out: &Path) -> io::Result<()> {
+    let mut file = io::BufWriter::new(std::fs::File::create(out)?);
+    export_treemap_svg(scan, nav_path, SVG_WIDTH, SVG_HEIGHT, &mut file)?;
+    file.flush()
+}
+
+/// Write `tree` as folded stacks to `out`, or to stdout when `out` is `-`.
+fn write_folded(tree: &FileNode, out: &Path) -> io::Result<()> {
+    let written = if out == Path::new("-") {
+        let mut stdout = io::BufWriter::new(io::stdout().lock());
+        export_folded(tree, &mut stdout).and_then(|()| stdout.flush())
+    } else {
+        let mut file = io::BufWriter::new(std::fs::File::create(out)?);
+        export_folded(tree, &mut file).and_then(|()| file.flush())
+    };
+    match written {
+        // Piping into `head` and friends closes stdout early; that's fine
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        r => r,
+    }
 }
 
 fn cleanup_terminal() -> io::Result<()> {
     disable_raw_mode()?;
-    execute!(io::stdout(), LeaveAlternateScreen)?;
+    execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen)?;
     Ok(())
 }
+
+// -- Argument parsing --
+
+struct Args {
+    path: Option<PathBuf>,
+    /// Scan these drives one after another instead of `path` (`--drives`).
+    drives: Vec<PathBuf>,
+    options: ScanOptions,
+    /// Forced on/off by flag; `None` means detect from the environment.
+    hyperlinks: Option<bool>,
+    /// Draw in color: off with `--no-color`, or when the environment asks.
+    color: bool,
+    /// Colors for directories and file categories.
+    palette: Palette,
+    /// Show storage cost at this price per GB-month.
+    cost_per_gb_month: Option<f64>,
+    /// Fold entries under this percent of their directory into one row.
+    group_below: Option<f64>,
+    /// Decimal places of the list's percentages; `None` picks by width.
+    percent_decimals: Option<u8>,
+    /// Print an ASCII tree instead of starting the TUI.
+    output_tree: bool,
+    /// Print the N largest immediate children instead of starting the TUI.
+    top: Option<usize>,
+    /// Write the root's treemap to this SVG file instead of starting the TUI.
+    treemap_svg: Option<PathBuf>,
+    /// Write the tree as flame graph folded stacks to this file (`-` for
+    /// stdout) instead of starting the TUI.
+    folded: Option<PathBuf>,
+    /// Stream progress and then the finished scan to stdout as JSON lines
+    /// instead of starting the TUI.
+    json: bool,
+    /// Scan this directory after `path` and print the two side by side
+    /// instead of starting the TUI.
+    compare: Option<PathBuf>,
+    /// Write the finished scan to this snapshot file.
+    save: Option<PathBuf>,
+    /// Browse a saved snapshot (or an ncdu export) instead of scanning.
+    load: Option<PathBuf>,
+    /// Update the loaded snapshot from the NTFS change journal first.
+    refresh: bool,
+    /// Scan this `[user@]host:path` over SFTP instead of a local path.
+    ssh: Option<String>,
+    /// Checkpoint finished top-level directories here, resuming from it if
+    /// it already exists.
+    resume: Option<PathBuf>,
+    /// Stop reading directories after this long, biggest-looking first.
+    time_limit: Option<Duration>,
+    /// Scan at the lowest CPU and disk priority.
+    low_priority: bool,
+    /// Delete for good instead of moving to the Trash.
+    permanent: bool,
+    /// Let deletes touch system paths and `cleanup.protected_paths`.
+    allow_protected: bool,
+    /// Show the scanner's directory queue depth while scanning.
+    debug: bool,
+    /// Append a line of progress to this file every few seconds while
+    /// scanning; see [`disku_core::progress_log`].
+    log: Option<PathBuf>,
+    /// Leave files out of the tree shown or printed, keeping directories
+    /// with their full totals. Snapshots are still saved whole.
+    dirs_only: bool,
+    depth: usize,
+    per_dir: usize,
+}
+
+const USAGE: &str = "usage: disku [--collapse-bundles] [--intern-names] [--round-blocks] [--file-ids]
+             [--cross-filesystems] [--expand-trash] [--xattrs]
+             [--exclude PATTERN]... [--exclude-from FILE]...
+             [--system-files include|group|exclude] [--time-limit DURATION]
+             [--low-priority] [--spindle-friendly | --no-spindle-friendly] [--max-open-dirs N]
+             [--hyperlinks | --no-hyperlinks] [--no-color]
+             [--palette default|deuteranopia|protanopia|colorblind] [--cost-per-gb RATE]
+             [--group-below PERCENT] [--percent-decimals N]
+             [--output-tree [--depth N] [--per-dir N] | --top N | --treemap-svg FILE
+              | --folded FILE | --json | --compare OTHER]
+             [--save FILE] [--load FILE [--refresh]] [--resume CHECKPOINT]
+             [--dirs-only] [--permanent] [--allow-protected] [--debug] [--log FILE]
+             [--ssh [USER@]HOST:PATH | --drives DRIVE,... | PATH]
+
+Defaults come from ~/.config/disku/config.toml; flags take precedence.";
+
+/// Parse the command line on top of the defaults from `config`.
+fn parse_args(config: &Config) -> Args {
+    let mut args_iter = std::env::args().skip(1);
+    let mut path: Option<PathBuf> = None;
+    let mut drives: Vec<String> = Vec::new();
+    let mut options = config.scan_options();
+    let mut hyperlinks = config.ui.hyperlinks;
+    let mut no_color = false;
+    let mut palette = config.ui.palette;
+    let mut cost_per_gb_month = config.ui.cost_per_gb_month;
+    let mut group_below = config.ui.group_below;
+    let mut percent_decimals = config.ui.percent_decimals;
+    let mut output_tree = false;
+    let mut top = None;
+    let mut treemap_svg = None;
+    let mut folded = None;
+    let mut json = false;
+    let mut compare: Option<PathBuf> = None;
+    let mut save = None;
+    let mut log: Option<PathBuf> = None;
+    let mut load = None;
+    let mut ssh = None;
+    let mut resume = None;
+    let mut time_limit = None;
+    let mut low_priority = config.scan.low_priority;
+    let mut refresh = false;
+    let mut permanent = false;
+    let mut allow_protected = false;
+    let mut debug = false;
+    let mut dirs_only = false;
+    let mut exclude: Vec<String> = Vec::new();
+    let mut depth: usize = config.tree.depth;
+    let mut per_dir: usize = config.tree.per_dir;
+
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "--collapse-bundles" => options.treat_bundles_as_files = true,
+            "--intern-names" => options.intern_names = true,
+            "--round-blocks" => options.round_to_blocks = true,
+            "--file-ids" => options.capture_ids = true,
+            "--cross-filesystems" => options.cross_filesystems = true,
+            "--expand-trash" => options.expand_trash = true,
+            "--xattrs" => options.include_xattrs = true,
+            "--spindle-friendly" => options.spindle_friendly = Some(true),
+            "--no-spindle-friendly" => options.spindle_friendly = Some(false),
+            "--exclude" => exclude.push(parse_value(&arg, args_iter.next())),
+            "--exclude-from" => {
+                let file: PathBuf = parse_value(&arg, args_iter.next());
+                let patterns = read_patterns(&file).unwrap_or_else(|e| {
+                    eprintln!("error: reading {}: {}", file.display(), e);
+                    std::process::exit(1);
+                });
+                exclude.extend(patterns);
+            }
+            "--system-files" => options.system_files = parse_value(&arg, args_iter.next()),
+            "--hyperlinks" => hyperlinks = Some(true),
+            "--no-hyperlinks" => hyperlinks = Some(false),
+            "--no-color" => no_color = true,
+            "--palette" => palette = parse_value(&arg, args_iter.next()),
+            "--cost-per-gb" => cost_per_gb_month = Some(parse_value(&arg, args_iter.next())),
+            "--group-below" => group_below = Some(parse_value(&arg, args_iter.next())),
+            "--percent-decimals" => {
+                percent_decimals = Some(parse_value(&arg, args_iter.next()));
+            }
+            "--output-tree" => output_tree = true,
+            "--top" => top = Some(parse_value(&arg, args_iter.next())),
+            "--treemap-svg" => treemap_svg = Some(parse_value(&arg, args_iter.next())),
+            "--folded" => folded = Some(parse_value(&arg, args_iter.next())),
+            "--json" => json = true,
+            "--compare" => compare = Some(parse_value(&arg, args_iter.next())),
+            "--save" => save = Some(parse_value(&arg, args_iter.next())),
+            "--log" => log = Some(parse_value(&arg, args_iter.next())),
+            "--load" => load = Some(parse_value(&arg, args_iter.next())),
+            "--ssh" => ssh = Some(parse_value(&arg, args_iter.next())),
+            "--drives" => {
+                let list: String = parse_value(&arg, args_iter.next());
+                drives.extend(list.split(',').filter(|d| !d.is_empty()).map(str::to_string));
+            }
+            "--refresh" => refresh = true,
+            "--permanent" => permanent = true,
+            "--allow-protected" => allow_protected = true,
+            "--debug" => debug = true,
+            "--dirs-only" => dirs_only = true,
+            "--resume" => resume = Some(parse_value(&arg, args_iter.next())),
+            "--low-priority" => low_priority = true,
+            "--max-open-dirs" => {
+                options.max_open_dirs = Some(parse_value(&arg, args_iter.next()));
+            }
+            "--time-limit" => {
+                let value: String = parse_value(&arg, args_iter.next());
+                time_limit = Some(parse_time_limit(&value).unwrap_or_else(|| {
+                    eprintln!("error: invalid value for {}: {} (try 10s or 2m)", arg, value);
+                    std::process::exit(1);
+                }));
+            }
+            "--depth" => depth = parse_value(&arg, args_iter.next()),
+            "--per-dir" => per_dir = parse_value(&arg, args_iter.next()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            other if other.starts_with('-') => {
+                eprintln!("error: unknown option: {}", other);
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+            _ => {
+                path = Some(PathBuf::from(arg));
+            }
+        }
+    }
+
+    if let Some(rate) = cost_per_gb_month.filter(|r| !r.is_finite() || *r < 0.0) {
+        eprintln!("error: cost per GB-month must be a number from 0 up, not {}", rate);
+        std::process::exit(1);
+    }
+    if let Some(pct) = group_below.filter(|p| !(0.0..=100.0).contains(p)) {
+        eprintln!("error: --group-below must be a percentage from 0 to 100, not {}", pct);
+        std::process::exit(1);
+    }
+    if let Some(n) = percent_decimals.filter(|&n| n > MAX_PERCENT_DECIMALS) {
+        eprintln!(
+            "error: percent decimals must be from 0 to {}, not {}",
+            MAX_PERCENT_DECIMALS, n
+        );
+        std::process::exit(1);
+    }
+
+    // Find out now rather than once the terminal is taken over
+    if let Some(path) = &log {
+        if let Err(e) = std::fs::OpenOptions::new().create(true).append(true).open(path) {
+            eprintln!("error: opening {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    if options.max_open_dirs == Some(0) {
+        eprintln!("error: --max-open-dirs must be at least 1");
+        std::process::exit(1);
+    }
+
+    if time_limit.is_some() && (resume.is_some() || ssh.is_some()) {
+        eprintln!("error: --time-limit can't be combined with --resume or --ssh");
+        std::process::exit(1);
+    }
+
+    if !drives.is_empty() {
+        if !json {
+            eprintln!("error: --drives requires --json");
+            std::process::exit(1);
+        }
+        if path.is_some() || ssh.is_some() || load.is_some() || resume.is_some() || save.is_some()
+        {
+            eprintln!(
+                "error: --drives can't be combined with a PATH, --ssh, --load, --resume or --save"
+            );
+            std::process::exit(1);
+        }
+    }
+    let drives = resolve_drives(&drives);
+
+    if json && (output_tree || top.is_some() || treemap_svg.is_some() || folded.is_some()) {
+        eprintln!(
+            "error: --json can't be combined with --output-tree, --top, --treemap-svg or --folded"
+        );
+        std::process::exit(1);
+    }
+
+    if compare.is_some() {
+        if json || output_tree || top.is_some() || treemap_svg.is_some() || folded.is_some() {
+            eprintln!(
+                "error: --compare can't be combined with --output-tree, --top, --treemap-svg, \
+                 --folded or --json"
+            );
+            std::process::exit(1);
+        }
+        if ssh.is_some() || load.is_some() || resume.is_some() || save.is_some() {
+            eprintln!("error: --compare can't be combined with --ssh, --load, --resume or --save");
+            std::process::exit(1);
+        }
+    }
+
+    if !exclude.is_empty() {
+        let set = ExcludeSet::new(&exclude).unwrap_or_else(|e| {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        });
+        options.exclude = Some(Arc::new(set));
+    }
+
+    Args {
+        path,
+        drives,
+        options,
+        hyperlinks,
+        color: !no_color && terminal_supports_color(),
+        palette,
+        cost_per_gb_month,
+        group_below,
+        percent_decimals,
+        output_tree,
+        top,
+        treemap_svg,
+        folded,
+        json,
+        compare,
+        save,
+        load,
+        refresh,
+        ssh,
+        resume,
+        time_limit,
+        low_priority,
+        permanent,
+        allow_protected,
+        debug,
+        log,
+        dirs_only,
+        depth,
+        per_dir,
+    }
+}
+
+/// The mount points `--drives` names, checked against [`detect_drives`]:
+/// drive letters (`C` or `C:`) on Windows, mount point paths elsewhere.
+/// Exits naming the available drives if one isn't found.
+fn resolve_drives(names: &[String]) -> Vec<PathBuf> {
+    if names.is_empty() {
+        return Vec::new();
+    }
+    let drives = detect_drives();
+    let mut paths: Vec<PathBuf> = Vec::new();
+    for name in names {
+        let Some(drive) = find_drive(&drives, name) else {
+            let available: Vec<&str> = drives.iter().map(|d| d.path.as_str()).collect();
+            eprintln!("error: unknown drive: {} (available: {})", name, available.join(", "));
+            std::process::exit(1);
+        };
+        let path = PathBuf::from(&drive.path);
+        if !paths.contains(&path) {
+            paths.push(path);
+        }
+    }
+    paths
+}
+
+/// The drive `name` refers to, if any.
+fn find_drive<'a>(drives: &'a [DriveInfo], name: &str) -> Option<&'a DriveInfo> {
+    #[cfg(windows)]
+    {
+        let letter = name.trim_end_matches(['\\', '/']).trim_end_matches(':');
+        drives.iter().find(|d| {
+            let drive_letter = d.path.trim_end_matches('\\').trim_end_matches(':');
+            !letter.is_empty() && drive_letter.eq_ignore_ascii_case(letter)
+        })
+    }
+    #[cfg(not(windows))]
+    {
+        let root = canonical_root(Path::new(name));
+        drives
+            .iter()
+            .find(|d| canonical_root(Path::new(&d.path)) == root)
+    }
+}
+
+/// Parse the value following `flag`, exiting with a usage error if it is
+/// missing or malformed.
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> T {
+    let Some(value) = value else {
+        eprintln!("error: {} requires a value", flag);
+        std::process::exit(1);
+    };
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("error: invalid value for {}: {}", flag, value);
+        std::process::exit(1);
+    })
+}
+
+/// Parse a `--time-limit` value: seconds, with an optional `s`, `m` or `h`
+/// unit (`10`, `10s`, `1.5m`).
+fn parse_time_limit(value: &str) -> Option<Duration> {
+    let (number, scale) = match value.char_indices().last()? {
+        (i, 's') => (&value[..i], 1.0),
+        (i, 'm') => (&value[..i], 60.0),
+        (i, 'h') => (&value[..i], 3600.0),
+        _ => (value, 1.0),
+    };
+    let secs = number.parse::<f64>().ok()? * scale;
+    (secs > 0.0 && secs.is_finite()).then(|| Duration::from_secs_f64(secs))
+}
+
+/// Last step of drawing every frame: drop its colors when they're off.
+fn finish_frame(f: &mut ratatui::Frame, color: bool) {
+    if !color {
+        strip_colors(f.buffer_mut());
+    }
+}
+
+/// Whether to draw in color: not when `NO_COLOR` is set (see no-color.org),
+/// on a `dumb` terminal, or when stdout isn't a terminal at all.
+fn terminal_supports_color() -> bool {
+    use std::io::IsTerminal;
+
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let dumb = std::env::var("TERM").is_ok_and(|t| t == "dumb");
+    !no_color && !dumb && io::stdout().is_terminal()
+}
+
+/// Best-effort check for terminals known to render OSC 8 hyperlinks. Unknown
+/// terminals get plain text so no stray escape codes show up.
+fn terminal_supports_hyperlinks() -> bool {
+    let env = |k: &str| std::env::var(k).unwrap_or_default();
+
+    if matches!(
+        env("TERM_PROGRAM").as_str(),
+        "iTerm.app" | "WezTerm" | "vscode" | "Hyper" | "ghostty"
+    ) {
+        return true;
+    }
+    // Windows Terminal, kitty
+    if !env("WT_SESSION").is_empty() || !env("KITTY_WINDOW_ID").is_empty() {
+        return true;
+    }
+    // GNOME Terminal and other VTE terminals gained support in 0.50
+    if env("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5000) {
+        return true;
+    }
+    let term = env("TERM");
+    term.contains("kitty") || term.contains("alacritty") || term.contains("foot")
+}
@@ -0,0 +1,89 @@
+//! Ctrl-Z and `fg` while the TUI is up.
+//!
+//! Raw mode turns Ctrl-Z into a key press rather than a SIGTSTP, and a
+//! SIGTSTP sent some other way would stop the process with the terminal still
+//! in raw mode on the alternate screen, leaving the shell unusable. So the
+//! event loops watch for both and call [`suspend`], which hands the terminal
+//! back before stopping and takes it over again once continued. A scan in
+//! progress just stops with the process and carries on where it was.
+
+use std::io;
+
+use crossterm::event::{EnableMouseCapture, KeyCode, KeyEvent, KeyModifiers};
+use crossterm::execute;
+use crossterm::terminal::{enable_raw_mode, EnterAlternateScreen};
+use ratatui::backend::CrosstermBackend;
+use ratatui::Terminal;
+
+#[cfg(unix)]
+mod imp {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, OnceLock};
+
+    use signal_hook::consts::SIGTSTP;
+
+    /// Set by SIGTSTP once [`install`] has replaced its default action.
+    static REQUESTED: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+
+    pub fn install() {
+        let flag = REQUESTED.get_or_init(Default::default);
+        // Without the handler a SIGTSTP still stops us, just messily
+        let _ = signal_hook::flag::register(SIGTSTP, Arc::clone(flag));
+    }
+
+    pub fn take_requested() -> bool {
+        REQUESTED
+            .get()
+            .is_some_and(|flag| flag.swap(false, Ordering::Relaxed))
+    }
+
+    /// Stop the process as SIGTSTP would have, returning once it's continued.
+    pub fn stop() -> std::io::Result<()> {
+        signal_hook::low_level::emulate_default_handler(SIGTSTP)
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    pub fn install() {}
+
+    pub fn take_requested() -> bool {
+        false
+    }
+
+    pub fn stop() -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Catch SIGTSTP from now on, so the event loops can suspend cleanly. Call
+/// once the terminal is taken over; before that, the default stop is right.
+pub fn install() {
+    imp::install();
+}
+
+/// Whether to suspend now: on Ctrl-Z, or on a SIGTSTP received since the last
+/// check. Always false where there's no job control.
+pub fn requested(key: Option<&KeyEvent>) -> bool {
+    let ctrl_z = key.is_some_and(|key| {
+        key.code == KeyCode::Char('z') && key.modifiers.contains(KeyModifiers::CONTROL)
+    });
+    (cfg!(unix) && ctrl_z) || imp::take_requested()
+}
+
+/// Restore the terminal, stop until continued (`fg`), then take the terminal
+/// back and force a full redraw. `mouse` re-enables mouse capture.
+pub fn suspend(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    mouse: bool,
+) -> io::Result<()> {
+    crate::cleanup_terminal()?;
+    terminal.show_cursor()?;
+    imp::stop()?;
+    enable_raw_mode()?;
+    execute!(terminal.backend_mut(), EnterAlternateScreen)?;
+    if mouse {
+        execute!(terminal.backend_mut(), EnableMouseCapture)?;
+    }
+    terminal.clear()
+}
@@ -0,0 +1,137 @@
+use disku_core::config::Palette;
+use disku_core::utils::Category;
+use ratatui::style::Color;
+
+/// Colors for the parts of the UI where color carries meaning: directories
+/// against files, and file categories.
+pub struct Theme {
+    /// Directory names in the list.
+    pub dir: Color,
+    /// The `+` marking a directory in the list.
+    pub dir_icon: Color,
+    /// File name colors by content category, so media, archives and code
+    /// stand out in the list. Categories not listed use `file`.
+    pub file_colors: &'static [(Category, Color)],
+    pub file: Color,
+    /// Fill each category's bar in the "by file type" popup with its own
+    /// glyph in its own color, rather than every bar a solid block in `bar`.
+    /// Keeps bars apart without relying on hue alone.
+    pub patterned_bars: bool,
+    pub bar: Color,
+    /// The age heatmap's hues: modified this week, about a month ago, and
+    /// years ago. Brightness stands for size, so these are the full-size
+    /// shades.
+    pub heat: [Color; 3],
+}
+
+impl Theme {
+    pub fn new(palette: Palette) -> &'static Theme {
+        match palette {
+            Palette::Default => &DEFAULT,
+            Palette::Deuteranopia => &DEUTERANOPIA,
+            Palette::Protanopia => &PROTANOPIA,
+        }
+    }
+
+    /// Name color for a file in `category`.
+    pub fn file_color(&self, category: Category) -> Color {
+        self.file_colors
+            .iter()
+            .find(|&&(c, _)| c == category)
+            .map_or(self.file, |&(_, color)| color)
+    }
+
+    /// Glyph and color filling `category`'s bar in the "by file type" popup.
+    pub fn bar(&self, category: Category) -> (&'static str, Color) {
+        if !self.patterned_bars {
+            return ("\u{2588}", self.bar);
+        }
+        let glyph = match category {
+            Category::Video => "\u{2588}",
+            Category::Image => "\u{2593}",
+            Category::Audio => "\u{2592}",
+            Category::Document => "#",
+            Category::Code => "=",
+            Category::Archive => "%",
+            Category::DiskImage => "@",
+            Category::Executable => "+",
+            Category::Other => "-",
+        };
+        (glyph, self.file_color(category))
+    }
+}
+
+const DEFAULT: Theme = Theme {
+    dir: Color::Rgb(120, 170, 255),
+    dir_icon: Color::Rgb(100, 150, 255),
+    file_colors: &[
+        (Category::Video, Color::Rgb(215, 130, 215)),
+        (Category::Image, Color::Rgb(110, 200, 210)),
+        (Category::Audio, Color::Rgb(130, 200, 130)),
+        (Category::Document, Color::Rgb(220, 200, 140)),
+        (Category::Code, Color::Rgb(170, 150, 240)),
+        (Category::Archive, Color::Rgb(230, 120, 110)),
+        (Category::DiskImage, Color::Rgb(230, 160, 90)),
+        (Category::Executable, Color::Rgb(200, 210, 100)),
+    ],
+    file: Color::Rgb(180, 180, 180),
+    patterned_bars: false,
+    bar: Color::Rgb(100, 150, 255),
+    heat: [
+        Color::Rgb(90, 170, 255),
+        Color::Rgb(235, 200, 90),
+        Color::Rgb(240, 90, 70),
+    ],
+};
+
+/// Red-green (deuteranopia, the most common) friendly: the Okabe-Ito hues,
+/// lightened for dark backgrounds, telling categories apart by blue-yellow
+/// balance and brightness. Directories are white so blue is free for files.
+const DEUTERANOPIA: Theme = Theme {
+    dir: Color::Rgb(235, 235, 235),
+    dir_icon: Color::Rgb(200, 200, 200),
+    file_colors: &[
+        (Category::Video, Color::Rgb(204, 121, 167)),
+        (Category::Image, Color::Rgb(86, 180, 233)),
+        (Category::Audio, Color::Rgb(240, 228, 66)),
+        (Category::Document, Color::Rgb(230, 159, 0)),
+        (Category::Code, Color::Rgb(0, 158, 115)),
+        (Category::Archive, Color::Rgb(213, 94, 0)),
+        (Category::DiskImage, Color::Rgb(40, 120, 200)),
+        (Category::Executable, Color::Rgb(170, 170, 255)),
+    ],
+    file: Color::Rgb(140, 140, 140),
+    patterned_bars: true,
+    bar: Color::Rgb(86, 180, 233),
+    heat: [
+        Color::Rgb(86, 180, 233),
+        Color::Rgb(240, 228, 66),
+        Color::Rgb(213, 94, 0),
+    ],
+};
+
+/// Red-blind (protanopia) friendly: as [`DEUTERANOPIA`], but with the reds
+/// lightened, since they look dim without red cones and would sink into
+/// the background.
+const PROTANOPIA: Theme = Theme {
+    dir: Color::Rgb(235, 235, 235),
+    dir_icon: Color::Rgb(200, 200, 200),
+    file_colors: &[
+        (Category::Video, Color::Rgb(225, 150, 200)),
+        (Category::Image, Color::Rgb(86, 180, 233)),
+        (Category::Audio, Color::Rgb(240, 228, 66)),
+        (Category::Document, Color::Rgb(245, 180, 40)),
+        (Category::Code, Color::Rgb(0, 158, 115)),
+        (Category::Archive, Color::Rgb(250, 125, 50)),
+        (Category::DiskImage, Color::Rgb(40, 120, 200)),
+        (Category::Executable, Color::Rgb(170, 170, 255)),
+    ],
+    file: Color::Rgb(140, 140, 140),
+    patterned_bars: true,
+    bar: Color::Rgb(86, 180, 233),
+    heat: [
+        Color::Rgb(86, 180, 233),
+        Color::Rgb(240, 228, 66),
+        Color::Rgb(250, 125, 50),
+    ],
+};
@@ -0,0 +1,33 @@
+use disku_core::treemap::Tile;
+use ratatui::layout::Rect;
+
+/// Terminal cells are roughly twice as tall as they are wide; layouts are
+/// computed with heights scaled by this so blocks look square on screen.
+const CELL_ASPECT: f64 = 2.0;
+
+/// Lay out `sizes` (largest first, all non-zero) inside `area` with the
+/// squarified treemap algorithm. Returns one rect per input in the same order;
+/// items too small to cover a cell get an empty rect.
+pub fn squarify(sizes: &[u64], area: Rect) -> Vec<Rect> {
+    let bounds = Tile {
+        x: area.x as f64,
+        y: area.y as f64 * CELL_ASPECT,
+        w: area.width as f64,
+        h: area.height as f64 * CELL_ASPECT,
+    };
+    disku_core::treemap::squarify(sizes, bounds)
+        .into_iter()
+        .map(|tile| to_cells(tile, area))
+        .collect()
+}
+
+/// Snap to whole cells. Edges are rounded (rather than sizes) so neighbours
+/// share a boundary with no gaps or overlaps.
+fn to_cells(tile: Tile, bounds: Rect) -> Rect {
+    let snap = |v: f64, lo: u16, hi: u16| (v.round().max(lo as f64) as u16).min(hi);
+    let x0 = snap(tile.x, bounds.left(), bounds.right());
+    let x1 = snap(tile.x + tile.w, bounds.left(), bounds.right());
+    let y0 = snap(tile.y / CELL_ASPECT, bounds.top(), bounds.bottom());
+    let y1 = snap((tile.y + tile.h) / CELL_ASPECT, bounds.top(), bounds.bottom());
+    Rect::new(x0, y0, x1 - x0, y1 - y0)
+}
@@ -1,35 +1,228 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ratatui::buffer::Buffer;
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::style::{Color, Modifier, Style};
 use ratatui::text::{Line, Span};
-use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph};
 use ratatui::Frame;
 
-use disku_core::tree::FileNode;
-use disku_core::utils::{format_size, percent, DriveInfo};
+use disku_core::config::{DriveSort, Palette, SortKey, Truncation};
+use disku_core::delete::{remove_path, DeleteMode};
+use disku_core::tree::{
+    self, saturating_sum, CategoryTotal, FileNode, ScanResult, SearchHit, SearchMode,
+};
+
+use crate::theme::Theme;
+use crate::treemap;
+use disku_core::utils::{
+    categorize, file_url, format_cost, format_count, format_duration, format_relative_time,
+    format_size, is_protected_path, list_subdirs, percent, DriveInfo,
+};
 
 pub struct App {
-    pub tree: FileNode,
+    /// The scanned tree and the absolute path it came from; every displayed or
+    /// linked path is built from `scan.root_path`.
+    pub scan: ScanResult,
     pub nav_path: Vec<usize>,
     pub list_state: ListState,
-    pub sort_by_size: bool,
+    /// Order of the current directory's entries. Sorting by recency also
+    /// adds a column with how long ago each entry was modified.
+    pub sort: SortKey,
+    /// List directories by the files directly inside them rather than their
+    /// inclusive size.
+    pub own_sizes: bool,
+    /// Add on-disk size and slack (on-disk minus logical size) columns to
+    /// the list.
+    pub both_sizes: bool,
+    /// Add a column with the number of entries directly in each directory.
+    pub item_counts: bool,
+    /// Decimal places of the list's percentages; `None` picks by width.
+    pub percent_decimals: Option<u8>,
+    /// Show each entry's percentage of the whole scan rather than of the
+    /// directory it's in, so a deep entry's weight on the drive shows.
+    pub percent_of_root: bool,
+    /// Which part of a name too long for the list is cut.
+    pub truncation: Truncation,
+    /// Colors for directories and file categories.
+    pub theme: &'static Theme,
+    /// Storage price per GB-month, to show what the selected entry costs.
+    pub cost_per_gb_month: Option<f64>,
+    /// When sorted by size, list each directory's entries under this percent
+    /// of its size as one "… N more" row (see [`tree::small_tail`]).
+    pub group_below: Option<f64>,
+    /// Directories whose "… N more" row has been opened, listed in full.
+    expanded_groups: HashSet<PathBuf>,
+    /// Largest directories anywhere in the tree, shown as a popup when `Some`.
+    pub largest: Option<Vec<(String, u64)>>,
+    pub largest_state: ListState,
+    /// Directories bookmarked with `b`, by real path, in the order they were
+    /// marked. Bookmarks outside this scan are kept but not offered.
+    pub bookmarks: Vec<PathBuf>,
+    /// The bookmarks found in this scan, with their sizes, shown as a popup
+    /// when `Some`.
+    pub bookmark_list: Option<Vec<(PathBuf, u64)>>,
+    pub bookmark_state: ListState,
+    /// Search across the whole tree by name, shown as a popup when `Some`.
+    pub search: Option<Search>,
+    pub search_state: ListState,
+    /// Per-category totals for the current directory while that popup is open.
+    pub categories: Option<Vec<CategoryTotal>>,
+    /// Cache directories below the current directory, shown as a checklist
+    /// for bulk removal when `Some`.
+    pub caches: Option<Vec<CacheEntry>>,
+    pub caches_state: ListState,
+    /// Directory names the cache checklist looks for.
+    pub cache_names: Vec<String>,
+    /// Paths never to delete on top of the system ones (see
+    /// [`is_protected_path`]), unless `allow_protected` is set.
+    pub protected_paths: Vec<PathBuf>,
+    pub allow_protected: bool,
+    /// Wrap displayed names in OSC 8 hyperlinks to their `file://` URL.
+    pub hyperlinks: bool,
+    /// Draw in color; see [`strip_colors`].
+    pub color: bool,
+    /// One-line hint shown at the right of the footer (e.g. why the scan was slow).
+    pub notice: Option<String>,
+    /// How long the scan took, when this session ran one.
+    pub scan_secs: Option<f64>,
+    /// CPU time, user and system, the process spent during that scan.
+    pub scan_cpu_secs: Option<f64>,
+    /// Entries left out by exclusion patterns during that scan.
+    pub excluded: u64,
+    /// Directories that scan skipped as already entered (bind mounts, mount
+    /// loops).
+    pub cycles: u64,
+    /// Entries that scan couldn't read.
+    pub errors: u64,
+    /// The drive this session scanned whole, when it did; its free and total
+    /// space are shown in the title.
+    pub drive: Option<DriveInfo>,
+    /// How much of that drive's used space the scan didn't find, through
+    /// unreadable directories or exclusions; see
+    /// [`disku_core::utils::volume_usage`].
+    pub unaccounted: Option<u64>,
+    /// How much of that drive's used space macOS can reclaim on its own,
+    /// shown apart from (and left out of) `unaccounted`; see
+    /// [`disku_core::utils::purgeable_space`].
+    pub purgeable: Option<u64>,
+    /// Show the current directory as a treemap instead of a list.
+    pub treemap: bool,
+    /// Color list rows by size and age together (see [`heat_color`]), with
+    /// the age column shown, so big stale entries stand out from big active
+    /// ones.
+    pub heatmap: bool,
+    /// How `d` removes the selected entry.
+    pub delete_mode: DeleteMode,
+    /// Why nothing may be deleted, when it may not: the paths aren't on this
+    /// machine (a remote scan), or the files are left out of the tree, so
+    /// totals couldn't be recomputed after a delete.
+    pub read_only: Option<&'static str>,
+    /// The tree came from a local scan run this session, so `R` can scan its
+    /// root again.
+    pub rescannable: bool,
+    /// Asking to confirm deleting the selected entry.
+    pub confirming_delete: bool,
+    /// What deleting the selected entry would free (see
+    /// [`tree::reclaimable`]), worked out when the delete is requested.
+    pub reclaimable: u64,
+    /// Child index and screen area of every block in the last treemap drawn,
+    /// for mapping mouse clicks back to entries.
+    treemap_cells: Vec<(usize, Rect)>,
+}
+
+/// A row of the cache checklist.
+pub struct CacheEntry {
+    /// Nav path from the scan root.
+    pub nav_path: Vec<usize>,
+    /// Path relative to the directory the checklist was opened in.
+    pub rel: String,
+    pub size: u64,
+    pub checked: bool,
 }
 
+/// The search popup's query and its results.
+pub struct Search {
+    pub query: String,
+    pub mode: SearchMode,
+    /// Match the query as a glob over paths from the scan root, optionally
+    /// followed by a minimum size (`*.log >10M`), instead of by name in
+    /// `mode`. See [`tree::glob`].
+    pub glob: bool,
+    /// Best matches first, with nav paths from the scan root.
+    pub hits: Vec<SearchHit>,
+    /// Why a glob query found nothing, when it doesn't parse.
+    pub error: Option<String>,
+}
+
+const LARGEST_DIRS_COUNT: usize = 20;
+/// Most results the search popup lists.
+const SEARCH_RESULTS: usize = 100;
+
 impl App {
-    pub fn new(root: FileNode) -> Self {
+    pub fn new(scan: ScanResult) -> Self {
         let mut list_state = ListState::default();
-        if !root.children.is_empty() {
+        if !scan.tree.children.is_empty() {
             list_state.select(Some(0));
         }
         Self {
-            tree: root,
+            scan,
             nav_path: Vec::new(),
             list_state,
-            sort_by_size: true,
+            sort: SortKey::Size,
+            own_sizes: false,
+            both_sizes: false,
+            item_counts: false,
+            percent_decimals: None,
+            percent_of_root: false,
+            truncation: Truncation::Tail,
+            theme: Theme::new(Palette::Default),
+            cost_per_gb_month: None,
+            group_below: None,
+            expanded_groups: HashSet::new(),
+            largest: None,
+            largest_state: ListState::default(),
+            bookmarks: Vec::new(),
+            bookmark_list: None,
+            bookmark_state: ListState::default(),
+            search: None,
+            search_state: ListState::default(),
+            categories: None,
+            caches: None,
+            caches_state: ListState::default(),
+            cache_names: tree::DEFAULT_CACHE_NAMES.iter().map(|n| n.to_string()).collect(),
+            protected_paths: Vec::new(),
+            allow_protected: false,
+            hyperlinks: false,
+            color: true,
+            notice: None,
+            scan_secs: None,
+            scan_cpu_secs: None,
+            excluded: 0,
+            cycles: 0,
+            errors: 0,
+            drive: None,
+            unaccounted: None,
+            purgeable: None,
+            treemap: false,
+            heatmap: false,
+            delete_mode: DeleteMode::new(false),
+            read_only: None,
+            rescannable: false,
+            confirming_delete: false,
+            reclaimable: 0,
+            treemap_cells: Vec::new(),
         }
     }
 
     pub fn current(&self) -> &FileNode {
-        let mut node = &self.tree;
+        let mut node = &self.scan.tree;
         for &idx in &self.nav_path {
             node = &node.children[idx];
         }
@@ -37,176 +230,2028 @@ impl App {
     }
 
     fn current_mut(&mut self) -> &mut FileNode {
-        let mut node = &mut self.tree;
+        let mut node = &mut self.scan.tree;
         for &idx in &self.nav_path {
             node = &mut node.children[idx];
         }
         node
     }
 
+    /// Display form of [`current_real_path`](Self::current_real_path).
     pub fn current_path(&self) -> String {
-        let mut parts = vec![self.tree.name.clone()];
-        let mut node = &self.tree;
-        for &idx in &self.nav_path {
-            node = &node.children[idx];
-            parts.push(node.name.clone());
+        self.current_real_path().display().to_string()
+    }
+
+    /// Real filesystem path of the directory currently being viewed.
+    pub fn current_real_path(&self) -> PathBuf {
+        self.scan
+            .path_of(&self.nav_path)
+            .unwrap_or_else(|| self.scan.root_path.clone())
+    }
+
+    /// The highlighted entry (or the file itself for a single-file scan).
+    fn selected_entry(&self) -> Option<&FileNode> {
+        let current = self.current();
+        if !current.is_dir {
+            return Some(current);
+        }
+        let i = self.list_state.selected()?;
+        if self.group_start().is_some_and(|start| i >= start) {
+            return None;
+        }
+        current.children.get(i)
+    }
+
+    /// Index of the current directory's "… N more" row, which stands in for
+    /// every entry from there on; None when everything is listed.
+    fn group_start(&self) -> Option<usize> {
+        let percent = self.group_below?;
+        let current = self.current();
+        if !current.is_dir || self.treemap || self.sort != SortKey::Size {
+            return None;
+        }
+        if self.expanded_groups.contains(&self.current_real_path()) {
+            return None;
+        }
+        tree::small_tail(current, percent)
+    }
+
+    /// Number of rows the current directory's list has.
+    fn row_count(&self) -> usize {
+        let len = self.current().children.len();
+        self.group_start().map_or(len, |start| start + 1)
+    }
+
+    /// List the current directory in full if the selected entry is folded
+    /// into its "… N more" row, as after jumping to it.
+    fn reveal_selected(&mut self) {
+        let Some(i) = self.list_state.selected() else {
+            return;
+        };
+        if self.group_start().is_some_and(|start| i >= start) {
+            self.expanded_groups.insert(self.current_real_path());
+        }
+    }
+
+    /// Real path of [`selected_entry`](Self::selected_entry).
+    fn selected_path(&self) -> Option<PathBuf> {
+        if !self.current().is_dir {
+            return self.scan.path_of(&self.nav_path);
+        }
+        let i = self.list_state.selected()?;
+        self.scan.path_of(&[self.nav_path.as_slice(), &[i]].concat())
+    }
+
+    /// Filesystem type mounted at the highlighted directory, when the scan
+    /// crossed onto a different filesystem there.
+    fn selected_mount(&self) -> Option<&str> {
+        if self.scan.mounts.is_empty() {
+            return None;
+        }
+        self.selected_entry().filter(|e| e.is_dir)?;
+        let path = self.selected_path()?;
+        let mount = self.scan.mount_at(&path)?;
+        Some(mount.fs_type.as_deref().unwrap_or("other"))
+    }
+
+    pub fn move_up(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            if i > 0 {
+                self.list_state.select(Some(i - 1));
+            }
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            if i + 1 < self.row_count() {
+                self.list_state.select(Some(i + 1));
+            }
+        }
+    }
+
+    pub fn enter(&mut self) {
+        if let Some(i) = self.list_state.selected() {
+            if self.group_start() == Some(i) {
+                self.expanded_groups.insert(self.current_real_path());
+                return;
+            }
+            let current = self.current();
+            if let Some(child) = current.children.get(i) {
+                if child.is_dir && !child.children.is_empty() {
+                    self.nav_path.push(i);
+                    self.list_state.select(Some(0));
+                }
+            }
+        }
+    }
+
+    /// Carry the view of `previous`, an earlier scan of the same root, over
+    /// to this one: the display toggles and sort order, and the directory and
+    /// entry that were open, or the nearest parent still there.
+    pub fn restore_view(&mut self, previous: &App) {
+        self.own_sizes = previous.own_sizes;
+        self.both_sizes = previous.both_sizes;
+        self.item_counts = previous.item_counts;
+        self.percent_of_root = previous.percent_of_root;
+        self.treemap = previous.treemap;
+        self.heatmap = previous.heatmap;
+        if self.sort != previous.sort {
+            self.set_sort(previous.sort);
+        }
+        let mut dir = previous.current_real_path();
+        self.nav_path = loop {
+            if let Some(nav_path) = self.scan.find_nav_path(&dir) {
+                break nav_path;
+            }
+            if !dir.pop() {
+                return;
+            }
+        };
+        let selected = previous.selected_entry().map(|e| e.name.clone());
+        let current = self.current();
+        let idx = selected
+            .and_then(|name| current.children.iter().position(|c| c.name == name))
+            .or((!current.children.is_empty()).then_some(0));
+        self.list_state.select(idx);
+        self.expanded_groups = previous.expanded_groups.clone();
+        self.bookmarks = previous.bookmarks.clone();
+        self.reveal_selected();
+    }
+
+    pub fn go_back(&mut self) {
+        if !self.nav_path.is_empty() {
+            self.nav_path.pop();
+            self.list_state.select(Some(0));
+        }
+    }
+
+    pub fn open_largest_dirs(&mut self) {
+        let ranked = tree::largest_dirs(&self.scan.tree, LARGEST_DIRS_COUNT);
+        self.largest_state
+            .select(if ranked.is_empty() { None } else { Some(0) });
+        self.largest = Some(ranked);
+    }
+
+    pub fn open_categories(&mut self) {
+        self.categories = Some(tree::aggregate_by_category(self.current()));
+    }
+
+    pub fn close_categories(&mut self) {
+        self.categories = None;
+    }
+
+    pub fn close_largest_dirs(&mut self) {
+        self.largest = None;
+    }
+
+    pub fn largest_move_up(&mut self) {
+        if let Some(i) = self.largest_state.selected() {
+            if i > 0 {
+                self.largest_state.select(Some(i - 1));
+            }
+        }
+    }
+
+    pub fn largest_move_down(&mut self) {
+        let len = self.largest.as_ref().map_or(0, |l| l.len());
+        if let Some(i) = self.largest_state.selected() {
+            if i + 1 < len {
+                self.largest_state.select(Some(i + 1));
+            }
+        }
+    }
+
+    /// Navigate to the directory selected in the largest-directories popup.
+    pub fn jump_to_largest(&mut self) {
+        let Some(ranked) = self.largest.take() else {
+            return;
+        };
+        let Some((rel, _)) = self.largest_state.selected().and_then(|i| ranked.get(i)) else {
+            return;
+        };
+        let target = self.scan.root_path.join(rel);
+        if let Some(nav_path) = self.scan.find_nav_path(&target) {
+            self.nav_path = nav_path;
+            let has_children = !self.current().children.is_empty();
+            self.list_state.select(if has_children { Some(0) } else { None });
+        }
+    }
+
+    /// Bookmark the current directory, or drop its bookmark if it has one.
+    pub fn toggle_bookmark(&mut self) {
+        if !self.current().is_dir {
+            return;
+        }
+        let path = self.current_real_path();
+        let name = path.display().to_string();
+        if let Some(i) = self.bookmarks.iter().position(|b| *b == path) {
+            self.bookmarks.remove(i);
+            self.notice = Some(format!("removed bookmark {}", name));
+        } else {
+            self.bookmarks.push(path);
+            self.notice = Some(format!("bookmarked {}", name));
+        }
+    }
+
+    /// List the bookmarks this scan still has, with their current sizes.
+    /// Ones deleted or outside the scanned tree are left out.
+    pub fn open_bookmarks(&mut self) {
+        let found: Vec<(PathBuf, u64)> = self
+            .bookmarks
+            .iter()
+            .filter_map(|path| {
+                let nav_path = self.scan.find_nav_path(path)?;
+                let node = self.scan.node_at(&nav_path)?;
+                node.is_dir.then(|| (path.clone(), node.size))
+            })
+            .collect();
+        if found.is_empty() {
+            self.notice = Some("no bookmarks here (b marks a directory)".to_string());
+            return;
+        }
+        self.bookmark_state.select(Some(0));
+        self.bookmark_list = Some(found);
+    }
+
+    pub fn close_bookmarks(&mut self) {
+        self.bookmark_list = None;
+    }
+
+    pub fn bookmarks_move_up(&mut self) {
+        if let Some(i) = self.bookmark_state.selected() {
+            if i > 0 {
+                self.bookmark_state.select(Some(i - 1));
+            }
+        }
+    }
+
+    pub fn bookmarks_move_down(&mut self) {
+        let len = self.bookmark_list.as_ref().map_or(0, |l| l.len());
+        if let Some(i) = self.bookmark_state.selected() {
+            if i + 1 < len {
+                self.bookmark_state.select(Some(i + 1));
+            }
+        }
+    }
+
+    /// Navigate to the directory selected in the bookmarks popup.
+    pub fn jump_to_bookmark(&mut self) {
+        let Some(list) = self.bookmark_list.take() else {
+            return;
+        };
+        let Some((path, _)) = self.bookmark_state.selected().and_then(|i| list.get(i)) else {
+            return;
+        };
+        if let Some(nav_path) = self.scan.find_nav_path(path) {
+            self.nav_path = nav_path;
+            let has_children = !self.current().children.is_empty();
+            self.list_state.select(if has_children { Some(0) } else { None });
+        }
+    }
+
+    /// Drop the bookmark selected in the bookmarks popup, closing it once
+    /// none are left.
+    pub fn remove_bookmark(&mut self) {
+        let Some(list) = &mut self.bookmark_list else {
+            return;
+        };
+        let Some(i) = self.bookmark_state.selected().filter(|&i| i < list.len()) else {
+            return;
+        };
+        let (path, _) = list.remove(i);
+        self.bookmarks.retain(|b| *b != path);
+        if list.is_empty() {
+            self.bookmark_list = None;
+        } else {
+            self.bookmark_state.select(Some(i.min(list.len() - 1)));
+        }
+    }
+
+    pub fn open_search(&mut self) {
+        self.search = Some(Search {
+            query: String::new(),
+            mode: SearchMode::default(),
+            glob: false,
+            hits: Vec::new(),
+            error: None,
+        });
+        self.search_state.select(None);
+    }
+
+    pub fn close_search(&mut self) {
+        self.search = None;
+    }
+
+    pub fn search_push(&mut self, c: char) {
+        if let Some(search) = &mut self.search {
+            search.query.push(c);
+        }
+        self.run_search();
+    }
+
+    pub fn search_pop(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.query.pop();
+        }
+        self.run_search();
+    }
+
+    /// Cycle the search through fuzzy, exact and glob matching.
+    pub fn toggle_search_mode(&mut self) {
+        if let Some(search) = &mut self.search {
+            match (search.glob, search.mode.next()) {
+                (true, _) => {
+                    search.glob = false;
+                    search.mode = SearchMode::default();
+                }
+                (false, next) if next == SearchMode::default() => search.glob = true,
+                (false, next) => search.mode = next,
+            }
+        }
+        self.run_search();
+    }
+
+    /// Refresh the search results for the current query and mode.
+    fn run_search(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        search.error = None;
+        search.hits = if search.glob {
+            glob_hits(&self.scan, &search.query).unwrap_or_else(|e| {
+                search.error = Some(e);
+                Vec::new()
+            })
+        } else {
+            tree::search(&self.scan.tree, &search.query, search.mode, SEARCH_RESULTS)
+        };
+        self.search_state
+            .select(if search.hits.is_empty() { None } else { Some(0) });
+    }
+
+    pub fn search_move_up(&mut self) {
+        if let Some(i) = self.search_state.selected() {
+            if i > 0 {
+                self.search_state.select(Some(i - 1));
+            }
+        }
+    }
+
+    pub fn search_move_down(&mut self) {
+        let len = self.search.as_ref().map_or(0, |s| s.hits.len());
+        if let Some(i) = self.search_state.selected() {
+            if i + 1 < len {
+                self.search_state.select(Some(i + 1));
+            }
+        }
+    }
+
+    /// Navigate to the parent of the selected search result, with the
+    /// result selected.
+    pub fn jump_to_search_hit(&mut self) {
+        let Some(search) = self.search.take() else {
+            return;
+        };
+        let Some(hit) = self.search_state.selected().and_then(|i| search.hits.get(i)) else {
+            return;
+        };
+        let mut nav_path = hit.nav_path.clone();
+        let Some(idx) = nav_path.pop() else {
+            return;
+        };
+        self.nav_path = nav_path;
+        self.list_state.select(Some(idx));
+        self.reveal_selected();
+    }
+
+    /// List the cache directories below the current directory, all checked.
+    pub fn open_caches(&mut self) {
+        if let Some(reason) = self.read_only {
+            self.notice = Some(format!("{}: can't delete from here", reason));
+            return;
+        }
+        let found = tree::find_caches(self.current(), &self.cache_names);
+        if found.is_empty() {
+            self.notice = Some("no caches here".to_string());
+            return;
+        }
+        let entries = found
+            .into_iter()
+            .map(|(rel_nav, rel, size)| CacheEntry {
+                nav_path: [self.nav_path.as_slice(), &rel_nav].concat(),
+                rel,
+                size,
+                checked: true,
+            })
+            .collect();
+        self.caches_state.select(Some(0));
+        self.caches = Some(entries);
+    }
+
+    pub fn close_caches(&mut self) {
+        self.caches = None;
+    }
+
+    pub fn caches_move_up(&mut self) {
+        if let Some(i) = self.caches_state.selected() {
+            if i > 0 {
+                self.caches_state.select(Some(i - 1));
+            }
+        }
+    }
+
+    pub fn caches_move_down(&mut self) {
+        let len = self.caches.as_ref().map_or(0, |c| c.len());
+        if let Some(i) = self.caches_state.selected() {
+            if i + 1 < len {
+                self.caches_state.select(Some(i + 1));
+            }
+        }
+    }
+
+    pub fn toggle_cache(&mut self) {
+        let selected = self.caches_state.selected();
+        if let Some(entry) = self
+            .caches
+            .as_mut()
+            .zip(selected)
+            .and_then(|(caches, i)| caches.get_mut(i))
+        {
+            entry.checked = !entry.checked;
+        }
+    }
+
+    /// Check every row, or uncheck them all if they already are.
+    pub fn toggle_all_caches(&mut self) {
+        if let Some(caches) = self.caches.as_mut() {
+            let check = !caches.iter().all(|c| c.checked);
+            for entry in caches {
+                entry.checked = check;
+            }
+        }
+    }
+
+    /// Remove every checked cache from disk, and from the tree for those that
+    /// went. The outcome is reported through `notice`.
+    pub fn remove_checked_caches(&mut self) {
+        let Some(caches) = self.caches.take() else {
+            return;
+        };
+        let mut checked: Vec<CacheEntry> = caches.into_iter().filter(|c| c.checked).collect();
+        // Later siblings first, so removing one never shifts the indices of
+        // those still to go
+        checked.sort_unstable_by(|a, b| b.nav_path.cmp(&a.nav_path));
+
+        let mut removed = Vec::new();
+        let mut freed = 0;
+        let mut failed = None;
+        for entry in checked {
+            let Some(path) = self.scan.path_of(&entry.nav_path) else {
+                continue;
+            };
+            if self.is_protected(&path) {
+                failed.get_or_insert_with(|| format!("{} is protected", entry.rel));
+                continue;
+            }
+            if let Err(e) = remove_path(&path, self.delete_mode) {
+                failed.get_or_insert_with(|| format!("couldn't delete {}: {}", entry.rel, e));
+                continue;
+            }
+            let Some((&i, parent)) = entry.nav_path.split_last() else {
+                continue;
+            };
+            let mut node = &mut self.scan.tree;
+            for &idx in parent {
+                node = &mut node.children[idx];
+            }
+            node.children.remove(i);
+            freed += entry.size;
+            removed.push(parent.to_vec());
+        }
+        // Totals change all the way up to the root
+        for parent in &removed {
+            refresh_path_totals(&mut self.scan.tree, parent);
+        }
+
+        self.clamp_selection(self.list_state.selected().unwrap_or(0));
+        let done = match self.delete_mode {
+            DeleteMode::Trash => "moved to Trash",
+            DeleteMode::Permanent => "deleted",
+        };
+        self.notice = Some(match failed {
+            Some(err) => format!("{} {} caches; {}", done, removed.len(), err),
+            None => format!("{} {} caches, {}", done, removed.len(), format_size(freed)),
+        });
+    }
+
+    /// Navigate to the parent of the largest file anywhere below the current
+    /// directory and select it.
+    pub fn jump_to_largest_file(&mut self) {
+        let Some((rel, _)) = tree::top_files(self.current(), 1).into_iter().next() else {
+            return;
+        };
+        let target = self.current_real_path().join(rel);
+        let Some(mut nav_path) = self.scan.find_nav_path(&target) else {
+            return;
+        };
+        let Some(file_idx) = nav_path.pop() else {
+            return;
+        };
+        self.nav_path = nav_path;
+        self.list_state.select(Some(file_idx));
+        self.reveal_selected();
+    }
+
+    /// Ask to delete the selected entry; [`delete_selected`](Self::delete_selected)
+    /// does it once confirmed.
+    pub fn request_delete(&mut self) {
+        if let Some(reason) = self.read_only {
+            self.notice = Some(format!("{}: can't delete from here", reason));
+            return;
+        }
+        // The scan root itself (a single-file scan) is never offered
+        if !self.current().is_dir || self.selected_entry().is_none() {
+            return;
+        }
+        if let Some(path) = self.selected_path().filter(|p| self.is_protected(p)) {
+            self.notice = Some(protected_notice(&path));
+            return;
+        }
+        if let Some(i) = self.list_state.selected() {
+            let nav_path = [self.nav_path.as_slice(), &[i]].concat();
+            self.reclaimable = tree::reclaimable(&self.scan.tree, &nav_path);
+            self.confirming_delete = true;
+        }
+    }
+
+    /// Whether deleting `path` is refused: it's a system location or one of
+    /// `protected_paths`, and that hasn't been overridden.
+    fn is_protected(&self, path: &Path) -> bool {
+        !self.allow_protected && is_protected_path(path, &self.protected_paths)
+    }
+
+    pub fn cancel_delete(&mut self) {
+        self.confirming_delete = false;
+    }
+
+    /// Remove the selected entry from disk and, if that worked, from the tree.
+    /// The outcome is reported through `notice`.
+    pub fn delete_selected(&mut self) {
+        self.confirming_delete = false;
+        let Some(i) = self.list_state.selected() else {
+            return;
+        };
+        let Some(child) = self.current().children.get(i) else {
+            return;
+        };
+        let name = child.name.to_string();
+        let Some(path) = self.selected_path() else {
+            return;
+        };
+        if self.is_protected(&path) {
+            self.notice = Some(protected_notice(&path));
+            return;
+        }
+
+        if let Err(e) = remove_path(&path, self.delete_mode) {
+            self.notice = Some(format!("couldn't delete {}: {}", name, e));
+            return;
+        }
+
+        self.current_mut().children.remove(i);
+        // Totals change all the way up to the root
+        let nav_path = self.nav_path.clone();
+        refresh_path_totals(&mut self.scan.tree, &nav_path);
+        self.clamp_selection(i);
+        self.notice = Some(match self.delete_mode {
+            DeleteMode::Trash => format!("moved {} to Trash", name),
+            DeleteMode::Permanent => format!("deleted {}", name),
+        });
+    }
+
+    /// Select row `i` of the current directory's list, or its last row if
+    /// it has fewer.
+    fn clamp_selection(&mut self, i: usize) {
+        let rows = self.row_count();
+        self.list_state
+            .select(if rows == 0 { None } else { Some(i.min(rows - 1)) });
+    }
+
+    pub fn toggle_treemap(&mut self) {
+        self.treemap = !self.treemap;
+        self.treemap_cells.clear();
+        // A block picked in the treemap may be folded away in the list
+        self.reveal_selected();
+    }
+
+    /// Handle a click at screen cell (`x`, `y`) in the treemap: select the
+    /// block under it, or open it if it was already selected.
+    pub fn click_treemap(&mut self, x: u16, y: u16) {
+        let hit = self
+            .treemap_cells
+            .iter()
+            .find(|(_, r)| x >= r.left() && x < r.right() && y >= r.top() && y < r.bottom())
+            .map(|&(i, _)| i);
+        let Some(i) = hit else {
+            return;
+        };
+        if self.list_state.selected() == Some(i) {
+            self.enter();
+        } else {
+            self.list_state.select(Some(i));
+        }
+    }
+
+    pub fn toggle_sort(&mut self) {
+        self.set_sort(self.sort.next());
+    }
+
+    /// Re-sort the current directory by `sort`.
+    pub fn set_sort(&mut self, sort: SortKey) {
+        self.sort = sort;
+        let current = self.current_mut();
+        match sort {
+            SortKey::Size => current.sort_by_size(),
+            SortKey::Name => current.sort_by_name(),
+            SortKey::Recent => current.sort_by_recency(),
+        }
+        if let Some(i) = self.list_state.selected() {
+            self.clamp_selection(i);
+        }
+    }
+
+    pub fn toggle_own_sizes(&mut self) {
+        self.own_sizes = !self.own_sizes;
+    }
+
+    pub fn toggle_both_sizes(&mut self) {
+        self.both_sizes = !self.both_sizes;
+    }
+
+    pub fn toggle_item_counts(&mut self) {
+        self.item_counts = !self.item_counts;
+    }
+
+    pub fn toggle_heatmap(&mut self) {
+        self.heatmap = !self.heatmap;
+    }
+
+    pub fn toggle_percent_of_root(&mut self) {
+        self.percent_of_root = !self.percent_of_root;
+    }
+
+    /// What the list's percentages are of: the scan root's total, or the
+    /// current directory's.
+    fn percent_base(&self) -> u64 {
+        if self.percent_of_root {
+            self.scan.tree.size
+        } else {
+            self.current().size
+        }
+    }
+
+    /// Size to list `node` with under the current display mode.
+    fn shown_size(&self, node: &FileNode) -> u64 {
+        if self.own_sizes && node.is_dir {
+            own_size(node, |c| c.size)
+        } else {
+            node.size
+        }
+    }
+
+    /// Whether the list has a column with each entry's modification age.
+    fn shows_ages(&self) -> bool {
+        self.sort == SortKey::Recent || self.heatmap
+    }
+
+    /// The time modification ages are measured from, when that column is
+    /// shown.
+    fn age_reference(&self) -> Option<u64> {
+        self.shows_ages().then(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        })
+    }
+
+    /// How list rows `width` cells wide are drawn under the current display
+    /// mode.
+    fn row_format(&self, width: usize) -> RowFormat<'static> {
+        RowFormat {
+            width,
+            decimals: self.percent_decimals(width),
+            now: self.age_reference(),
+            counts: self.item_counts,
+            heatmap: self.heatmap,
+            truncation: self.truncation,
+            theme: self.theme,
         }
-        parts.join(std::path::MAIN_SEPARATOR_STR)
     }
 
-    pub fn move_up(&mut self) {
-        if let Some(i) = self.list_state.selected() {
-            if i > 0 {
-                self.list_state.select(Some(i - 1));
-            }
+    /// Width of everything right of the name in list rows `width` cells wide,
+    /// under the current display mode.
+    fn right_width(&self, width: usize) -> usize {
+        let decimals = self.percent_decimals(width);
+        right_width(decimals, self.both_sizes, self.shows_ages(), self.item_counts)
+    }
+
+    /// Decimal places of the percentages in list rows `width` cells wide: as
+    /// configured, or else one unless the rows are narrow.
+    fn percent_decimals(&self, width: usize) -> usize {
+        match self.percent_decimals {
+            Some(n) => usize::from(n),
+            None if width < NARROW_LIST_WIDTH => 0,
+            None => 1,
+        }
+    }
+
+    /// On-disk size to list `node` with, when that column is shown.
+    fn shown_allocated(&self, node: &FileNode) -> Option<u64> {
+        if !self.both_sizes {
+            None
+        } else if self.own_sizes && node.is_dir {
+            Some(own_size(node, |c| c.allocated))
+        } else {
+            Some(node.allocated)
+        }
+    }
+}
+
+/// Why deleting `path` was refused, and how to get past that.
+fn protected_notice(path: &Path) -> String {
+    format!("{} is protected; run with --allow-protected to delete it", path.display())
+}
+
+/// Recompute totals for every directory from `node` down along `nav_path`,
+/// deepest first.
+fn refresh_path_totals(node: &mut FileNode, nav_path: &[usize]) {
+    if let Some((&first, rest)) = nav_path.split_first() {
+        refresh_path_totals(&mut node.children[first], rest);
+    }
+    node.update_totals();
+}
+
+/// Bytes (as measured by `size`) in the files directly inside `node`,
+/// excluding subdirectories.
+fn own_size(node: &FileNode, size: fn(&FileNode) -> u64) -> u64 {
+    saturating_sum(node.children.iter().filter(|c| !c.is_dir).map(size))
+}
+
+/// Text field for the "Scan Directory" prompt with Tab completion against
+/// the subdirectories of whatever directory the input currently names.
+///
+/// Directories are listed on a thread of their own, so a slow network share
+/// or a hung mount holds up only its completions, not the typing; call
+/// [`poll`](Self::poll) each frame to pick up a listing that's arrived.
+#[derive(Default)]
+pub struct PathInput {
+    pub text: String,
+    /// Directory part of `text` that `subdirs` is (or is being) read from,
+    /// so each directory is listed once rather than on every keystroke.
+    listed: Option<String>,
+    subdirs: Vec<String>,
+    /// The listing of `listed` while it's still being read. Replaced when
+    /// the directory part changes, so a stale listing is never shown.
+    listing: Option<mpsc::Receiver<Vec<String>>>,
+    /// Tab was pressed before the listing arrived; complete once it does.
+    complete_pending: bool,
+}
+
+/// Number of completion candidates shown under the input.
+const MAX_MATCHES: usize = 6;
+
+impl PathInput {
+    pub fn push(&mut self, c: char) {
+        self.text.push(c);
+        self.complete_pending = false;
+        self.refresh();
+    }
+
+    pub fn pop(&mut self) {
+        self.text.pop();
+        self.complete_pending = false;
+        self.refresh();
+    }
+
+    /// Split the input into its directory part (up to and including the last
+    /// separator) and the partially typed final component.
+    fn split(&self) -> (&str, &str) {
+        let cut = self
+            .text
+            .rfind(std::path::is_separator)
+            .map(|i| i + 1)
+            .unwrap_or(0);
+        self.text.split_at(cut)
+    }
+
+    /// Start re-reading the subdirectory list if the directory part changed.
+    fn refresh(&mut self) {
+        let (dir, _) = self.split();
+        if self.listed.as_deref() == Some(dir) {
+            return;
+        }
+        let dir = dir.to_string();
+        // A bare name is relative to the working directory
+        let read_from = PathBuf::from(if dir.is_empty() { "." } else { dir.as_str() });
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(list_subdirs(&read_from));
+        });
+        self.subdirs = Vec::new();
+        self.listing = Some(rx);
+        self.complete_pending = false;
+        self.listed = Some(dir);
+    }
+
+    /// Take the subdirectory list if it has arrived, finishing a Tab pressed
+    /// while it was on its way.
+    pub fn poll(&mut self) {
+        let Some(listing) = &self.listing else {
+            return;
+        };
+        match listing.try_recv() {
+            Ok(subdirs) => self.subdirs = subdirs,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {}
+        }
+        self.listing = None;
+        if std::mem::take(&mut self.complete_pending) {
+            self.complete();
+        }
+    }
+
+    /// Whether the subdirectory list is still being read.
+    pub fn is_listing(&self) -> bool {
+        self.listing.is_some()
+    }
+
+    /// Subdirectories matching the partial last component.
+    pub fn matches(&self) -> impl Iterator<Item = &str> {
+        let (_, partial) = self.split();
+        let partial = partial.to_lowercase();
+        self.subdirs
+            .iter()
+            .filter(move |n| n.to_lowercase().starts_with(&partial))
+            .map(String::as_str)
+    }
+
+    /// Complete the last component: a unique match is filled in with a
+    /// trailing separator, several matches extend to their common prefix.
+    pub fn complete(&mut self) {
+        self.refresh();
+        self.poll();
+        if self.is_listing() {
+            self.complete_pending = true;
+            return;
+        }
+        let matches: Vec<&str> = self.matches().collect();
+        let completion = match matches.as_slice() {
+            [] => return,
+            [only] => format!("{}{}", only, std::path::MAIN_SEPARATOR),
+            [first, rest @ ..] => {
+                let mut prefix: &str = first;
+                for m in rest {
+                    let common = prefix
+                        .char_indices()
+                        .zip(m.chars())
+                        .find(|((_, a), b)| !a.eq_ignore_ascii_case(b))
+                        .map(|((i, _), _)| i)
+                        .unwrap_or(prefix.len().min(m.len()));
+                    prefix = &prefix[..common];
+                }
+                prefix.to_string()
+            }
+        };
+        let (dir, partial) = self.split();
+        if completion.len() < partial.len() {
+            return;
+        }
+        self.text = format!("{}{}", dir, completion);
+        self.refresh();
+    }
+}
+
+/// Below this size the centered, bordered box leaves too little room for names
+/// and titles, so the main screens switch to a plain full-screen layout.
+const MIN_BOXED_WIDTH: u16 = 60;
+const MIN_BOXED_HEIGHT: u16 = 14;
+
+fn is_compact(area: Rect) -> bool {
+    area.width < MIN_BOXED_WIDTH || area.height < MIN_BOXED_HEIGHT
+}
+
+/// Where a main screen draws, and its frame: a centered bordered box on roomy
+/// terminals, or the whole terminal with just a title row on small ones.
+fn screen_area<'a>(full: Rect, percent_x: u16, percent_y: u16) -> (Rect, Block<'a>) {
+    if is_compact(full) {
+        (full, Block::default())
+    } else {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
+        (centered_rect(percent_x, percent_y, full), block)
+    }
+}
+
+/// Hits for a glob search query: a pattern for [`tree::glob`], optionally
+/// followed by `>SIZE` to leave out anything smaller. Errors say why the
+/// pattern doesn't parse.
+fn glob_hits(scan: &ScanResult, query: &str) -> Result<Vec<SearchHit>, String> {
+    let (pattern, min_size) = match query.rsplit_once(" >") {
+        Some((pattern, size)) => match parse_size(size.trim()) {
+            Some(min_size) => (pattern.trim_end(), min_size),
+            None => (query, 0),
+        },
+        None => (query, 0),
+    };
+    let found = tree::glob(&scan.tree, pattern, min_size)
+        .map_err(|e| format!("invalid glob: {}", e.kind()))?;
+    Ok(found
+        .into_iter()
+        .take(SEARCH_RESULTS)
+        .filter_map(|(rel, size)| {
+            Some(SearchHit {
+                nav_path: scan.find_nav_path(&scan.root_path.join(rel))?,
+                score: 0,
+                size,
+                matched: Vec::new(),
+            })
+        })
+        .collect())
+}
+
+/// Parse a size: bytes, or a number with a `K`, `M`, `G` or `T` unit in
+/// powers of 1024 as [`format_size`] shows them (`512`, `1.5G`, `10MB`).
+fn parse_size(value: &str) -> Option<u64> {
+    let upper = value.to_ascii_uppercase();
+    let value = upper.strip_suffix('B').unwrap_or(&upper);
+    let (number, shift) = match value.char_indices().last()? {
+        (i, 'K') => (&value[..i], 10),
+        (i, 'M') => (&value[..i], 20),
+        (i, 'G') => (&value[..i], 30),
+        (i, 'T') => (&value[..i], 40),
+        _ => (value, 0),
+    };
+    let bytes = number.trim_end().parse::<f64>().ok()? * (1u64 << shift) as f64;
+    (bytes >= 0.0 && bytes.is_finite()).then_some(bytes as u64)
+}
+
+fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
+    let vert = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(r);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vert[1])[1]
+}
+
+/// Width of the sliding block in the scanning bar.
+const SCAN_BAR_BLOCK: usize = 6;
+
+/// Scanner internals shown over the scanning screen with `--debug`.
+pub struct ScanDebug {
+    /// Directories the scanner has queued and not yet finished with.
+    pub pending_dirs: u64,
+    /// Most directories pending at once so far.
+    pub peak_pending: u64,
+    /// Worker threads the scan can use.
+    pub threads: usize,
+}
+
+/// `tick` advances once per redraw and drives the bar's animation; `rate` is
+/// entries (files and directories) per second, when known. `debug`, given
+/// with `--debug`, adds a line at the bottom with the scanner's queue depth.
+pub fn draw_scanning(
+    f: &mut Frame,
+    files_scanned: u64,
+    _errors: u64,
+    rate: Option<f64>,
+    stalled_at: Option<&str>,
+    tick: usize,
+    debug: Option<&ScanDebug>,
+) {
+    let (area, block) = screen_area(f.area(), 44, 30);
+    let block = block.title(" disku ");
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let rows = if stalled_at.is_some() { 9 } else { 6 };
+    let top = inner.height.saturating_sub(rows) / 2;
+    let mut lines: Vec<Line> = (0..top).map(|_| Line::from("")).collect();
+
+    let (status, status_color) = if files_scanned == 0 {
+        ("  initializing...", Color::Rgb(150, 150, 150))
+    } else {
+        ("  scanning...", Color::Rgb(100, 200, 255))
+    };
+
+    lines.push(Line::from(Span::styled(
+        status,
+        Style::default()
+            .fg(status_color)
+            .add_modifier(Modifier::BOLD),
+    )));
+    lines.push(Line::from(""));
+    let mut count_line = vec![Span::styled(
+        if files_scanned == 0 {
+            "  reading filesystem...".to_string()
+        } else {
+            format!("  {} files", files_scanned)
+        },
+        Style::default().fg(Color::Rgb(100, 100, 100)),
+    )];
+    if let Some(rate) = rate {
+        count_line.push(Span::styled(
+            format!("  {} entries/s", format_rate(rate)),
+            Style::default().fg(Color::Rgb(70, 70, 70)),
+        ));
+    }
+    lines.push(Line::from(count_line));
+    lines.push(Line::from(""));
+    lines.push(scan_bar(inner.width.saturating_sub(4) as usize, tick));
+    if let Some(path) = stalled_at {
+        let warn = Style::default().fg(Color::Rgb(230, 180, 80));
+        // Keep the tail of the path: the deepest components say where it's stuck
+        let max = (inner.width as usize).saturating_sub(4);
+        let shown: String = if path.chars().count() > max {
+            let skip = path.chars().count() - max.saturating_sub(1);
+            format!("~{}", path.chars().skip(skip).collect::<String>())
+        } else {
+            path.to_string()
+        };
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("  possibly stalled at", warn)));
+        lines.push(Line::from(Span::styled(format!("  {}", shown), warn)));
+        lines.push(Line::from(Span::styled(
+            "  q to cancel",
+            Style::default().fg(Color::Rgb(70, 70, 70)),
+        )));
+    }
+
+    f.render_widget(Paragraph::new(lines), inner);
+
+    if let Some(debug) = debug {
+        draw_scan_debug(f, debug, 0);
+    }
+}
+
+/// The scanning screen once the scan has found some bytes: the largest of the
+/// root's directories so far, from `preview` (see
+/// [`disku_core::scanner::LiveTotals`]), growing and reordering in place, over
+/// a status bar with the file count, rate and the directory being read.
+/// `stalled` marks that directory as where the scan seems stuck.
+pub fn draw_scan_preview(
+    f: &mut Frame,
+    preview: &FileNode,
+    files_scanned: u64,
+    rate: Option<f64>,
+    current: &str,
+    stalled: bool,
+    debug: Option<&ScanDebug>,
+) {
+    let full = f.area();
+    let status_rows = if debug.is_some() { 2 } else { 1 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(status_rows)])
+        .split(full);
+
+    let title = format!(
+        " disku  scanning {}  {} so far ",
+        preview.name(),
+        format_size(preview.size)
+    );
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
+    let inner = block.inner(chunks[0]);
+    f.render_widget(block, chunks[0]);
+
+    let width = inner.width as usize;
+    let bar_width = (width / 4).min(30);
+    let largest = preview.children.first().map_or(0, |c| c.size);
+    let lines: Vec<Line> = preview
+        .children
+        .iter()
+        .take(inner.height as usize)
+        .map(|dir| {
+            let right = format!(
+                "{:>9}  {:>5.1}%  ",
+                format_size(dir.size),
+                percent(dir.size, preview.size)
+            );
+            let name_max = width.saturating_sub(right.chars().count() + bar_width + 3);
+            let name = truncate_name(dir.name(), name_max, Truncation::Tail);
+            let pad = name_max.saturating_sub(name.chars().count());
+            let filled = if largest == 0 {
+                0
+            } else {
+                (dir.size as f64 / largest as f64 * bar_width as f64).round() as usize
+            };
+            Line::from(vec![
+                Span::raw(" "),
+                Span::styled(name, Style::default().fg(Color::Rgb(120, 170, 255))),
+                Span::raw(" ".repeat(pad + 2)),
+                Span::styled(right, Style::default().fg(Color::Rgb(200, 200, 200))),
+                Span::styled(
+                    "\u{2588}".repeat(filled.min(bar_width)),
+                    Style::default().fg(Color::Rgb(100, 150, 255)),
+                ),
+            ])
+        })
+        .collect();
+    f.render_widget(Paragraph::new(lines), inner);
+
+    let status_area = Rect {
+        y: chunks[1].y + chunks[1].height - 1,
+        height: 1,
+        ..chunks[1]
+    };
+    let mut counts = format!(" {} files", format_count(files_scanned));
+    if let Some(rate) = rate {
+        counts.push_str(&format!("  {} entries/s", format_rate(rate)));
+    }
+    let label = if stalled { "  possibly stalled at " } else { "  " };
+    let path_max = (status_area.width as usize)
+        .saturating_sub(counts.chars().count() + label.chars().count() + 1);
+    let path_color = if stalled {
+        Color::Rgb(230, 180, 80)
+    } else {
+        Color::Rgb(100, 100, 100)
+    };
+    let status = Line::from(vec![
+        Span::styled(counts, Style::default().fg(Color::Rgb(100, 200, 255))),
+        Span::styled(label, Style::default().fg(path_color)),
+        // The deepest components say the most about where the scan is
+        Span::styled(
+            truncate_name(current, path_max, Truncation::Head),
+            Style::default().fg(path_color),
+        ),
+    ]);
+    f.render_widget(
+        Paragraph::new(status).style(Style::default().bg(Color::Rgb(25, 25, 35))),
+        status_area,
+    );
+
+    if let Some(debug) = debug {
+        draw_scan_debug(f, debug, 1);
+    }
+}
+
+/// One line `above` rows up from the bottom of the screen with the scanner's
+/// queue depth. A queue that stays below the thread count means threads sit
+/// idle.
+fn draw_scan_debug(f: &mut Frame, debug: &ScanDebug, above: u16) {
+    let full = f.area();
+    if full.height <= above {
+        return;
+    }
+    let area = Rect {
+        y: full.y + full.height - 1 - above,
+        height: 1,
+        ..full
+    };
+    let starved = debug.pending_dirs < debug.threads as u64;
+    let color = if starved {
+        Color::Rgb(230, 180, 80)
+    } else {
+        Color::Rgb(120, 120, 120)
+    };
+    let text = format!(
+        " pending dirs {} (peak {})  threads {}",
+        debug.pending_dirs, debug.peak_pending, debug.threads
+    );
+    f.render_widget(
+        Paragraph::new(Span::styled(text, Style::default().fg(color))),
+        area,
+    );
+}
+
+/// An indeterminate progress bar: a block sliding back and forth along a
+/// track `width` cells wide.
+fn scan_bar(width: usize, tick: usize) -> Line<'static> {
+    if width <= SCAN_BAR_BLOCK {
+        return Line::from("");
+    }
+    let travel = width - SCAN_BAR_BLOCK;
+    let phase = tick % (2 * travel);
+    let pos = if phase < travel { phase } else { 2 * travel - phase };
+    Line::from(vec![
+        Span::raw("  "),
+        Span::styled(
+            "\u{2500}".repeat(pos),
+            Style::default().fg(Color::Rgb(50, 50, 60)),
+        ),
+        Span::styled(
+            "\u{2501}".repeat(SCAN_BAR_BLOCK),
+            Style::default().fg(Color::Rgb(100, 200, 255)),
+        ),
+        Span::styled(
+            "\u{2500}".repeat(travel - pos),
+            Style::default().fg(Color::Rgb(50, 50, 60)),
+        ),
+    ])
+}
+
+/// Compact entries-per-second figure: "850", "12.4k", "1.2M".
+fn format_rate(rate: f64) -> String {
+    if rate >= 1_000_000.0 {
+        format!("{:.1}M", rate / 1_000_000.0)
+    } else if rate >= 1_000.0 {
+        format!("{:.1}k", rate / 1_000.0)
+    } else {
+        format!("{:.0}", rate)
+    }
+}
+
+/// "scanned in 2.1s", with the CPU time spent as a share of that when known
+/// (well under 100% means the scan waited on the disk), plus how many
+/// entries were excluded or unreadable, how many directories were skipped
+/// as repeats, and how much of a whole drive's used space wasn't found and
+/// how much is purgeable, when there were any.
+pub fn scan_summary(
+    secs: f64,
+    cpu_secs: Option<f64>,
+    excluded: u64,
+    errors: u64,
+    cycles: u64,
+    unaccounted: Option<u64>,
+    purgeable: Option<u64>,
+) -> String {
+    let mut text = format!("scanned in {}", format_duration(secs));
+    if let Some(cpu) = cpu_secs.filter(|_| secs > 0.0) {
+        text.push_str(&format!(" ({:.0}% cpu)", cpu / secs * 100.0));
+    }
+    if excluded > 0 {
+        text.push_str(&format!(", {} excluded", excluded));
+    }
+    if errors > 0 {
+        text.push_str(&format!(", {} unreadable", errors));
+    }
+    match cycles {
+        0 => {}
+        1 => text.push_str(", 1 cycle skipped"),
+        n => text.push_str(&format!(", {} cycles skipped", n)),
+    }
+    if let Some(bytes) = unaccounted.filter(|&b| b > 0) {
+        text.push_str(&format!(", {} unaccounted", format_size(bytes)));
+    }
+    if let Some(bytes) = purgeable.filter(|&b| b > 0) {
+        text.push_str(&format!(", {} purgeable", format_size(bytes)));
+    }
+    text
+}
+
+pub fn draw(f: &mut Frame, app: &mut App) {
+    let (area, block) = screen_area(f.area(), 88, 90);
+    let boxed = !is_compact(f.area());
+
+    let path_str = app.current_path();
+    let current = app.current();
+    // Only worth mentioning when compression actually saved space; block slack
+    // makes on-disk size exceed logical size almost everywhere else.
+    let size_str = if app.both_sizes {
+        format!(
+            "{} ({} on disk, {} slack)",
+            format_size(current.size),
+            format_size(current.allocated),
+            slack(current.size, current.allocated)
+        )
+    } else if current.allocated < current.size {
+        format!(
+            "{} ({} on disk)",
+            format_size(current.size),
+            format_size(current.allocated)
+        )
+    } else {
+        format_size(current.size)
+    };
+    let count = app.current().children.len();
+    let sort_label = match (app.sort, app.own_sizes) {
+        (SortKey::Size, false) => "size",
+        (SortKey::Name, false) => "name",
+        (SortKey::Recent, false) => "recent",
+        (SortKey::Size, true) => "size, own files",
+        (SortKey::Name, true) => "name, own files",
+        (SortKey::Recent, true) => "recent, own files",
+    };
+
+    // Percentages of the parent directory, or of the whole scan (home)
+    let percent_label = if app.percent_of_root { "%\u{2302}" } else { "%\u{2191}" };
+    let title = if app.current().is_dir {
+        format!(
+            " {}  {}  {} items  [{}]  {} ",
+            path_str, size_str, count, sort_label, percent_label
+        )
+    } else {
+        format!(" {}  {}  file ", path_str, size_str)
+    };
+
+    let block = block.title(Span::styled(
+        title,
+        Style::default().fg(Color::Rgb(120, 120, 120)),
+    ));
+    let block = match &app.drive {
+        Some(drive) => block.title(
+            Line::from(Span::styled(
+                format!(
+                    " {} free of {} ",
+                    format_size(drive.free),
+                    format_size(drive.total)
+                ),
+                Style::default().fg(Color::Rgb(120, 120, 120)),
+            ))
+            .right_aligned(),
+        ),
+        None => block,
+    };
+
+    let block = if app.heatmap && !app.treemap && app.current().is_dir {
+        block.title_bottom(heat_legend(app.theme).right_aligned())
+    } else {
+        block
+    };
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    if app.hyperlinks {
+        // Breadcrumb: the path sits right after the leading space of the title
+        // (and the corner, when boxed)
+        let path_x = area.x + if boxed { 2 } else { 1 };
+        let path_width = (path_str.chars().count() as u16)
+            .min(area.right().saturating_sub(path_x + 1));
+        let url = file_url(&app.current_real_path());
+        hyperlink_cells(f.buffer_mut(), path_x, area.y, path_width, &url);
+    }
+
+    // Tiny panes give every row to the list
+    let footer_height = if inner.height >= 4 { 1 } else { 0 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(footer_height)])
+        .split(inner);
+
+    if app.current().is_dir && app.current().children.is_empty() {
+        draw_empty(f, app, chunks[0]);
+    } else if app.treemap && app.current().is_dir {
+        draw_treemap(f, app, chunks[0]);
+    } else {
+        draw_file_list(f, app, chunks[0]);
+    }
+    draw_footer(f, chunks[1]);
+    // Right side of the footer: a notice if there is one, otherwise the
+    // selected entry's details (the filesystem mounted there, its file ID
+    // when the scan captured them, its storage cost when there's a rate),
+    // otherwise the scan time
+    let dim = Color::Rgb(120, 120, 120);
+    let mut details = Vec::new();
+    if let Some(fs) = app.selected_mount() {
+        details.push(format!("{} mount", fs));
+    }
+    if let Some(id) = app.selected_entry().and_then(|e| e.file_id) {
+        details.push(format!("id {}", id));
+    }
+    if let (Some(rate), Some(entry)) = (app.cost_per_gb_month, app.selected_entry()) {
+        details.push(format_cost(entry.size, rate));
+    }
+    let status = match (&app.notice, details.is_empty()) {
+        (Some(notice), _) => Some((notice.clone(), Color::Rgb(200, 160, 60))),
+        (None, false) => Some((details.join("  "), dim)),
+        (None, true) => app.scan_secs.map(|secs| {
+            let (excluded, errors, cycles) = (app.excluded, app.errors, app.cycles);
+            let (unaccounted, purgeable) = (app.unaccounted, app.purgeable);
+            let summary = scan_summary(
+                secs,
+                app.scan_cpu_secs,
+                excluded,
+                errors,
+                cycles,
+                unaccounted,
+                purgeable,
+            );
+            // Missing entries make every total low; don't let that pass unseen
+            let color = if app.errors > 0 { Color::Rgb(200, 160, 60) } else { dim };
+            (summary, color)
+        }),
+    };
+    if let Some((text, color)) = status {
+        let width = (text.chars().count() as u16 + 1).min(chunks[1].width);
+        let area = Rect {
+            x: chunks[1].x + chunks[1].width - width,
+            width,
+            ..chunks[1]
+        };
+        f.render_widget(
+            Paragraph::new(Span::styled(text, Style::default().fg(color))),
+            area,
+        );
+    }
+
+    if app.largest.is_some() {
+        draw_largest_dirs(f, app);
+    }
+    if app.bookmark_list.is_some() {
+        draw_bookmarks(f, app);
+    }
+    if app.caches.is_some() {
+        draw_caches(f, app);
+    }
+    if app.search.is_some() {
+        draw_search(f, app);
+    }
+    if let Some(categories) = &app.categories {
+        draw_categories(f, categories, app.current().size, app.theme);
+    }
+    if app.confirming_delete {
+        if let Some(entry) = app.selected_entry() {
+            draw_confirm_delete(f, entry, app.reclaimable, app.delete_mode);
+        }
+    }
+}
+
+/// Most children laid out in the treemap; anything past this would be a
+/// sub-cell sliver anyway.
+const TREEMAP_MAX_ITEMS: usize = 256;
+
+/// Background shades cycled through so neighbouring blocks stay distinct.
+const TREEMAP_DIR_COLORS: [Color; 4] = [
+    Color::Rgb(40, 60, 100),
+    Color::Rgb(50, 75, 120),
+    Color::Rgb(35, 55, 85),
+    Color::Rgb(60, 85, 135),
+];
+const TREEMAP_FILE_COLORS: [Color; 4] = [
+    Color::Rgb(55, 55, 60),
+    Color::Rgb(70, 70, 75),
+    Color::Rgb(48, 48, 52),
+    Color::Rgb(82, 82, 88),
+];
+
+/// Draw the current directory's children as a squarified treemap, block area
+/// proportional to size, labelled with name and share where they fit.
+fn draw_treemap(f: &mut Frame, app: &mut App, area: Rect) {
+    let current = app.current();
+    let total = app.percent_base();
+    let selected = app.list_state.selected();
+
+    let mut order: Vec<usize> = (0..current.children.len())
+        .filter(|&i| current.children[i].size > 0)
+        .collect();
+    order.sort_unstable_by_key(|&i| Reverse(current.children[i].size));
+    order.truncate(TREEMAP_MAX_ITEMS);
+    let sizes: Vec<u64> = order.iter().map(|&i| current.children[i].size).collect();
+    let rects = treemap::squarify(&sizes, area);
+
+    let buf = f.buffer_mut();
+    let mut cells = Vec::with_capacity(order.len());
+    for (rank, (&i, rect)) in order.iter().zip(rects).enumerate() {
+        if rect.is_empty() {
+            continue;
+        }
+        let child = &current.children[i];
+        let palette = if child.is_dir { &TREEMAP_DIR_COLORS } else { &TREEMAP_FILE_COLORS };
+        let mut style = Style::default()
+            .bg(palette[rank % palette.len()])
+            .fg(Color::Rgb(210, 210, 210));
+        if selected == Some(i) {
+            style = style
+                .bg(Color::Rgb(100, 200, 255))
+                .fg(Color::Rgb(20, 20, 30))
+                .add_modifier(Modifier::BOLD);
+        }
+        buf.set_style(rect, style);
+
+        // Keep text off the last column so neighbouring labels don't run together
+        let text_width = rect.width.saturating_sub(1) as usize;
+        if text_width >= 2 {
+            let suffix = if child.is_dir { "/" } else { "" };
+            let label = format!("{}{}", display_name(child), suffix);
+            buf.set_stringn(rect.x, rect.y, &label, text_width, style);
+            if rect.height >= 2 {
+                let detail = format!(
+                    "{} {:.1}%",
+                    format_size(child.size),
+                    percent(child.size, total)
+                );
+                buf.set_stringn(rect.x, rect.y + 1, &detail, text_width, style);
+            }
+        }
+        cells.push((i, rect));
+    }
+    app.treemap_cells = cells;
+}
+
+fn draw_largest_dirs(f: &mut Frame, app: &mut App) {
+    let ranked = app.largest.as_deref().unwrap_or_default();
+    let hint = " enter jump  j/k nav  esc close";
+    let total = app.scan.tree.size;
+    draw_path_popup(f, " largest directories ", ranked, total, &mut app.largest_state, hint);
+}
+
+fn draw_bookmarks(f: &mut Frame, app: &mut App) {
+    let rows: Vec<(String, u64)> = app
+        .bookmark_list
+        .iter()
+        .flatten()
+        .map(|(path, size)| (path.display().to_string(), *size))
+        .collect();
+    let hint = " enter jump  b remove  j/k nav  esc close";
+    let total = app.scan.tree.size;
+    draw_path_popup(f, " bookmarks ", &rows, total, &mut app.bookmark_state, hint);
+}
+
+/// A popup listing paths with their sizes as a share of `total`, and `hint`
+/// at the bottom.
+fn draw_path_popup(
+    f: &mut Frame,
+    title: &str,
+    rows: &[(String, u64)],
+    total: u64,
+    state: &mut ListState,
+    hint: &str,
+) {
+    let area = centered_rect(70, 70, f.area());
+    f.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(title.to_string())
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(inner);
+
+    let available_width = chunks[0].width as usize;
+
+    let items: Vec<ListItem> = rows
+        .iter()
+        .map(|(rel, size)| {
+            let right = format!("{:>9}  {:>5.1}%", format_size(*size), percent(*size, total));
+            let name_max = available_width.saturating_sub(right.chars().count() + 2);
+            let name: String = if rel.chars().count() > name_max {
+                // Keep the tail: the deepest components are the interesting part
+                let skip = rel.chars().count() - name_max.saturating_sub(1);
+                format!("~{}", rel.chars().skip(skip).collect::<String>())
+            } else {
+                format!("{:<width$}", rel, width = name_max)
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::raw(" "),
+                Span::styled(name, Style::default().fg(Color::Rgb(120, 170, 255))),
+                Span::styled(right, Style::default().fg(Color::Rgb(200, 200, 200))),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .bg(Color::Rgb(35, 35, 50))
+            .add_modifier(Modifier::BOLD),
+    );
+
+    f.render_stateful_widget(list, chunks[0], state);
+
+    f.render_widget(
+        Paragraph::new(Line::from(Span::styled(
+            hint.to_string(),
+            Style::default().fg(Color::Rgb(60, 60, 60)),
+        ))),
+        chunks[1],
+    );
+}
+
+/// The search popup: the query, then matching entries with the matched
+/// characters of their names highlighted.
+fn draw_search(f: &mut Frame, app: &mut App) {
+    let Some(search) = &app.search else {
+        return;
+    };
+    let area = centered_rect(70, 70, f.area());
+    f.render_widget(Clear, area);
+
+    let mode = match search.mode {
+        _ if search.glob => "glob",
+        SearchMode::Fuzzy => "fuzzy",
+        SearchMode::Exact => "exact",
+    };
+    let block = Block::default()
+        .title(format!(" search ({}) ", mode))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
+
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Length(2),
+            Constraint::Min(3),
+            Constraint::Length(1),
+        ])
+        .split(inner);
+
+    f.render_widget(
+        Paragraph::new(Line::from(vec![
+            Span::styled(" / ", Style::default().fg(Color::Rgb(100, 200, 255))),
+            Span::raw(search.query.as_str()),
+            Span::styled("_", Style::default().fg(Color::Rgb(100, 100, 100))),
+        ])),
+        chunks[0],
+    );
+
+    let available_width = chunks[1].width as usize;
+    let items: Vec<ListItem> = search
+        .hits
+        .iter()
+        .map(|hit| search_hit_item(&app.scan.tree, hit, available_width))
+        .collect();
+
+    let list = List::new(items).highlight_style(
+        Style::default()
+            .bg(Color::Rgb(35, 35, 50))
+            .add_modifier(Modifier::BOLD),
+    );
+
+    f.render_stateful_widget(list, chunks[1], &mut app.search_state);
+
+    let hint = match &search.error {
+        Some(error) => Span::styled(
+            format!(" {}", error),
+            Style::default().fg(Color::Rgb(200, 160, 60)),
+        ),
+        None => Span::styled(
+            " enter jump  up/down nav  tab fuzzy/exact/glob  esc close",
+            Style::default().fg(Color::Rgb(60, 60, 60)),
+        ),
+    };
+    f.render_widget(Paragraph::new(Line::from(hint)), chunks[2]);
+}
+
+/// A search result row: the entry's parent directory dimmed, then its name
+/// with the matched characters highlighted, then its size.
+fn search_hit_item(root: &FileNode, hit: &SearchHit, width: usize) -> ListItem<'static> {
+    let mut node = root;
+    let mut parent = String::new();
+    for &i in &hit.nav_path {
+        if !std::ptr::eq(node, root) {
+            parent.push_str(&node.name);
+            parent.push(std::path::MAIN_SEPARATOR);
         }
+        node = &node.children[i];
     }
-
-    pub fn move_down(&mut self) {
-        if let Some(i) = self.list_state.selected() {
-            let len = self.current().children.len();
-            if i + 1 < len {
-                self.list_state.select(Some(i + 1));
-            }
+    let name = display_name(node);
+    let suffix = if node.is_dir { "/" } else { "" };
+
+    let right = format!("{:>9}", format_size(hit.size));
+    let name_len = name.chars().count() + suffix.len();
+    let room = width.saturating_sub(right.chars().count() + 2);
+    // Shorten the parent first, keeping its tail as in the largest-directories
+    // popup; the name is what matched
+    let parent_len = parent.chars().count();
+    let parent_room = room.saturating_sub(name_len);
+    let parent = if parent_len > parent_room {
+        let skip = parent_len - parent_room.saturating_sub(1);
+        if parent_room == 0 {
+            String::new()
+        } else {
+            format!("~{}", parent.chars().skip(skip).collect::<String>())
         }
-    }
+    } else {
+        parent
+    };
 
-    pub fn enter(&mut self) {
-        if let Some(i) = self.list_state.selected() {
-            let current = self.current();
-            if let Some(child) = current.children.get(i) {
-                if child.is_dir && !child.children.is_empty() {
-                    self.nav_path.push(i);
-                    self.list_state.select(Some(0));
-                }
-            }
+    let plain = Style::default().fg(Color::Rgb(120, 170, 255));
+    let matched = Style::default()
+        .fg(Color::Rgb(255, 200, 80))
+        .add_modifier(Modifier::BOLD);
+    let mut spans = vec![
+        Span::raw(" "),
+        Span::styled(parent.clone(), Style::default().fg(Color::Rgb(100, 100, 100))),
+    ];
+    let mut used = parent.chars().count();
+    for (i, c) in name.chars().enumerate() {
+        if used >= room {
+            break;
         }
+        let style = if hit.matched.contains(&i) { matched } else { plain };
+        spans.push(Span::styled(c.to_string(), style));
+        used += 1;
     }
-
-    pub fn go_back(&mut self) {
-        if !self.nav_path.is_empty() {
-            self.nav_path.pop();
-            self.list_state.select(Some(0));
-        }
+    if used < room {
+        spans.push(Span::styled(suffix, plain));
+        used += suffix.len();
     }
+    spans.push(Span::raw(" ".repeat(room.saturating_sub(used))));
+    spans.push(Span::styled(right, Style::default().fg(Color::Rgb(200, 200, 200))));
 
-    pub fn toggle_sort(&mut self) {
-        self.sort_by_size = !self.sort_by_size;
-        let by_size = self.sort_by_size;
-        let current = self.current_mut();
-        if by_size {
-            current.sort_by_size();
-        } else {
-            current.sort_by_name();
-        }
-    }
+    ListItem::new(Line::from(spans))
 }
 
-fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
-    let vert = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([
-            Constraint::Percentage((100 - percent_y) / 2),
-            Constraint::Percentage(percent_y),
-            Constraint::Percentage((100 - percent_y) / 2),
-        ])
-        .split(r);
-    Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([
-            Constraint::Percentage((100 - percent_x) / 2),
-            Constraint::Percentage(percent_x),
-            Constraint::Percentage((100 - percent_x) / 2),
-        ])
-        .split(vert[1])[1]
-}
+/// Checklist of the cache directories below the current directory.
+fn draw_caches(f: &mut Frame, app: &mut App) {
+    let caches = app.caches.as_deref().unwrap_or_default();
+    let (count, total) = caches
+        .iter()
+        .filter(|c| c.checked)
+        .fold((0, 0), |(n, size), c| (n + 1, size + c.size));
 
-pub fn draw_scanning(f: &mut Frame, files_scanned: u64, _errors: u64) {
-    let area = centered_rect(44, 30, f.area());
+    let area = centered_rect(70, 70, f.area());
+    f.render_widget(Clear, area);
 
     let block = Block::default()
-        .title(" disku ")
+        .title(format!(" caches: {} selected, {} ", count, format_size(total)))
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
 
     let inner = block.inner(area);
     f.render_widget(block, area);
 
-    let top = inner.height.saturating_sub(4) / 2;
-    let mut lines: Vec<Line> = (0..top).map(|_| Line::from("")).collect();
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .split(inner);
 
-    let (status, status_color) = if files_scanned == 0 {
-        ("  initializing...", Color::Rgb(150, 150, 150))
-    } else {
-        ("  scanning...", Color::Rgb(100, 200, 255))
-    };
+    let available_width = chunks[0].width as usize;
+    let items: Vec<ListItem> = caches
+        .iter()
+        .map(|c| {
+            let mark = if c.checked { "[x] " } else { "[ ] " };
+            let right = format!("{:>9}", format_size(c.size));
+            let name_max = available_width.saturating_sub(right.chars().count() + mark.len() + 2);
+            let name: String = if c.rel.chars().count() > name_max {
+                // Keep the tail, as in the largest-directories popup
+                let skip = c.rel.chars().count() - name_max.saturating_sub(1);
+                format!("~{}", c.rel.chars().skip(skip).collect::<String>())
+            } else {
+                format!("{:<width$}", c.rel, width = name_max)
+            };
+            let name_color = if c.checked {
+                Color::Rgb(120, 170, 255)
+            } else {
+                Color::Rgb(100, 100, 100)
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::raw(" "),
+                Span::styled(mark, Style::default().fg(Color::Rgb(100, 200, 255))),
+                Span::styled(name, Style::default().fg(name_color)),
+                Span::styled(right, Style::default().fg(Color::Rgb(200, 200, 200))),
+            ]))
+        })
+        .collect();
 
-    lines.push(Line::from(Span::styled(
-        status,
+    let list = List::new(items).highlight_style(
         Style::default()
-            .fg(status_color)
+            .bg(Color::Rgb(35, 35, 50))
             .add_modifier(Modifier::BOLD),
-    )));
-    lines.push(Line::from(""));
-    lines.push(Line::from(Span::styled(
-        if files_scanned == 0 {
-            "  reading filesystem...".to_string()
-        } else {
-            format!("  {} files", files_scanned)
-        },
-        Style::default().fg(Color::Rgb(100, 100, 100)),
-    )));
+    );
+    f.render_stateful_widget(list, chunks[0], &mut app.caches_state);
 
-    f.render_widget(Paragraph::new(lines), inner);
+    let hint = format!(
+        " space toggle  a all  enter {}  esc close",
+        app.delete_mode.verb().to_lowercase()
+    );
+    f.render_widget(
+        Paragraph::new(Line::from(Span::styled(
+            hint,
+            Style::default().fg(Color::Rgb(60, 60, 60)),
+        ))),
+        chunks[1],
+    );
 }
 
-pub fn draw(f: &mut Frame, app: &mut App) {
-    let area = centered_rect(88, 90, f.area());
-
-    let path_str = app.current_path();
-    let size_str = format_size(app.current().size);
-    let count = app.current().children.len();
-    let sort_label = if app.sort_by_size { "size" } else { "name" };
-
-    let title = format!(
-        " {}  {}  {} items  [{}] ",
-        path_str, size_str, count, sort_label
-    );
+/// Popup table of the current directory's size by file category, with a bar
+/// per row scaled to the directory total.
+fn draw_categories(f: &mut Frame, categories: &[CategoryTotal], total: u64, theme: &Theme) {
+    let height = (categories.len() as u16 + 4).min(f.area().height);
+    let width = 64.min(f.area().width);
+    let full = f.area();
+    let area = Rect {
+        x: full.x + (full.width - width) / 2,
+        y: full.y + (full.height - height) / 2,
+        width,
+        height,
+    };
+    f.render_widget(Clear, area);
 
     let block = Block::default()
-        .title(Span::styled(
-            title,
-            Style::default().fg(Color::Rgb(120, 120, 120)),
-        ))
+        .title(" by file type ")
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
-
     let inner = block.inner(area);
     f.render_widget(block, area);
 
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Min(3), Constraint::Length(1)])
+        .constraints([Constraint::Min(1), Constraint::Length(1)])
         .split(inner);
 
-    draw_file_list(f, app, chunks[0]);
-    draw_footer(f, chunks[1]);
+    // " label  bar  size  pct"
+    const LABEL_WIDTH: usize = 16;
+    let right = right_width(1, false, false, false);
+    let bar_width = (chunks[0].width as usize).saturating_sub(LABEL_WIDTH + right + 3);
+    let rows: Vec<Line> = if categories.is_empty() {
+        vec![Line::from(Span::styled(
+            " no files here",
+            Style::default().fg(Color::Rgb(100, 100, 100)),
+        ))]
+    } else {
+        categories
+            .iter()
+            .map(|c| {
+                let pct = percent(c.size, total);
+                let filled = ((pct / 100.0) * bar_width as f64).round() as usize;
+                let (glyph, bar_color) = theme.bar(c.category);
+                Line::from(vec![
+                    Span::styled(
+                        format!(" {:<width$} ", c.label, width = LABEL_WIDTH),
+                        Style::default().fg(Color::Rgb(180, 180, 180)),
+                    ),
+                    Span::styled(glyph.repeat(filled), Style::default().fg(bar_color)),
+                    Span::styled(
+                        "\u{2591}".repeat(bar_width - filled.min(bar_width)),
+                        Style::default().fg(Color::Rgb(50, 50, 60)),
+                    ),
+                    Span::styled(
+                        format!("{:>9}", format_size(c.size)),
+                        Style::default().fg(Color::Rgb(200, 200, 200)),
+                    ),
+                    Span::styled(
+                        format!("  {:>5.1}%", pct),
+                        Style::default().fg(Color::Rgb(100, 100, 100)),
+                    ),
+                ])
+            })
+            .collect()
+    };
+    f.render_widget(Paragraph::new(rows), chunks[0]);
+
+    f.render_widget(
+        Paragraph::new(Line::from(Span::styled(
+            " esc close",
+            Style::default().fg(Color::Rgb(60, 60, 60)),
+        ))),
+        chunks[1],
+    );
+}
+
+/// Popup asking to confirm deleting `entry`, worded for how it will go.
+fn draw_confirm_delete(f: &mut Frame, entry: &FileNode, reclaimable: u64, mode: DeleteMode) {
+    let full = f.area();
+    let width = 56.min(full.width);
+    let height = 6.min(full.height);
+    let area = Rect {
+        x: full.x + (full.width - width) / 2,
+        y: full.y + (full.height - height) / 2,
+        width,
+        height,
+    };
+    f.render_widget(Clear, area);
+
+    let (title, color) = match mode {
+        DeleteMode::Trash => (" move to trash ", Color::Rgb(200, 160, 60)),
+        DeleteMode::Permanent => (" permanently delete ", Color::Rgb(220, 90, 90)),
+    };
+    let block = Block::default()
+        .title(Span::styled(title, Style::default().fg(color)))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(color));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let kind = if entry.is_dir { "directory" } else { "file" };
+    let size_str = format_size(entry.size);
+    let name_max = (inner.width as usize).saturating_sub(size_str.len() + 4);
+    let name: String = if entry.name.chars().count() > name_max {
+        let truncated: String = entry.name.chars().take(name_max.saturating_sub(1)).collect();
+        format!("{}~", truncated)
+    } else {
+        entry.name.to_string()
+    };
+    let lines = vec![
+        Line::from(Span::styled(
+            format!(" {} this {}?", mode.verb(), kind),
+            Style::default().fg(Color::Rgb(220, 220, 220)),
+        )),
+        Line::from(vec![
+            Span::styled(
+                format!(" {}  ", name),
+                Style::default().fg(Color::Rgb(180, 180, 180)),
+            ),
+            Span::styled(size_str, Style::default().fg(Color::Rgb(140, 140, 140))),
+        ]),
+        Line::from(Span::styled(
+            match mode {
+                DeleteMode::Trash => {
+                    format!(" frees {} once the trash is emptied", format_size(reclaimable))
+                }
+                DeleteMode::Permanent => format!(" frees {}", format_size(reclaimable)),
+            },
+            Style::default().fg(Color::Rgb(140, 140, 140)),
+        )),
+        Line::from(vec![
+            Span::styled(" y", Style::default().fg(Color::Rgb(100, 200, 255))),
+            Span::styled(" confirm  ", Style::default().fg(Color::Rgb(65, 65, 65))),
+            Span::styled("n/esc", Style::default().fg(Color::Rgb(100, 200, 255))),
+            Span::styled(" cancel", Style::default().fg(Color::Rgb(65, 65, 65))),
+        ]),
+    ];
+    f.render_widget(Paragraph::new(lines), inner);
+}
+
+/// Stand-in for the list when the current directory has nothing in it. An
+/// empty scan root usually means it couldn't be read, so say so when the scan
+/// hit errors.
+fn draw_empty(f: &mut Frame, app: &App, area: Rect) {
+    let text = match app.errors {
+        n if n > 0 && app.nav_path.is_empty() => format!(
+            "empty or inaccessible \u{2014} {} error{}",
+            n,
+            if n == 1 { "" } else { "s" }
+        ),
+        _ => "empty".to_string(),
+    };
+    let width = text.chars().count() as u16;
+    let row = Rect::new(
+        area.x + area.width.saturating_sub(width) / 2,
+        area.y + area.height / 2,
+        width.min(area.width),
+        1.min(area.height),
+    );
+    f.render_widget(
+        Paragraph::new(Span::styled(text, Style::default().fg(Color::Rgb(120, 120, 120)))),
+        row,
+    );
 }
 
 fn draw_file_list(f: &mut Frame, app: &mut App, area: Rect) {
     let visible_height = area.height as usize;
     let current = app.current();
-    let total_size = current.size;
-    let total_children = current.children.len();
+
+    // A single-file scan target has no children; show the file itself as the only row.
+    if !current.is_dir {
+        let item = format_child_item(
+            current,
+            current.size,
+            current.size,
+            app.shown_allocated(current),
+            &app.row_format(area.width as usize),
+        );
+        f.render_widget(List::new(vec![item]), area);
+        if app.hyperlinks {
+            let width = area.width as usize;
+            let width = name_cells(current, width, app.right_width(width));
+            let url = file_url(&app.scan.root_path);
+            hyperlink_cells(f.buffer_mut(), area.x + ICON_WIDTH as u16, area.y, width, &url);
+        }
+        return;
+    }
+
+    let total_size = app.percent_base();
+    let group_start = app.group_start();
+    let total_rows = app.row_count();
     let available_width = area.width as usize;
 
     let selected = app.list_state.selected().unwrap_or(0);
     let window_start = selected.saturating_sub(visible_height);
-    let window_end = (window_start + visible_height * 3).min(total_children);
-
-    let items: Vec<ListItem> = current.children[window_start..window_end]
-        .iter()
-        .map(|child| format_child_item(child, total_size, available_width))
+    let window_end = (window_start + visible_height * 3).min(total_rows);
+    let row = app.row_format(available_width);
+
+    let items: Vec<ListItem> = (window_start..window_end)
+        .map(|i| match group_start {
+            Some(start) if i == start => group_item(app, &current.children[start..], &row),
+            _ => {
+                let child = &current.children[i];
+                format_child_item(
+                    child,
+                    app.shown_size(child),
+                    total_size,
+                    app.shown_allocated(child),
+                    &row,
+                )
+            }
+        })
         .collect();
 
     let list = List::new(items).highlight_style(
@@ -219,51 +2264,350 @@ fn draw_file_list(f: &mut Frame, app: &mut App, area: Rect) {
     windowed_state.select(Some(selected - window_start));
 
     f.render_stateful_widget(list, area, &mut windowed_state);
+
+    if app.hyperlinks {
+        let dir_path = app.current_real_path();
+        let current = app.current();
+        let first = window_start + windowed_state.offset();
+        // The "… N more" row isn't anything on disk to link to
+        let linked_end = group_start.map_or(window_end, |start| start.min(window_end));
+        for (row, child) in current.children[first.min(linked_end)..linked_end]
+            .iter()
+            .take(visible_height)
+            .enumerate()
+        {
+            let width = name_cells(child, available_width, app.right_width(available_width));
+            let url = file_url(&dir_path.join(child.os_name()));
+            hyperlink_cells(
+                f.buffer_mut(),
+                area.x + ICON_WIDTH as u16,
+                area.y + row as u16,
+                width,
+                &url,
+            );
+        }
+    }
 }
 
-fn format_child_item(child: &FileNode, total_size: u64, available_width: usize) -> ListItem<'static> {
-    let pct = percent(child.size, total_size);
-    let size_str = format_size(child.size);
+/// The row standing in for `folded`, the small entries at the end of the
+/// current directory: their count, combined size and newest modification.
+fn group_item(app: &App, folded: &[FileNode], row: &RowFormat) -> ListItem<'static> {
+    let size = saturating_sum(folded.iter().map(|c| app.shown_size(c)));
+    let allocated = app
+        .both_sizes
+        .then(|| saturating_sum(folded.iter().filter_map(|c| app.shown_allocated(c))));
+    let mtime = folded.iter().map(|c| c.mtime).max().unwrap_or(0);
+    let stand_in = FileNode::new_file(format!("\u{2026} {} more", folded.len()), size)
+        .with_mtime(mtime);
+    format_child_item(&stand_in, size, app.percent_base(), allocated, row)
+}
 
-    // Right side: "  1.23 GB   45.3%" -- fixed 18 chars
-    let right_width = 18usize;
-    // Icon: " + " = 3 chars
-    let icon_width = 3usize;
-    let name_max = available_width.saturating_sub(right_width + icon_width);
+/// Number of cells the (possibly truncated) name of `child` occupies in a row.
+fn name_cells(child: &FileNode, available_width: usize, right_width: usize) -> u16 {
+    let name_max = available_width.saturating_sub(right_width + ICON_WIDTH);
+    display_name(child).chars().count().min(name_max) as u16
+}
 
-    let name: String = if child.name.chars().count() > name_max {
-        let truncated: String = child.name.chars().take(name_max.saturating_sub(1)).collect();
-        format!("{}~", truncated)
+/// `child`'s name as listed: a collapsed trash folder is labeled as such.
+fn display_name(child: &FileNode) -> Cow<'_, str> {
+    if child.trash {
+        Cow::Owned(format!("{} (Trash)", child.name))
+    } else {
+        Cow::Borrowed(&child.name)
+    }
+}
+
+/// Drop every color from a drawn frame, for terminals that can't show them
+/// (or users who'd rather not). Cells that stood out by their background (the
+/// selection, treemap blocks) are shown in reverse video instead.
+pub fn strip_colors(buf: &mut Buffer) {
+    for cell in &mut buf.content {
+        if cell.bg != Color::Reset {
+            cell.modifier.insert(Modifier::REVERSED);
+        }
+        cell.fg = Color::Reset;
+        cell.bg = Color::Reset;
+    }
+}
+
+/// Wrap the `width` cells starting at (`x`, `y`) in an OSC 8 hyperlink to `url`.
+///
+/// ratatui counts escape sequences as printable width, so the link is written in
+/// two-cell chunks: the oversized symbol makes the diff skip exactly the next
+/// cell, whose glyph the chunk has already printed. Rows holding wide
+/// characters are left alone since the chunks would no longer line up.
+fn hyperlink_cells(buf: &mut Buffer, x: u16, y: u16, width: u16, url: &str) {
+    let area = buf.area;
+    if width == 0 || y >= area.bottom() || x >= area.right() {
+        return;
+    }
+    // Round up to whole chunks; a trailing padding cell is harmless inside a link
+    let end = (x + width + width % 2).min(area.right());
+    if buf.content[buf.index_of(x, y)..buf.index_of(end - 1, y) + 1]
+        .iter()
+        .any(|c| c.symbol().chars().count() != 1 || c.symbol().chars().any(char::is_control))
+    {
+        return;
+    }
+
+    let mut cx = x;
+    while cx < end {
+        let mut text = buf[(cx, y)].symbol().to_string();
+        if cx + 1 < end {
+            text.push_str(buf[(cx + 1, y)].symbol());
+        }
+        let linked = format!("\x1B]8;;{}\x07{}\x1B]8;;\x07", url, text);
+        buf[(cx, y)].set_symbol(&linked);
+        cx += 2;
+    }
+}
+
+// Size column: "1023.9 KB", right-aligned -- fixed 9 chars
+const SIZE_WIDTH: usize = 9;
+// Room left after the last column
+const RIGHT_MARGIN: usize = 1;
+// On-disk and slack columns: "  1.25 GB  +20.0 MB" -- fixed 22 chars
+const ON_DISK_WIDTH: usize = 22;
+// Modified column: "  11 months ago" -- fixed 16 chars
+const AGE_WIDTH: usize = 16;
+// Entry count column: "  (1,234,567)" -- fixed 13 chars
+const COUNT_WIDTH: usize = 13;
+// Icon: " + " = 3 chars
+const ICON_WIDTH: usize = 3;
+// List rows narrower than this show whole percentages unless told otherwise,
+// leaving the names two more cells
+const NARROW_LIST_WIDTH: usize = 60;
+
+/// Width of a percentage with `decimals` places, up to `100.0`.
+fn percent_field(decimals: usize) -> usize {
+    if decimals == 0 {
+        3
+    } else {
+        4 + decimals
+    }
+}
+
+/// `pct` with `decimals` places, right-aligned in its column: "   45.3%".
+fn percent_cell(pct: f64, decimals: usize) -> String {
+    format!("  {:>width$.decimals$}%", pct, width = percent_field(decimals))
+}
+
+/// A size right-aligned in its column. Sizes in bytes get a trailing space
+/// so their digits line up with those of "KB", "MB", ... sizes.
+fn size_cell(size: &str) -> String {
+    if size.ends_with(" B") {
+        format!("{:>width$} ", size, width = SIZE_WIDTH - 1)
+    } else {
+        format!("{:>width$}", size, width = SIZE_WIDTH)
+    }
+}
+
+/// Width of everything right of the name, with percentages to `decimals`
+/// places.
+fn right_width(decimals: usize, both_sizes: bool, ages: bool, counts: bool) -> usize {
+    let mut width = SIZE_WIDTH + 3 + percent_field(decimals) + RIGHT_MARGIN;
+    if both_sizes {
+        width += ON_DISK_WIDTH;
+    }
+    if ages {
+        width += AGE_WIDTH;
+    }
+    if counts {
+        width += COUNT_WIDTH;
+    }
+    width
+}
+
+/// `allocated` relative to `size`: block slack as `+N`, or what compression
+/// (or sparseness) saved as `-N`.
+fn slack(size: u64, allocated: u64) -> String {
+    if allocated >= size {
+        format!("+{}", format_size(allocated - size))
+    } else {
+        format!("-{}", format_size(size - allocated))
+    }
+}
+
+/// Settings shared by every row of the list.
+struct RowFormat<'a> {
+    width: usize,
+    /// Decimal places of the percentage column.
+    decimals: usize,
+    /// Add a column with how long before this time each entry was modified.
+    now: Option<u64>,
+    /// Add a column with each directory's number of entries.
+    counts: bool,
+    /// Color names and sizes by [`heat_color`] rather than by kind.
+    heatmap: bool,
+    truncation: Truncation,
+    theme: &'a Theme,
+}
+
+/// `name` cut to at most `max` characters as `mode` says, with `~` marking
+/// the cut.
+fn truncate_name(name: &str, max: usize, mode: Truncation) -> String {
+    let len = name.chars().count();
+    if len <= max {
+        return name.to_string();
+    }
+    let keep = max.saturating_sub(1);
+    let (head, tail) = match mode {
+        Truncation::Tail => (keep, 0),
+        Truncation::Middle => {
+            // The whole extension stays when there's room for it
+            let ext = name
+                .rfind('.')
+                .filter(|&i| i > 0)
+                .map_or(0, |i| name[i..].chars().count());
+            let tail = (keep - keep / 2).max(ext).min(keep);
+            (keep - tail, tail)
+        }
+        Truncation::Head => (0, keep),
+    };
+    let start: String = name.chars().take(head).collect();
+    let end: String = name.chars().skip(len - tail).collect();
+    format!("{}~{}", start, end)
+}
+
+/// One list row for `child`, showing `size` as a share of `total_size`, and
+/// `allocated` with the slack when given.
+fn format_child_item(
+    child: &FileNode,
+    size: u64,
+    total_size: u64,
+    allocated: Option<u64>,
+    row: &RowFormat,
+) -> ListItem<'static> {
+    let pct = percent(size, total_size);
+    // A time-limited scan never read this directory, so its size is unknown
+    let size_str = if child.unexpanded {
+        "?".to_string()
     } else {
-        format!("{:<width$}", child.name, width = name_max)
+        format_size(size)
     };
 
+    let right = right_width(row.decimals, allocated.is_some(), row.now.is_some(), row.counts);
+    let name_max = row.width.saturating_sub(right + ICON_WIDTH);
+
+    let name = format!(
+        "{:<width$}",
+        truncate_name(&display_name(child), name_max, row.truncation),
+        width = name_max
+    );
+
     let icon = if child.is_dir { "+" } else { " " };
-    let name_color = if child.is_dir {
-        Color::Rgb(120, 170, 255)
+    let heat = match row.now {
+        Some(now) if row.heatmap => Some(heat_color(row.theme, pct, child.mtime, now)),
+        _ => None,
+    };
+    let name_color = if let Some(heat) = heat {
+        heat
+    } else if child.is_dir {
+        row.theme.dir
     } else {
-        Color::Rgb(180, 180, 180)
+        row.theme.file_color(categorize(&child.name))
     };
     let icon_color = if child.is_dir {
-        Color::Rgb(100, 150, 255)
+        row.theme.dir_icon
     } else {
         Color::Rgb(60, 60, 60)
     };
 
-    let line = Line::from(vec![
+    let mut spans = vec![
         Span::styled(format!(" {} ", icon), Style::default().fg(icon_color)),
         Span::styled(name, Style::default().fg(name_color)),
         Span::styled(
-            format!("{:>9}", size_str),
-            Style::default().fg(Color::Rgb(200, 200, 200)),
+            size_cell(&size_str),
+            Style::default().fg(heat.unwrap_or(Color::Rgb(200, 200, 200))),
         ),
-        Span::styled(
-            format!("  {:>5.1}%", pct),
+    ];
+    if let Some(allocated) = allocated {
+        let (on_disk, slack_str) = if child.unexpanded {
+            ("?".to_string(), "?".to_string())
+        } else {
+            (format_size(allocated), slack(size, allocated))
+        };
+        spans.push(Span::styled(
+            format!("  {:>9}", on_disk),
+            Style::default().fg(Color::Rgb(160, 160, 160)),
+        ));
+        spans.push(Span::styled(
+            format!("  {:>9}", slack_str),
+            Style::default().fg(Color::Rgb(200, 160, 100)),
+        ));
+    }
+    if let Some(now) = row.now {
+        spans.push(Span::styled(
+            format!("  {:>14}", format_relative_time(u64::from(child.mtime), now)),
+            Style::default().fg(Color::Rgb(140, 160, 180)),
+        ));
+    }
+    spans.push(Span::styled(
+        percent_cell(pct, row.decimals),
+        Style::default().fg(Color::Rgb(100, 100, 100)),
+    ));
+    if row.counts {
+        let count = if child.is_dir && !child.unexpanded {
+            format!("({})", format_count(child.children.len() as u64))
+        } else {
+            String::new()
+        };
+        spans.push(Span::styled(
+            format!("  {:>11}", count),
             Style::default().fg(Color::Rgb(100, 100, 100)),
-        ),
-    ]);
+        ));
+    }
+
+    ListItem::new(Line::from(spans))
+}
+
+/// Ages the heatmap's hues stand for: up to a week old is the first, a month
+/// the second, three years and older the third, blending in between on a log
+/// scale.
+const HEAT_AGES: [u64; 3] = [7 * 86_400, 30 * 86_400, 3 * 365 * 86_400];
+
+/// Dimmest an entry gets in the heatmap, as a fraction of full brightness.
+const HEAT_FLOOR: f64 = 0.3;
+
+/// Heatmap color for an entry taking `share` percent of its directory, last
+/// modified at `mtime` (0 if unknown): hue by age from `theme.heat`, and
+/// brightness by size, so a big old entry is a bright red and a small new one
+/// a dim blue.
+fn heat_color(theme: &Theme, share: f64, mtime: u32, now: u64) -> Color {
+    if mtime == 0 {
+        return Color::Rgb(100, 100, 100);
+    }
+    let age = now.saturating_sub(u64::from(mtime)).max(HEAT_AGES[0]) as f64;
+    let log = |secs: u64| (secs as f64).ln();
+    let (from, to, t) = if age < HEAT_AGES[1] as f64 {
+        let t = (age.ln() - log(HEAT_AGES[0])) / (log(HEAT_AGES[1]) - log(HEAT_AGES[0]));
+        (theme.heat[0], theme.heat[1], t)
+    } else {
+        let t = (age.ln() - log(HEAT_AGES[1])) / (log(HEAT_AGES[2]) - log(HEAT_AGES[1]));
+        (theme.heat[1], theme.heat[2], t.min(1.0))
+    };
+    // Square root, so a few percent already reads clearly above the floor
+    let brightness = HEAT_FLOOR + (1.0 - HEAT_FLOOR) * (share / 100.0).sqrt();
+    let (Color::Rgb(r1, g1, b1), Color::Rgb(r2, g2, b2)) = (from, to) else {
+        return from;
+    };
+    let mix = |a: u8, b: u8| ((a as f64 + (b as f64 - a as f64) * t) * brightness) as u8;
+    Color::Rgb(mix(r1, r2), mix(g1, g2), mix(b1, b2))
+}
 
-    ListItem::new(line)
+/// The heatmap's key, for the bottom edge of the list: its hues from new to
+/// old at full brightness.
+fn heat_legend(theme: &Theme) -> Line<'static> {
+    let dim = Style::default().fg(Color::Rgb(120, 120, 120));
+    let mut spans = vec![Span::styled(" new ", dim)];
+    for (i, color) in theme.heat.iter().enumerate() {
+        if i > 0 {
+            spans.push(Span::raw(" "));
+        }
+        spans.push(Span::styled("\u{25a0}", Style::default().fg(*color)));
+    }
+    spans.push(Span::styled(" old, brighter is bigger ", dim));
+    Line::from(spans)
 }
 
 fn draw_footer(f: &mut Frame, area: Rect) {
@@ -284,6 +2628,51 @@ fn draw_footer(f: &mut Frame, area: Rect) {
         Span::styled("s", k),
         Span::styled(" sort", d),
         sp.clone(),
+        Span::styled("/", k),
+        Span::styled(" search", d),
+        sp.clone(),
+        Span::styled("L", k),
+        Span::styled(" largest", d),
+        sp.clone(),
+        Span::styled("b/B", k),
+        Span::styled(" bookmark", d),
+        sp.clone(),
+        Span::styled("c", k),
+        Span::styled(" types", d),
+        sp.clone(),
+        Span::styled("F", k),
+        Span::styled(" biggest file", d),
+        sp.clone(),
+        Span::styled("t", k),
+        Span::styled(" treemap", d),
+        sp.clone(),
+        Span::styled("y", k),
+        Span::styled(" copy sizes", d),
+        sp.clone(),
+        Span::styled("o", k),
+        Span::styled(" own size", d),
+        sp.clone(),
+        Span::styled("a", k),
+        Span::styled(" on disk", d),
+        sp.clone(),
+        Span::styled("n", k),
+        Span::styled(" counts", d),
+        sp.clone(),
+        Span::styled("%", k),
+        Span::styled(" of root", d),
+        sp.clone(),
+        Span::styled("h", k),
+        Span::styled(" heatmap", d),
+        sp.clone(),
+        Span::styled("d", k),
+        Span::styled(" delete", d),
+        sp.clone(),
+        Span::styled("C", k),
+        Span::styled(" caches", d),
+        sp.clone(),
+        Span::styled("R", k),
+        Span::styled(" rescan", d),
+        sp.clone(),
         Span::styled("q", k),
         Span::styled(" quit", d),
     ]);
@@ -385,8 +2774,8 @@ pub fn draw_start_screen(f: &mut Frame, selected: usize, menu_items: &[&str]) {
     f.render_widget(Paragraph::new(lines), area);
 }
 
-pub fn draw_path_input(f: &mut Frame, input: &str) {
-    let area = centered_rect(50, 30, f.area());
+pub fn draw_path_input(f: &mut Frame, input: &PathInput) {
+    let area = centered_rect(50, 40, f.area());
 
     let block = Block::default()
         .title(" scan directory ")
@@ -396,7 +2785,7 @@ pub fn draw_path_input(f: &mut Frame, input: &str) {
     let inner = block.inner(area);
     f.render_widget(block, area);
 
-    let top = inner.height.saturating_sub(5) / 2;
+    let top = inner.height.saturating_sub(6 + MAX_MATCHES as u16) / 2;
     let mut lines: Vec<Line> = (0..top).map(|_| Line::from("")).collect();
 
     lines.push(Line::from(Span::styled(
@@ -405,11 +2794,14 @@ pub fn draw_path_input(f: &mut Frame, input: &str) {
     )));
     lines.push(Line::from(""));
 
+    let text = input.text.as_str();
     let field_width = (inner.width as usize).saturating_sub(2);
-    let display_input = if input.len() > field_width.saturating_sub(1) {
-        &input[input.len() - field_width.saturating_sub(1)..]
+    let keep = field_width.saturating_sub(1);
+    let display_input = if text.chars().count() > keep {
+        let skip = text.chars().count() - keep;
+        text.char_indices().nth(skip).map_or(text, |(i, _)| &text[i..])
     } else {
-        input
+        text
     };
 
     lines.push(Line::from(vec![
@@ -418,20 +2810,59 @@ pub fn draw_path_input(f: &mut Frame, input: &str) {
         Span::styled("█", Style::default().fg(Color::Rgb(100, 200, 255))),
     ]));
 
+    // Matching subdirectories, padded so the hint line doesn't jump around
+    let mut shown = 0;
+    for name in input.matches().take(MAX_MATCHES) {
+        lines.push(Line::from(Span::styled(
+            format!("   + {}", name),
+            Style::default().fg(Color::Rgb(120, 170, 255)),
+        )));
+        shown += 1;
+    }
+    if shown == 0 && input.is_listing() {
+        lines.push(Line::from(Span::styled(
+            "   listing…",
+            Style::default().fg(Color::Rgb(60, 60, 60)),
+        )));
+        shown += 1;
+    }
+    lines.extend((shown..MAX_MATCHES).map(|_| Line::from("")));
+
     lines.push(Line::from(""));
     lines.push(Line::from(Span::styled(
-        " enter confirm  esc cancel",
+        " tab complete  enter confirm  esc cancel",
         Style::default().fg(Color::Rgb(60, 60, 60)),
     )));
 
     f.render_widget(Paragraph::new(lines), inner);
 }
 
-pub fn draw_drive_picker(f: &mut Frame, drives: &[DriveInfo], selected: usize) {
+/// Frames of the spinner shown on drive picker rows still waiting for their
+/// space.
+const SPINNER: &[char] = &['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
+/// The drive picker. Rows whose path is in `pending` are still being asked
+/// for their space and show frame `spin` of a spinner instead.
+pub fn draw_drive_picker(
+    f: &mut Frame,
+    drives: &[DriveInfo],
+    pending: &HashSet<String>,
+    spin: usize,
+    selected: usize,
+    sort: DriveSort,
+) {
     let area = centered_rect(60, 70, f.area());
 
+    let noun = if cfg!(windows) { "drive" } else { "volume" };
+    let title = match sort {
+        DriveSort::Detected => format!(" select {} ", noun),
+        DriveSort::Free => format!(" select {}  [most free] ", noun),
+        DriveSort::Total => format!(" select {}  [largest] ", noun),
+        DriveSort::Used => format!(" select {}  [fullest] ", noun),
+        DriveSort::Path => format!(" select {}  [path] ", noun),
+    };
     let block = Block::default()
-        .title(if cfg!(windows) { " select drive " } else { " select volume " })
+        .title(title)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Rgb(70, 70, 70)));
 
@@ -452,12 +2883,16 @@ pub fn draw_drive_picker(f: &mut Frame, drives: &[DriveInfo], selected: usize) {
             let pct = percent(used, drive.total);
 
             let left = format!(" {}  ", drive.path);
-            let right = format!(
-                "{}  /  {}   {:>5.1}%",
-                format_size(used),
-                format_size(drive.total),
-                pct
-            );
+            let right = if pending.contains(&drive.path) {
+                SPINNER[spin % SPINNER.len()].to_string()
+            } else {
+                format!(
+                    "{}  /  {}   {:>5.1}%",
+                    format_size(used),
+                    format_size(drive.total),
+                    pct
+                )
+            };
             let gap = available_width
                 .saturating_sub(left.chars().count() + right.chars().count());
 
@@ -489,9 +2924,50 @@ pub fn draw_drive_picker(f: &mut Frame, drives: &[DriveInfo], selected: usize) {
 
     f.render_widget(
         Paragraph::new(Line::from(Span::styled(
-            " enter scan  j/k nav  q quit",
+            " enter scan  j/k nav  s sort  q quit",
             Style::default().fg(Color::Rgb(60, 60, 60)),
         ))),
         chunks[1],
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    /// Poll `input` until its listing arrives.
+    fn wait_for_listing(input: &mut PathInput) {
+        let started = Instant::now();
+        while input.is_listing() {
+            assert!(started.elapsed() < Duration::from_secs(10), "listing never arrived");
+            thread::sleep(Duration::from_millis(5));
+            input.poll();
+        }
+    }
+
+    #[test]
+    fn path_input_lists_in_the_background() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["alpha", "alps", "beta"] {
+            std::fs::create_dir(dir.path().join(name)).unwrap();
+        }
+        std::fs::write(dir.path().join("alfile"), "").unwrap();
+
+        let mut input = PathInput::default();
+        let typed = format!("{}{}al", dir.path().display(), std::path::MAIN_SEPARATOR);
+        typed.chars().for_each(|c| input.push(c));
+        // Tab before the listing is in: completes once it arrives
+        input.complete();
+        wait_for_listing(&mut input);
+        assert!(input.text.ends_with("alp"), "{}", input.text);
+        assert_eq!(input.matches().collect::<Vec<_>>(), ["alpha", "alps"]);
+
+        input.push('h');
+        input.complete();
+        assert!(input.text.ends_with(&format!("alpha{}", std::path::MAIN_SEPARATOR)));
+        assert!(input.is_listing());
+        wait_for_listing(&mut input);
+        assert_eq!(input.matches().count(), 0);
+    }
+}
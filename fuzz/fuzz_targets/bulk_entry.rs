@@ -0,0 +1,19 @@
+//! Feed arbitrary bytes to the macOS getattrlistbulk parser, which must
+//! reject a malformed or truncated batch rather than panic.
+//!
+//! ```sh
+//! cargo +nightly fuzz run bulk_entry
+//! ```
+//!
+//! macOS only, like the scanner it exercises.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    #[cfg(target_os = "macos")]
+    disku_core::mac_scanner::fuzz_bulk_batch(data);
+    #[cfg(not(target_os = "macos"))]
+    let _ = data;
+});
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
@@ -6,22 +7,72 @@ use serde::Serialize;
 use tauri::ipc::Channel;
 use tauri::State;
 
+use disku_core::config::Config;
+use disku_core::delete::{remove_path, DeleteMode, TRASH_SUPPORTED};
+use disku_core::guide;
 use disku_core::scanner::ScanProgress;
-use disku_core::tree::{self, FileNode};
-use disku_core::utils::{self, DriveInfo};
+use disku_core::tree::{self, FileNode, ScanResult};
+use disku_core::utils::{self, is_protected_path, DriveInfo};
 
 pub struct AppState {
-    pub scan_result: Arc<Mutex<Option<FileNode>>>,
+    pub scan_result: Arc<Mutex<Option<ScanResult>>>,
+    /// The order each viewed directory's children are in, by nav path: `true`
+    /// for size, `false` for name. Lets a directory skip re-sorting when it's
+    /// viewed again in the same order. Lock after `scan_result`; cleared
+    /// whenever the tree is replaced or changed.
+    pub view_sorts: Mutex<HashMap<Vec<usize>, bool>>,
+    /// `ui.group_below` from the config: the percent of a directory under
+    /// which its entries are grouped by `get_directory_view`.
+    pub group_below: Option<f64>,
+    /// `cleanup.protected_paths` from the config: refused by the deletes
+    /// along with the system paths [`is_protected_path`] knows.
+    pub protected_paths: Vec<PathBuf>,
+    /// `cleanup.cache_names` from the config, picked out by `get_disk_guide`.
+    pub cache_names: Vec<String>,
+    /// Why the config file was last ignored in favour of the defaults, until
+    /// it loads again; see [`get_config_error`].
+    pub config_error: Mutex<Option<String>>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
+        let config_error = Mutex::new(None);
+        let config = load_config(&config_error);
         Self {
             scan_result: Arc::new(Mutex::new(None)),
+            view_sorts: Mutex::new(HashMap::new()),
+            group_below: config.ui.group_below,
+            protected_paths: config.cleanup.protected_paths,
+            cache_names: config.cleanup.cache_names,
+            config_error,
         }
     }
 }
 
+/// Load the config file. One that can't be read or parsed shouldn't stop
+/// the app, so like the TUI this carries on with the defaults, recording why
+/// in `error` for the front end to show.
+fn load_config(error: &Mutex<Option<String>>) -> Config {
+    let loaded = Config::load();
+    let mut error = error.lock().unwrap_or_else(|e| e.into_inner());
+    match loaded {
+        Ok(config) => {
+            *error = None;
+            config
+        }
+        Err(e) => {
+            *error = Some(format!("ignoring config file: {}", e));
+            Config::default()
+        }
+    }
+}
+
+/// Why the config file was ignored the last time it was loaded, if it was.
+#[tauri::command]
+pub fn get_config_error(state: State<'_, AppState>) -> Option<String> {
+    state.config_error.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
 #[derive(Clone, Serialize)]
 #[serde(tag = "kind")]
 pub enum ScanEvent {
@@ -30,10 +81,35 @@ pub enum ScanEvent {
         dirs_scanned: u64,
         errors: u64,
         current_path: String,
+        /// No entries have been counted for [`STALL_AFTER`], as happens on a
+        /// hung network mount; `current_path` is where it's stuck.
+        stalled: bool,
+    },
+    /// The scan finished; totals for an immediate summary.
+    Complete {
+        total_size: u64,
+        file_count: u64,
+        dir_count: u64,
+        errors: u64,
+        elapsed_ms: u64,
+        /// Why the fastest scanner couldn't be used, when the user can fix
+        /// it, or else set when nothing in the tree stands out; see
+        /// [`tree::nothing_big`].
+        note: Option<String>,
+        /// When a whole drive was scanned, how much of its used space the
+        /// tree doesn't account for, apart from `purgeable`; see
+        /// [`utils::volume_usage`].
+        unaccounted: Option<u64>,
+        /// When a whole drive was scanned, how much of its used space macOS
+        /// can reclaim by itself; see [`utils::purgeable_space`].
+        purgeable: Option<u64>,
     },
-    Complete,
 }
 
+/// How long the entry count may stand still before a scan is reported as
+/// possibly stalled.
+const STALL_AFTER: std::time::Duration = std::time::Duration::from_secs(10);
+
 #[derive(Serialize)]
 pub struct DeleteResult {
     pub path: String,
@@ -42,57 +118,15 @@ pub struct DeleteResult {
     pub bytes_freed: u64,
 }
 
-/// Check whether a path is a protected system location that must not be deleted.
-fn is_protected_path(path: &Path) -> bool {
-    let path_str = path.to_string_lossy();
-    // Normalise to lowercase backslash form for Windows comparison.
-    let norm = path_str.replace('/', "\\").to_lowercase();
-    let norm = norm.trim_end_matches('\\');
-
-    // Drive roots  (C:\, D:\, …)
-    if norm.len() == 2 && norm.as_bytes()[1] == b':' {
-        return true;
-    }
-
-    let blocked: &[&str] = &[
-        r"c:\windows",
-        r"c:\program files",
-        r"c:\program files (x86)",
-        r"c:\programdata",
-        r"c:\recovery",
-        r"c:\$recycle.bin",
-        r"c:\boot",
-        r"c:\bootmgr",
-        r"c:\efi",
-        r"c:\users",
-    ];
-
-    for &b in blocked {
-        if norm == b || norm.starts_with(&format!("{b}\\")) {
-            // Allow items *inside* a user profile (C:\Users\<name>\…), but
-            // block C:\Users itself and the profile root (C:\Users\<name>).
-            if b == r"c:\users" {
-                // Count segments after "C:\Users\"
-                let after = &norm[r"c:\users\".len()..];
-                // If there are at least two more segments (profile + something inside),
-                // the path is inside a profile → allow it.
-                if after.contains('\\') {
-                    return false;
-                }
-                return true;
-            }
-            return true;
-        }
-    }
-
-    false
-}
-
 #[derive(Serialize)]
 pub struct DirectoryEntry {
     pub name: String,
     pub size: u64,
+    pub allocated: u64,
     pub is_dir: bool,
+    pub compressed: bool,
+    /// A trash folder collapsed into one entry; see [`FileNode::trash`].
+    pub trash: bool,
     pub has_children: bool,
 }
 
@@ -100,18 +134,100 @@ pub struct DirectoryEntry {
 pub struct DirectoryView {
     pub path: String,
     pub total_size: u64,
+    pub total_allocated: u64,
+    /// Index of the first of `entries` among all the directory's children.
+    pub offset: usize,
     pub entries: Vec<DirectoryEntry>,
+    /// Number of children in the directory, of which `entries` is one page.
+    /// Children in `grouped` aren't counted.
     pub item_count: usize,
+    /// The small children listed after the others, when they're grouped into
+    /// one entry rather than listed.
+    pub grouped: Option<GroupedEntries>,
 }
 
+/// Children of a directory too small to list one by one; see
+/// [`tree::small_tail`].
+#[derive(Serialize)]
+pub struct GroupedEntries {
+    pub count: usize,
+    pub size: u64,
+    pub allocated: u64,
+}
+
+impl From<&FileNode> for DirectoryEntry {
+    fn from(child: &FileNode) -> Self {
+        DirectoryEntry {
+            name: child.name.to_string(),
+            size: child.size,
+            allocated: child.allocated,
+            is_dir: child.is_dir,
+            compressed: child.compressed,
+            trash: child.trash,
+            has_children: child.is_dir && !child.children.is_empty(),
+        }
+    }
+}
+
+/// A window of a directory's children, for virtualized lists.
+#[derive(Serialize)]
+pub struct SubtreeChunk {
+    pub offset: usize,
+    /// Total number of children, so the front-end can size its scrollbar.
+    pub total: usize,
+    pub entries: Vec<DirectoryEntry>,
+}
+
+/// An entry anywhere in the scanned tree, with its path relative to the root.
+#[derive(Serialize)]
+pub struct RankedEntry {
+    pub path: String,
+    pub size: u64,
+}
+
+/// One heading of the "why is my disk full" summary; see [`guide::analyze`].
+#[derive(Serialize)]
+pub struct GuideFinding {
+    pub label: &'static str,
+    pub advice: &'static str,
+    /// Everything under this heading can be deleted without losing anything.
+    pub safe: bool,
+    pub size: u64,
+    pub entries: Vec<GuideEntry>,
+}
+
+/// One of the biggest entries under a [`GuideFinding`].
+#[derive(Serialize)]
+pub struct GuideEntry {
+    /// Absolute path of the directory the entry is in.
+    pub dir: String,
+    pub name: String,
+    pub size: u64,
+    pub is_dir: bool,
+}
+
+/// The drives' paths, without waiting on any of them for their space; the
+/// picker shows these at once and asks [`get_drive_info`] for each.
+#[tauri::command]
+pub fn get_drive_paths() -> Vec<String> {
+    utils::detect_drive_paths()
+}
+
+/// Total and free space of the drive at `path`, or `None` for one the picker
+/// should leave out. Runs off the async runtime's workers, since a hung
+/// network volume can block it indefinitely.
 #[tauri::command]
-pub fn get_drives() -> Vec<DriveInfo> {
-    utils::detect_drives()
+pub async fn get_drive_info(path: String) -> Option<DriveInfo> {
+    tauri::async_runtime::spawn_blocking(move || utils::drive_info(&path))
+        .await
+        .ok()
+        .flatten()
 }
 
 #[tauri::command]
 pub fn validate_path(path: String) -> bool {
-    PathBuf::from(&path).is_dir()
+    // Single files are valid scan targets too; they produce a one-node tree.
+    PathBuf::from(&path).exists()
 }
 
 #[tauri::command]
@@ -124,35 +240,42 @@ pub fn start_scan(
     {
         let mut result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
         *result = None;
+        state.view_sorts.lock().unwrap_or_else(|e| e.into_inner()).clear();
     }
 
-    let scan_path = PathBuf::from(&path);
+    let scan_path = utils::canonical_root(Path::new(&path));
     let progress = ScanProgress::new();
-    let files_counter = progress.files_scanned.clone();
-    let errors_counter = progress.errors.clone();
+    let options = load_config(&state.config_error).scan_options();
 
     let on_event_progress = on_event.clone();
-    let current_path = progress.current_path.clone();
-    let dirs_counter = progress.dirs_scanned.clone();
     let scan_done = Arc::new(AtomicBool::new(false));
     let done_flag = scan_done.clone();
 
     // Spawn progress reporter
-    let files_for_progress = files_counter.clone();
-    let dirs_for_progress = dirs_counter.clone();
-    let errors_for_progress = errors_counter.clone();
+    let reporter = progress.clone();
     let progress_handle = std::thread::spawn(move || {
+        let mut last_count = 0;
+        let mut moved_at = std::time::Instant::now();
         loop {
             std::thread::sleep(std::time::Duration::from_millis(100));
-            let files = files_for_progress.load(Ordering::Relaxed);
-            let dirs = dirs_for_progress.load(Ordering::Relaxed);
-            let errors = errors_for_progress.load(Ordering::Relaxed);
-            let cp = current_path.lock().unwrap_or_else(|e| e.into_inner()).clone();
+            let files = reporter.files_scanned.load(Ordering::Relaxed);
+            let dirs = reporter.dirs_scanned.load(Ordering::Relaxed);
+            if files + dirs != last_count {
+                last_count = files + dirs;
+                moved_at = std::time::Instant::now();
+            }
+            let errors = reporter.errors.load(Ordering::Relaxed);
+            let cp = reporter
+                .current_path
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone();
             let _ = on_event_progress.send(ScanEvent::Progress {
                 files_scanned: files,
                 dirs_scanned: dirs,
                 errors,
                 current_path: cp,
+                stalled: moved_at.elapsed() >= STALL_AFTER,
             });
             if done_flag.load(Ordering::Relaxed) {
                 break;
@@ -164,147 +287,283 @@ pub fn start_scan(
     let scan_result = state.scan_result.clone();
 
     std::thread::spawn(move || {
-        let p = ScanProgress {
-            files_scanned: files_counter,
-            dirs_scanned: dirs_counter,
-            errors: errors_counter,
-            current_path: progress.current_path.clone(),
-        };
-
-        let root = {
-            #[cfg(windows)]
-            {
-                let path_str = scan_path.to_string_lossy();
-                if path_str.len() >= 2 && path_str.as_bytes()[1] == b':' {
-                    let drive_letter = match path_str.chars().next() {
-                        Some(c) => c,
-                        None => return,
-                    };
-                    if let Some(root) = disku_core::mft_scanner::scan_mft(drive_letter, &p) {
-                        root
-                    } else {
-                        disku_core::scanner::scan(&scan_path, &p)
-                    }
-                } else {
-                    disku_core::scanner::scan(&scan_path, &p)
-                }
-            }
-
-            #[cfg(target_os = "macos")]
-            {
-                disku_core::mac_scanner::scan_bulk(&scan_path, &p)
-            }
-
-            #[cfg(all(not(windows), not(target_os = "macos")))]
-            {
-                disku_core::scanner::scan(&scan_path, &p)
-            }
-        };
+        let p = progress;
+        let scan = disku_core::scanner::scan_root(&scan_path, &p, &options, None);
+
+        let total_size = scan.tree.size;
+        let (file_count, dir_count, errors) = (scan.files, scan.dirs, scan.errors);
+        let elapsed_ms = scan.elapsed.as_millis() as u64;
+        // Showing why the scan was slow matters more than that nothing
+        // stood out
+        let note = scan
+            .hint
+            .or_else(|| tree::nothing_big(&scan.tree))
+            .map(str::to_string);
+        let whole_drive = utils::detect_drive_paths()
+            .iter()
+            .any(|path| utils::canonical_root(Path::new(path)) == scan_path);
+        let purgeable = whole_drive.then(|| utils::purgeable_space(&scan_path)).flatten();
+        let unaccounted = whole_drive
+            .then(|| utils::volume_usage(&scan_path))
+            .flatten()
+            .map(|used| used.saturating_sub(scan.tree.allocated))
+            .map(|missing| missing.saturating_sub(purgeable.unwrap_or(0)));
 
         // Store result
-        {
-            let mut result = scan_result.lock().unwrap_or_else(|e| e.into_inner());
-            *result = Some(root);
-        }
+        *scan_result.lock().unwrap_or_else(|e| e.into_inner()) = Some(scan);
 
         // Signal progress reporter to stop
         scan_done.store(true, Ordering::Relaxed);
         let _ = progress_handle.join();
 
         // Send complete event
-        let _ = on_event.send(ScanEvent::Complete);
+        let _ = on_event.send(ScanEvent::Complete {
+            total_size,
+            file_count,
+            dir_count,
+            errors,
+            elapsed_ms,
+            note,
+            unaccounted,
+            purgeable,
+        });
     });
 }
 
+/// Sort the children of the directory at `nav_path` by size or by name,
+/// unless `sorts` records them as already in that order. Only that one level
+/// is sorted: a directory is sorted when it's viewed, rather than the whole
+/// tree on every request, which takes seconds on a big scan.
+fn sort_for_view(
+    tree: &mut FileNode,
+    nav_path: &[usize],
+    by_size: bool,
+    sorts: &mut HashMap<Vec<usize>, bool>,
+) -> Option<()> {
+    let mut node = tree;
+    for &idx in nav_path {
+        node = node.children.get_mut(idx)?;
+    }
+    if sorts.get(nav_path) != Some(&by_size) {
+        if by_size {
+            node.sort_children_by_size();
+        } else {
+            node.sort_children_by_name();
+        }
+        // Directories below this one have moved, so their nav paths are stale
+        sorts.retain(|path, _| path.len() <= nav_path.len() || !path.starts_with(nav_path));
+        sorts.insert(nav_path.to_vec(), by_size);
+    }
+    Some(())
+}
+
+/// The directory at `nav_path` with up to `limit` of its children, sorted by
+/// size or name, starting at `offset`. `item_count` is the total, so a huge
+/// directory can be fetched a page at a time. With `group_small`, sorted by
+/// size and `ui.group_below` configured, the children under that share of
+/// the directory are left out and summed up in `grouped` instead; asking
+/// again without it lists them.
 #[tauri::command]
 pub fn get_directory_view(
     nav_path: Vec<usize>,
     sort_by_size: bool,
+    group_small: bool,
+    offset: usize,
+    limit: usize,
     state: State<'_, AppState>,
 ) -> Option<DirectoryView> {
     let mut result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
-    let root = result.as_mut()?;
-
-    // Apply sort
-    if sort_by_size {
-        root.sort_by_size();
-    } else {
-        root.sort_by_name();
-    }
-
-    // Navigate to the requested node
-    let mut node = &*root;
-    let mut path_parts = vec![node.name.clone()];
-    for &idx in &nav_path {
-        if idx < node.children.len() {
-            node = &node.children[idx];
-            path_parts.push(node.name.clone());
-        } else {
-            return None;
+    let scan = result.as_mut()?;
+    let mut sorts = state.view_sorts.lock().unwrap_or_else(|e| e.into_inner());
+    sort_for_view(&mut scan.tree, &nav_path, sort_by_size, &mut sorts)?;
+
+    let node = scan.node_at(&nav_path)?;
+    let path = scan.path_of(&nav_path)?;
+
+    let group_start = state
+        .group_below
+        .filter(|_| group_small && sort_by_size)
+        .and_then(|percent| tree::small_tail(node, percent));
+    let item_count = group_start.unwrap_or(node.children.len());
+    let start = offset.min(item_count);
+    let end = start.saturating_add(limit).min(item_count);
+    let entries: Vec<DirectoryEntry> =
+        node.children[start..end].iter().map(DirectoryEntry::from).collect();
+    let grouped = group_start.map(|first| {
+        let small = &node.children[first..];
+        GroupedEntries {
+            count: small.len(),
+            size: tree::saturating_sum(small.iter().map(|c| c.size)),
+            allocated: tree::saturating_sum(small.iter().map(|c| c.allocated)),
         }
-    }
-
-    let entries: Vec<DirectoryEntry> = node
-        .children
-        .iter()
-        .map(|child| DirectoryEntry {
-            name: child.name.clone(),
-            size: child.size,
-            is_dir: child.is_dir,
-            has_children: child.is_dir && !child.children.is_empty(),
-        })
-        .collect();
-
-    let item_count = entries.len();
+    });
 
     Some(DirectoryView {
-        path: path_parts.join(std::path::MAIN_SEPARATOR_STR),
+        path: path.to_string_lossy().to_string(),
         total_size: node.size,
+        total_allocated: node.allocated,
+        offset: start,
         entries,
         item_count,
+        grouped,
+    })
+}
+
+/// Return `limit` children of the directory at `nav_path` starting at `offset`,
+/// in the order set by the last `get_directory_view`. Lets huge directories be
+/// fetched a window at a time instead of serializing every entry at once.
+#[tauri::command]
+pub fn get_subtree_chunk(
+    nav_path: Vec<usize>,
+    offset: usize,
+    limit: usize,
+    state: State<'_, AppState>,
+) -> Option<SubtreeChunk> {
+    let result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
+    let node = result.as_ref()?.node_at(&nav_path)?;
+
+    let total = node.children.len();
+    let start = offset.min(total);
+    let end = start.saturating_add(limit).min(total);
+    Some(SubtreeChunk {
+        offset: start,
+        total,
+        entries: node.children[start..end].iter().map(DirectoryEntry::from).collect(),
     })
 }
 
+#[tauri::command]
+pub fn get_largest_dirs(count: usize, state: State<'_, AppState>) -> Vec<RankedEntry> {
+    let result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(scan) = result.as_ref() else {
+        return vec![];
+    };
+
+    tree::largest_dirs(&scan.tree, count)
+        .into_iter()
+        .map(|(path, size)| RankedEntry { path, size })
+        .collect()
+}
+
+/// Entries matching a glob over root-relative paths (see [`tree::glob`]),
+/// largest first. Errors say why the pattern doesn't parse.
+#[tauri::command]
+pub fn glob_entries(
+    pattern: String,
+    min_size: u64,
+    state: State<'_, AppState>,
+) -> Result<Vec<RankedEntry>, String> {
+    let result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(scan) = result.as_ref() else {
+        return Ok(vec![]);
+    };
+
+    let found = tree::glob(&scan.tree, &pattern, min_size)
+        .map_err(|e| format!("invalid glob: {}", e.kind()))?;
+    Ok(found
+        .into_iter()
+        .map(|(path, size)| RankedEntry { path, size })
+        .collect())
+}
+
+#[tauri::command]
+pub fn get_compression_summary(
+    nav_path: Vec<usize>,
+    state: State<'_, AppState>,
+) -> Option<tree::CompressionSummary> {
+    let result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
+    let node = result.as_ref()?.node_at(&nav_path)?;
+    Some(tree::compression_summary(node))
+}
+
+#[tauri::command]
+pub fn get_category_breakdown(
+    nav_path: Vec<usize>,
+    state: State<'_, AppState>,
+) -> Option<Vec<tree::CategoryTotal>> {
+    let result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
+    let node = result.as_ref()?.node_at(&nav_path)?;
+    Some(tree::aggregate_by_category(node))
+}
+
+/// Where the scanned tree's space went, under plain-language headings with
+/// their biggest entries, largest first; see [`guide::analyze`].
+#[tauri::command]
+pub fn get_disk_guide(state: State<'_, AppState>) -> Vec<GuideFinding> {
+    let result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(scan) = result.as_ref() else {
+        return vec![];
+    };
+
+    guide::analyze(&scan.tree, &state.cache_names)
+        .into_iter()
+        .map(|finding| GuideFinding {
+            label: finding.kind.label(),
+            advice: finding.kind.advice(),
+            safe: finding.kind.safe(),
+            size: finding.size,
+            entries: finding
+                .top
+                .iter()
+                .filter_map(|(nav_path, size)| {
+                    let node = scan.node_at(nav_path)?;
+                    let dir = scan.path_of(&nav_path[..nav_path.len() - 1])?;
+                    Some(GuideEntry {
+                        dir: dir.to_string_lossy().to_string(),
+                        name: node.name.to_string(),
+                        size: *size,
+                        is_dir: node.is_dir,
+                    })
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Whether deletes can go to the recycle bin / Trash here; when not, the
+/// confirmation warns that they will be permanent.
+#[tauri::command]
+pub fn trash_supported() -> bool {
+    TRASH_SUPPORTED
+}
+
 #[tauri::command]
 pub fn delete_entries(
     nav_path: Vec<usize>,
     entry_indices: Vec<usize>,
     sort_by_size: bool,
+    permanent: bool,
     state: State<'_, AppState>,
 ) -> Vec<DeleteResult> {
+    let mode = DeleteMode::new(permanent);
     let mut result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
-    let Some(root) = result.as_mut() else {
+    let Some(scan) = result.as_mut() else {
         return vec![];
     };
+    let root_abs = scan.root_path.clone();
+    let root = &mut scan.tree;
 
     // Apply the same sort so indices match the frontend view.
-    if sort_by_size {
-        root.sort_by_size();
-    } else {
-        root.sort_by_name();
+    let mut sorts = state.view_sorts.lock().unwrap_or_else(|e| e.into_inner());
+    if sort_for_view(root, &nav_path, sort_by_size, &mut sorts).is_none() {
+        return vec![];
     }
 
-    // Resolve the absolute path of the root for later joining.
-    let root_abs = PathBuf::from(&root.name);
-
     // Navigate to the parent node described by nav_path.
-    let mut node_path = root_abs.clone();
+    let Some(node_path) = tree::path_of(root, &root_abs, &nav_path) else {
+        return vec![];
+    };
     let mut parent = &mut *root;
     for &idx in &nav_path {
-        if idx >= parent.children.len() {
-            return vec![];
-        }
-        node_path = node_path.join(&parent.children[idx].name);
         parent = &mut parent.children[idx];
     }
 
     // Collect child names + sizes for the requested indices (resolve before mutating).
     let mut targets: Vec<(String, u64, PathBuf)> = Vec::new();
     for &idx in &entry_indices {
-        if idx < parent.children.len() {
+        if let Some(abs) = tree::path_of(parent, &node_path, &[idx]) {
             let child = &parent.children[idx];
-            let abs = node_path.join(&child.name);
-            targets.push((child.name.clone(), child.size, abs));
+            targets.push((child.name.to_string(), child.size, abs));
         }
     }
 
@@ -313,7 +572,7 @@ pub fn delete_entries(
     let mut deleted_names: Vec<(String, u64)> = Vec::new();
 
     for (name, size, abs_path) in &targets {
-        if is_protected_path(abs_path) {
+        if is_protected_path(abs_path, &state.protected_paths) {
             results.push(DeleteResult {
                 path: abs_path.to_string_lossy().to_string(),
                 success: false,
@@ -323,11 +582,7 @@ pub fn delete_entries(
             continue;
         }
 
-        let delete_result = if abs_path.is_dir() {
-            std::fs::remove_dir_all(abs_path)
-        } else {
-            std::fs::remove_file(abs_path)
-        };
+        let delete_result = remove_path(abs_path, mode);
 
         match delete_result {
             Ok(()) => {
@@ -352,6 +607,7 @@ pub fn delete_entries(
 
     // Phase 2: mutate the tree — remove children and propagate sizes.
     if !deleted_names.is_empty() {
+        sorts.clear();
         let total_freed: u64 = deleted_names.iter().map(|(_, s)| *s).sum();
 
         // Navigate again to the parent and remove children.
@@ -393,7 +649,7 @@ pub fn delete_entries(
         // Walk root → parent, setting each node's size to sum of children.
         fn resum(node: &mut FileNode) {
             if node.is_dir {
-                node.size = node.children.iter().map(|c| c.size).sum();
+                node.update_totals();
             }
         }
         resum(root);
@@ -412,20 +668,16 @@ pub fn delete_entries(
 #[tauri::command]
 pub fn delete_entries_by_path(
     paths: Vec<String>,
-    sort_by_size: bool,
+    permanent: bool,
     state: State<'_, AppState>,
 ) -> Vec<DeleteResult> {
+    let mode = DeleteMode::new(permanent);
     let mut result = state.scan_result.lock().unwrap_or_else(|e| e.into_inner());
-    let Some(root) = result.as_mut() else {
+    let Some(scan) = result.as_mut() else {
         return vec![];
     };
-
-    // Apply the same sort so tree indices match.
-    if sort_by_size {
-        root.sort_by_size();
-    } else {
-        root.sort_by_name();
-    }
+    let root_path = scan.root_path.clone();
+    let root = &mut scan.tree;
 
     let mut results = Vec::with_capacity(paths.len());
     let mut deleted_paths: Vec<PathBuf> = Vec::new();
@@ -434,7 +686,7 @@ pub fn delete_entries_by_path(
     for path_str in &paths {
         let abs_path = PathBuf::from(path_str);
 
-        if is_protected_path(&abs_path) {
+        if is_protected_path(&abs_path, &state.protected_paths) {
             results.push(DeleteResult {
                 path: path_str.clone(),
                 success: false,
@@ -444,11 +696,7 @@ pub fn delete_entries_by_path(
             continue;
         }
 
-        let delete_result = if abs_path.is_dir() {
-            std::fs::remove_dir_all(&abs_path)
-        } else {
-            std::fs::remove_file(&abs_path)
-        };
+        let delete_result = remove_path(&abs_path, mode);
 
         match delete_result {
             Ok(()) => {
@@ -492,7 +740,7 @@ pub fn delete_entries_by_path(
 
     for (parent_path, name_paths) in &by_parent {
         // Find nav_path for parent directory
-        let Some(nav_indices) = tree::find_nav_path(root, parent_path) else {
+        let Some(nav_indices) = tree::find_nav_path_under(root, &root_path, parent_path) else {
             continue; // path not in tree, skip
         };
 
@@ -524,13 +772,17 @@ pub fn delete_entries_by_path(
         }
     }
 
+    if !deleted_paths.is_empty() {
+        state.view_sorts.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+
     // Phase 3: recalculate sizes from root down through all affected branches.
     fn resum_recursive(node: &mut FileNode) {
         if node.is_dir {
             for child in node.children.iter_mut() {
                 resum_recursive(child);
             }
-            node.size = node.children.iter().map(|c| c.size).sum();
+            node.update_totals();
         }
     }
     resum_recursive(root);
@@ -81,12 +81,21 @@ pub fn run() {
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
-            commands::get_drives,
+            commands::get_drive_paths,
+            commands::get_drive_info,
             commands::start_scan,
             commands::get_directory_view,
+            commands::get_subtree_chunk,
+            commands::get_largest_dirs,
+            commands::glob_entries,
+            commands::get_compression_summary,
+            commands::get_category_breakdown,
+            commands::get_disk_guide,
             commands::validate_path,
+            commands::trash_supported,
             commands::delete_entries,
             commands::delete_entries_by_path,
+            commands::get_config_error,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
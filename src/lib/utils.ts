@@ -10,3 +10,23 @@ export function formatSize(bytes: number): string {
   if (bytes >= KB) return (bytes / KB).toFixed(1) + " KB";
   return bytes + " B";
 }
+
+export function joinPath(dirPath: string, name: string): string {
+  if (dirPath.endsWith("\\") || dirPath.endsWith("/")) {
+    return dirPath + name;
+  }
+  const sep = dirPath.includes("/") ? "/" : "\\";
+  return dirPath + sep + name;
+}
+
+export function formatDuration(ms: number): string {
+  if (!Number.isFinite(ms) || ms < 0) ms = 0;
+  if (ms < 1000) return Math.round(ms) + "ms";
+  const secs = ms / 1000;
+  if (secs < 60) return secs.toFixed(1) + "s";
+  const whole = Math.round(secs);
+  const h = Math.floor(whole / 3600);
+  const m = Math.floor((whole % 3600) / 60);
+  const s = whole % 60;
+  return h > 0 ? `${h}h ${m}m ${s}s` : `${m}m ${s}s`;
+}